@@ -58,6 +58,7 @@ mod tests {
     use crate::{antenna::{AntennaPolarization, AntennaProperties}, util::swr};
 
     #[test]
+    #[allow(unused_variables)]
     fn test_dipole_swr_sim() {
         let f_lower: f64 = 14.1e6;
         let f_upper: f64 = 15e6;
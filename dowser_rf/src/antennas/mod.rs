@@ -1 +1,2 @@
-mod dipole;
\ No newline at end of file
+pub(crate) mod dipole;
+pub mod wire;
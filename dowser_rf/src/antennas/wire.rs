@@ -0,0 +1,152 @@
+use nalgebra::Vector3;
+use num_complex::Complex;
+
+use crate::{
+    antenna::{AntennaModel, AntennaProperties, ModeledAntenna},
+    mom::{
+        excitation::VoltageSource,
+        geometry::{StraightWire, WireGeometry},
+        solver::ThinWireSolver,
+        MomError,
+    },
+};
+
+/// Arbitrary wire structure modeled with the method of moments
+#[derive(Debug, Clone)]
+pub struct WireAntenna {
+    pub geometry: WireGeometry,
+    /// Index of the wire carrying the feedpoint
+    pub feed_wire: usize,
+    /// Segment of `feed_wire` the feedpoint sits on
+    pub feed_segment: usize,
+    pub solver: ThinWireSolver,
+}
+
+impl WireAntenna {
+    pub fn new(geometry: WireGeometry, feed_wire: usize, feed_segment: usize) -> Self {
+        Self {
+            geometry,
+            feed_wire,
+            feed_segment,
+            solver: ThinWireSolver::default(),
+        }
+    }
+
+    /// Center-fed straight dipole along the x axis, `segments` should be odd so the feed sits in
+    /// the middle
+    pub fn dipole(length: f64, diameter: f64, segments: usize) -> Self {
+        let half: Vector3<f64> = Vector3::new(length / 2.0, 0.0, 0.0);
+        let wire: StraightWire = StraightWire::new(-half, half, diameter / 2.0, segments);
+        Self::new(wire.into(), 0, segments / 2)
+    }
+
+    /// Feedpoint impedance in ohms at `frequency` Hz
+    pub fn impedance(&self, frequency: f64) -> Result<Complex<f64>, MomError> {
+        let source: VoltageSource = VoltageSource {
+            wire: self.feed_wire,
+            segment: self.feed_segment,
+            voltage: Complex::new(1.0, 0.0),
+        };
+        self.solver
+            .solve(frequency, &self.geometry, &source.into())?
+            .input_impedance()
+            .ok_or(MomError::InvalidSource(source.wire, source.segment))
+    }
+}
+
+impl AntennaModel for WireAntenna {
+    /// The impedance is NaN if the structure could not be solved, use `impedance` to get the error
+    fn model(&self, properties: AntennaProperties) -> ModeledAntenna {
+        let impedance: Complex<f64> = self
+            .impedance(properties.frequency)
+            .unwrap_or(Complex::new(f64::NAN, f64::NAN));
+        ModeledAntenna::new(properties, impedance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_complex::Complex;
+
+    use crate::{
+        antenna::{AntennaModel, AntennaPolarization, AntennaProperties},
+        antennas::dipole::DipoleProperties,
+        feed_line::{FeedLineProperties, FeedlineModel},
+        feed_lines::coax_line::CoaxLineProperties,
+        consts::{FREE_SPACE_PERMEABILITY, FREE_SPACE_PERMITTIVITY},
+        util::swr,
+    };
+
+    use super::WireAntenna;
+
+    fn swr_sweep<A: AntennaModel>(antenna: &A, properties: AntennaProperties, frequencies: &[f64]) -> Vec<f64> {
+        frequencies
+            .iter()
+            .map(|&f| {
+                let modeled = antenna.model(AntennaProperties { frequency: f, ..properties });
+                swr(modeled.impedance, properties.z_s)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_wire_antenna_swr_sim() {
+        let length: f64 = 10.0; // meters
+        let diameter: f64 = 2.053e-3; // meters
+        let properties: AntennaProperties = AntennaProperties {
+            frequency: 14.1e6,
+            orientation: 0.0,
+            polarization: AntennaPolarization::Horizontal,
+            z_s: Complex::new(50.0, 0.0),
+        };
+        let frequencies: Vec<f64> = (0..30).map(|i| 14.1e6 + 30e3 * i as f64).collect();
+
+        let wire_swr: Vec<f64> = swr_sweep(&WireAntenna::dipole(length, diameter, 41), properties, &frequencies);
+        let dipole_swr: Vec<f64> = swr_sweep(&DipoleProperties { length, diameter }, properties, &frequencies);
+        for (f, (wire, dipole)) in frequencies.iter().zip(wire_swr.iter().zip(dipole_swr.iter())) {
+            println!("{} MHz: MoM SWR {wire}, analytic SWR {dipole}", f / 1e6);
+        }
+
+        // both models put the SWR minimum in the same part of the band
+        let minimum = |v: &[f64]| v.iter().enumerate().min_by(|a, b| a.1.total_cmp(b.1)).unwrap().0;
+        assert!(minimum(&wire_swr).abs_diff(minimum(&dipole_swr)) <= 6);
+        assert!(wire_swr.iter().all(|s| s.is_finite() && *s >= 1.0));
+    }
+
+    #[test]
+    fn test_wire_antenna_as_feed_line_load() {
+        let antenna: WireAntenna = WireAntenna::dipole(10.0, 2.053e-3, 31);
+        let properties: AntennaProperties = AntennaProperties {
+            frequency: 14.2e6,
+            orientation: 0.0,
+            polarization: AntennaPolarization::Horizontal,
+            z_s: Complex::new(50.0, 0.0),
+        };
+        let load: Complex<f64> = antenna.model(properties).impedance;
+
+        let coax: CoaxLineProperties = CoaxLineProperties {
+            inner_diameter: 0.00274,
+            shield_diameter: 0.00739,
+            dielectric_constant: 1.38 * FREE_SPACE_PERMITTIVITY,
+            magnetic_permeability: 1.0 * FREE_SPACE_PERMEABILITY,
+            resistivity_inner: 1.724e-8, // copper
+            resistivity_shield: 2.65e-8, // alu
+        };
+        let line = coax.model(FeedLineProperties {
+            frequency: properties.frequency,
+            length: 20.0,
+            z_l: load,
+            z_s: properties.z_s,
+        });
+
+        assert!(line.get_impedance_at_length().re.is_finite());
+        assert!(line.total_match_loss().is_finite());
+    }
+
+    #[test]
+    fn test_invalid_feed_gives_error() {
+        let mut antenna: WireAntenna = WireAntenna::dipole(10.0, 2.053e-3, 11);
+        antenna.feed_segment = 11;
+        assert!(antenna.impedance(14.2e6).is_err());
+    }
+}
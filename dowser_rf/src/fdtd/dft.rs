@@ -0,0 +1,199 @@
+use std::f64::consts::PI;
+
+use num_complex::Complex;
+
+use crate::util::{reflection_loss, swr};
+
+/// Fourier transform of a signal at a set of frequencies, summed one sample at a time while a
+/// simulation runs so the signal never has to be kept
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunningDft {
+    /// Frequencies in Hz
+    pub frequencies: Vec<f64>,
+    /// Time between samples in seconds
+    time_step: f64,
+    sums: Vec<Complex<f64>>,
+}
+
+impl RunningDft {
+    pub fn new(frequencies: &[f64], time_step: f64) -> Self {
+        Self {
+            frequencies: frequencies.to_vec(),
+            time_step,
+            sums: vec![Complex::new(0.0, 0.0); frequencies.len()],
+        }
+    }
+
+    /// Adds `value` sampled at `time` in seconds
+    pub fn add(&mut self, time: f64, value: f64) {
+        for (sum, frequency) in self.sums.iter_mut().zip(self.frequencies.iter()) {
+            *sum += value * self.time_step * Complex::from_polar(1.0, -2.0 * PI * frequency * time);
+        }
+    }
+
+    /// Integral of the signal times e^(-j omega t) at every frequency, for e^(j omega t) phasors
+    pub fn values(&self) -> &[Complex<f64>] {
+        &self.sums
+    }
+}
+
+/// Transforms of the voltage and current of one lumped port, both sampled at the same instants
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortSpectrum {
+    pub voltage: RunningDft,
+    pub current: RunningDft,
+}
+
+impl PortSpectrum {
+    pub fn new(frequencies: &[f64], time_step: f64) -> Self {
+        Self {
+            voltage: RunningDft::new(frequencies, time_step),
+            current: RunningDft::new(frequencies, time_step),
+        }
+    }
+
+    pub fn frequencies(&self) -> &[f64] {
+        &self.voltage.frequencies
+    }
+
+    /// Impedance in ohms seen looking out of the port at every frequency, its own series
+    /// resistance left out. The run must be long enough for the fields to have died down
+    pub fn impedance(&self) -> Vec<Complex<f64>> {
+        self.voltage.values().iter().zip(self.current.values().iter()).map(|(v, i)| v / i).collect()
+    }
+
+    /// SWR against `reference` at every frequency
+    pub fn swr(&self, reference: Complex<f64>) -> Vec<f64> {
+        self.impedance().into_iter().map(|z| swr(z, reference)).collect()
+    }
+
+    /// Reflection loss in dB against `reference` at every frequency
+    pub fn reflection_loss(&self, reference: Complex<f64>) -> Vec<f64> {
+        self.impedance().into_iter().map(|z| reflection_loss(z, reference)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use nalgebra::Vector3;
+    use num_complex::Complex;
+
+    use crate::{
+        fdtd::{
+            cpml::Cpml,
+            simulation::{Boundary, Simulation},
+            three_d::{Axis, LumpedPort, ThinWire},
+            waveform::Gaussian,
+        },
+        mom::{
+            excitation::VoltageSource,
+            geometry::{StraightWire, WireGeometry},
+            solver::ThinWireSolver,
+        },
+    };
+
+    use super::{PortSpectrum, RunningDft};
+
+    #[test]
+    fn test_running_dft() {
+        // a decaying exponential, 1 / (1 / tau + j omega)
+        let (dt, tau) = (1e-11, 2e-9);
+        let mut dft: RunningDft = RunningDft::new(&[0.0, 100e6, 300e6], dt);
+        for n in 0..20000 {
+            let t: f64 = (n as f64 + 0.5) * dt;
+            dft.add(t, (-t / tau).exp());
+        }
+        for (value, f) in dft.values().iter().zip(dft.frequencies.iter()) {
+            let expected: Complex<f64> = 1.0 / Complex::new(1.0 / tau, 2.0 * PI * f);
+            assert!((value - expected).norm() < 1e-4 * expected.norm());
+        }
+    }
+
+    #[test]
+    fn test_port_impedance() {
+        // a series RL load, v = R i + L di/dt, driven by a Gaussian current
+        let (dt, r, l) = (1e-11, 30.0, 50e-9);
+        let mut port: PortSpectrum = PortSpectrum::new(&[50e6, 150e6], dt);
+        let current = |t: f64| (-((t - 5e-9) / 1e-9).powi(2)).exp();
+        for n in 0..2000 {
+            let t: f64 = n as f64 * dt;
+            let derivative: f64 = (current(t + 1e-13) - current(t - 1e-13)) / 2e-13;
+            port.voltage.add(t, r * current(t) + l * derivative);
+            port.current.add(t, current(t));
+        }
+        for (z, f) in port.impedance().iter().zip(port.frequencies().iter()) {
+            assert!((z - Complex::new(r, 2.0 * PI * f * l)).norm() < 1e-6);
+        }
+        let matched: PortSpectrum = PortSpectrum {
+            voltage: port.current.clone(),
+            current: port.current.clone(),
+        };
+        assert!(matched.swr(Complex::new(1.0, 0.0)).iter().all(|s| (s - 1.0).abs() < 1e-9));
+        assert!(port.reflection_loss(Complex::new(50.0, 0.0)).iter().all(|&loss| loss > 0.0));
+    }
+
+    /// Frequency where the reactance of `impedance` at `frequencies` turns positive, with the
+    /// resistance there and the slope of the reactance in ohms/Hz
+    fn resonance(frequencies: &[f64], impedance: &[Complex<f64>]) -> (f64, f64, f64) {
+        let i: usize = impedance.windows(2).position(|z| z[0].im < 0.0 && z[1].im >= 0.0).unwrap();
+        let (z0, z1) = (impedance[i], impedance[i + 1]);
+        let fraction: f64 = z0.im / (z0.im - z1.im);
+        let step: f64 = frequencies[i + 1] - frequencies[i];
+        (frequencies[i] + step * fraction, z0.re + (z1.re - z0.re) * fraction, (z1.im - z0.im) / step)
+    }
+
+    #[test]
+    fn test_dipole_impedance() {
+        let (cell, radius) = (0.02, 1e-3);
+        let frequencies: Vec<f64> = (0..=10).map(|i| 250e6 + 5e6 * i as f64).collect();
+        let mut sim: Simulation = Simulation::three_d(17, 17, 37, cell)
+            .boundary(Boundary::Cpml(Cpml {
+                thickness: 4,
+                ..Default::default()
+            }))
+            .wire(ThinWire {
+                axis: Axis::Z,
+                start: (8, 8, 6),
+                cells: 25,
+                radius,
+            })
+            .port(LumpedPort::new(Axis::Z, (8, 8, 18), 50.0), Gaussian::new(1e9))
+            .frequencies(&frequencies)
+            .build()
+            .unwrap();
+        sim.run(700).unwrap();
+        let spectrum: &PortSpectrum = &sim.port_spectra()[0];
+        let measured: Vec<Complex<f64>> = spectrum.impedance();
+
+        // the same 25 cell wire in MoM, fed on its center segment like the port
+        let half: f64 = 12.5 * cell;
+        let wire: WireGeometry =
+            StraightWire::new(Vector3::new(0.0, 0.0, -half), Vector3::new(0.0, 0.0, half), radius, 25).into();
+        let feed: VoltageSource = VoltageSource {
+            wire: 0,
+            segment: 12,
+            voltage: Complex::new(1.0, 0.0),
+        };
+        let solver: ThinWireSolver = ThinWireSolver::default();
+        let reference: Vec<Complex<f64>> = frequencies
+            .iter()
+            .map(|&f| solver.solve(f, &wire, &feed.into()).unwrap().input_impedance().unwrap())
+            .collect();
+
+        let (found, resistance, slope) = resonance(&frequencies, &measured);
+        let (expected, expected_resistance, expected_slope) = resonance(&frequencies, &reference);
+        // the grid only places the charge at the wire ends to within half a cell, so each end
+        // can act up to about half a cell longer: at most 26 / 25 of the length, and a
+        // resonance that much lower with a quarter cell of slack
+        assert!(found < expected && expected / found < 1.0 + 1.25 / 25.0);
+        // neither the resistance at resonance nor how fast the reactance swings through it
+        // depends much on where the ends sit
+        assert!((resistance / expected_resistance - 1.0).abs() < 0.05);
+        assert!((slope / expected_slope - 1.0).abs() < 0.1);
+        // a half wave dipole is a fair match for 50 ohms around resonance
+        let best: f64 = spectrum.swr(Complex::new(50.0, 0.0)).into_iter().fold(f64::INFINITY, f64::min);
+        assert!(best < 2.0);
+    }
+}
@@ -0,0 +1,337 @@
+use num_complex::Complex;
+
+use crate::{
+    consts::FREE_SPACE_PERMITTIVITY,
+    fdtd::{Field, Material},
+};
+
+/// Most poles a material can have
+pub const MAX_POLES: usize = 2;
+
+/// Term of the relative permittivity that varies with frequency, solved in time as an auxiliary
+/// differential equation for the polarization it adds. Lorentz and Drude poles are stepped
+/// explicitly and need the Courant number a little below the limit of the grid when the
+/// permittivity is 1 at high frequencies
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pole {
+    /// delta / (1 + j omega tau), the permittivity relaxing from its static value down by `delta`
+    /// above 1 / (2 pi tau)
+    Debye {
+        delta: f64,
+        /// Relaxation time tau in seconds
+        relaxation_time: f64,
+    },
+    /// delta w0^2 / (w0^2 + j omega gamma - omega^2), a damped resonance
+    Lorentz {
+        delta: f64,
+        /// Resonant angular frequency w0 in radians/second
+        resonance: f64,
+        /// Damping gamma in 1/seconds
+        damping: f64,
+    },
+    /// wp^2 / (j omega gamma - omega^2), free charges with collisions
+    Drude {
+        /// Plasma angular frequency wp in radians/second
+        plasma_frequency: f64,
+        /// Collision frequency gamma in 1/seconds
+        collision_frequency: f64,
+    },
+}
+
+impl Pole {
+    /// Relative susceptibility at `omega` in radians/second, for e^(j omega t)
+    pub fn susceptibility(&self, omega: f64) -> Complex<f64> {
+        let j: Complex<f64> = Complex::i();
+        match *self {
+            Pole::Debye {
+                delta,
+                relaxation_time,
+            } => delta / (1.0 + j * omega * relaxation_time),
+            Pole::Lorentz {
+                delta,
+                resonance,
+                damping,
+            } => {
+                delta * resonance.powi(2)
+                    / (resonance.powi(2) + j * omega * damping - omega.powi(2))
+            }
+            Pole::Drude {
+                plasma_frequency,
+                collision_frequency,
+            } => plasma_frequency.powi(2) / (j * omega * collision_frequency - omega.powi(2)),
+        }
+    }
+
+    fn update(&self, time_step: f64) -> PoleUpdate {
+        match *self {
+            // tau dP/dt + P = eps0 delta E, centered half a step ahead
+            Pole::Debye {
+                delta,
+                relaxation_time,
+            } => {
+                let half: f64 = time_step / (2.0 * relaxation_time);
+                PoleUpdate::Implicit {
+                    decay: (1.0 - half) / (1.0 + half),
+                    gain: FREE_SPACE_PERMITTIVITY * delta * half / (1.0 + half),
+                }
+            }
+            // d2P/dt2 + gamma dP/dt + w0^2 P = eps0 delta w0^2 E, centered on the current step
+            Pole::Lorentz {
+                delta,
+                resonance,
+                damping,
+            } => second_order(
+                time_step,
+                resonance.powi(2),
+                damping,
+                delta * resonance.powi(2),
+            ),
+            Pole::Drude {
+                plasma_frequency,
+                collision_frequency,
+            } => second_order(
+                time_step,
+                0.0,
+                collision_frequency,
+                plasma_frequency.powi(2),
+            ),
+        }
+    }
+}
+
+fn second_order(time_step: f64, stiffness: f64, damping: f64, forcing: f64) -> PoleUpdate {
+    let lead: f64 = 1.0 + damping * time_step / 2.0;
+    PoleUpdate::Explicit {
+        own: (2.0 - stiffness * time_step.powi(2)) / lead,
+        previous: -(1.0 - damping * time_step / 2.0) / lead,
+        gain: FREE_SPACE_PERMITTIVITY * forcing * time_step.powi(2) / lead,
+    }
+}
+
+/// Step of the polarization P of one pole in coulombs/square meter
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PoleUpdate {
+    /// P' = decay P + gain (E' + E), solved together with the field
+    Implicit { decay: f64, gain: f64 },
+    /// P' = own P + previous P_old + gain E, ahead of the field
+    Explicit { own: f64, previous: f64, gain: f64 },
+}
+
+impl Material {
+    /// Polarization added to the permittivity in the electric field update for every step the
+    /// field takes, in farads/meter, from the poles solved together with the field
+    pub(crate) fn implicit_permittivity(&self, time_step: f64) -> f64 {
+        self.poles
+            .iter()
+            .flatten()
+            .map(|pole| match pole.update(time_step) {
+                PoleUpdate::Implicit { gain, .. } => gain,
+                PoleUpdate::Explicit { .. } => 0.0,
+            })
+            .sum()
+    }
+}
+
+/// Electric sample in a dispersive material with the polarization of its poles
+#[derive(Debug, Clone, PartialEq)]
+struct DispersiveSample<I> {
+    idx: I,
+    /// Turns a change of polarization into a change of field, the curl coefficient over dx / dt
+    scale: f64,
+    updates: [Option<PoleUpdate>; MAX_POLES],
+    /// Polarization now and one step back
+    polarization: [(f64, f64); MAX_POLES],
+    /// Field before the update
+    field: f64,
+    /// Field change from the polarization, found before the update
+    pending: f64,
+}
+
+/// Polarization of the samples of one electric component that lie in dispersive materials. It
+/// wraps the update of the component, which leaves out the polarization current
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dispersion<I> {
+    samples: Vec<DispersiveSample<I>>,
+}
+
+impl<I: Copy> Dispersion<I> {
+    pub(crate) fn new() -> Self {
+        Self {
+            samples: Vec::new(),
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Tracks the sample `idx` if `material` has poles, with the polarization starting at zero
+    pub(crate) fn push(&mut self, idx: I, material: &Material, time_step: f64, cell_size: f64) {
+        if material.poles.iter().all(|p| p.is_none()) {
+            return;
+        }
+        let (_, curl) = material.electric_coefficients(time_step, cell_size);
+        self.samples.push(DispersiveSample {
+            idx,
+            scale: curl * cell_size / time_step,
+            updates: material.poles.map(|p| p.map(|p| p.update(time_step))),
+            polarization: [(0.0, 0.0); MAX_POLES],
+            field: 0.0,
+            pending: 0.0,
+        });
+    }
+
+    /// Steps the poles that run ahead of the field, before the field update
+    pub(crate) fn before<F: Field<Index = I>>(&mut self, field: &F) {
+        for sample in self.samples.iter_mut() {
+            sample.field = *field.get(sample.idx);
+            sample.pending = 0.0;
+            for (update, (now, old)) in sample.updates.iter().zip(sample.polarization.iter_mut()) {
+                match update {
+                    Some(PoleUpdate::Implicit { decay, .. }) => {
+                        sample.pending += (1.0 - decay) * *now
+                    }
+                    Some(PoleUpdate::Explicit {
+                        own,
+                        previous,
+                        gain,
+                    }) => {
+                        let next: f64 = own * *now + previous * *old + gain * sample.field;
+                        sample.pending -= next - *now;
+                        (*now, *old) = (next, *now);
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+
+    /// Adds the polarization current to the updated field and steps the other poles with it
+    pub(crate) fn after<F: Field<Index = I>>(&mut self, field: &mut F) {
+        for sample in self.samples.iter_mut() {
+            let value: &mut f64 = field.get_mut(sample.idx);
+            *value += sample.scale * sample.pending;
+            for (update, (now, old)) in sample.updates.iter().zip(sample.polarization.iter_mut()) {
+                if let Some(PoleUpdate::Implicit { decay, gain }) = update {
+                    (*now, *old) = (decay * *now + gain * (*value + sample.field), *now);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use num_complex::Complex;
+
+    use crate::{
+        consts::SPEED_OF_LIGHT,
+        fdtd::{
+            cpml::Cpml,
+            simulation::{Boundary, FieldComponent, Probe, Region, Simulation, Source},
+            Material,
+        },
+    };
+
+    use super::Pole;
+
+    const CELL: f64 = 0.1;
+    const FREQUENCIES: [f64; 3] = [3.5e6, 14e6, 28e6];
+
+    /// Spectrum of Ez 5 m in front of a half-space of `material`, at every frequency
+    fn spectrum(material: Material) -> Vec<Complex<f64>> {
+        // the plasma has no margin at the 1D Courant limit
+        let mut sim: Simulation = Simulation::one_d(400, CELL)
+            .courant(0.99)
+            .material(Region::new((200, 0, 0), (400, 1, 1)), material)
+            .boundary(Boundary::Cpml(Cpml::default()))
+            .source(Source::new(FieldComponent::Ez, (60, 0, 0), |t: f64| {
+                (-((t - 3e-8) / 5e-9).powi(2)).exp()
+            }))
+            .probe(Probe::point(FieldComponent::Ez, (150, 0, 0)))
+            .build()
+            .unwrap();
+        sim.run(6000).unwrap();
+        let dt: f64 = sim.grid.time_step();
+        let series: Vec<f64> = sim.probes[0].series(0);
+        FREQUENCIES
+            .iter()
+            .map(|f| {
+                series
+                    .iter()
+                    .enumerate()
+                    .map(|(n, &e)| {
+                        e * Complex::from_polar(1.0, -2.0 * PI * f * (n + 1) as f64 * dt)
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Reflection off `material` seen by the grid and expected from its permittivity
+    fn reflection(material: Material) -> Vec<(Complex<f64>, Complex<f64>)> {
+        let incident: Vec<Complex<f64>> = spectrum(Material::VACUUM);
+        let total: Vec<Complex<f64>> = spectrum(material);
+        FREQUENCIES
+            .iter()
+            .zip(incident.iter().zip(total.iter()))
+            .map(|(&f, (i, t))| {
+                // the reflection travels there and back, from the probe to halfway between the last
+                // vacuum sample and the first one in the material
+                let delay: Complex<f64> =
+                    Complex::from_polar(1.0, 2.0 * 2.0 * PI * f / SPEED_OF_LIGHT * 4.95);
+                let index: Complex<f64> = material.permittivity_at(f).sqrt();
+                ((t - i) / i * delay, (1.0 - index) / (1.0 + index))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ground_reflection() {
+        for material in [
+            Material::wet_soil(),
+            Material::dry_sand(),
+            Material::sea_water(),
+        ] {
+            for (measured, expected) in reflection(material) {
+                dbg!(measured, expected);
+                assert!((measured.norm() - expected.norm()).abs() < 0.02 * expected.norm());
+                assert!((measured - expected).norm() < 0.05 * expected.norm());
+            }
+        }
+    }
+
+    #[test]
+    fn test_resonant_and_plasma_media() {
+        // a Lorentz line inside the band and a plasma whose cutoff is inside it as well
+        let mut lorentz: Material = Material::dielectric(2.0, 0.0);
+        lorentz.poles[0] = Some(Pole::Lorentz {
+            delta: 3.0,
+            resonance: 2.0 * PI * 10e6,
+            damping: 2.0 * PI * 2e6,
+        });
+        let mut plasma: Material = Material::VACUUM;
+        plasma.poles[1] = Some(Pole::Drude {
+            plasma_frequency: 2.0 * PI * 10e6,
+            collision_frequency: 1e6,
+        });
+        for material in [lorentz, plasma] {
+            for (measured, expected) in reflection(material) {
+                dbg!(measured, expected);
+                assert!((measured - expected).norm() < 0.05 * expected.norm().max(0.1));
+            }
+        }
+    }
+
+    #[test]
+    fn test_permittivity_at() {
+        let soil: Material = Material::wet_soil();
+        assert!((soil.permittivity_at(1e3).re - 35.0).abs() < 1e-3);
+        assert!((soil.permittivity_at(1e12).re - 20.0).abs() < 1e-3);
+        // the loss tangent of the conduction alone at 1.8 MHz
+        let loss: f64 = 0.01 / (2.0 * PI * 1.8e6 * crate::consts::FREE_SPACE_PERMITTIVITY);
+        assert!(-soil.permittivity_at(1.8e6).im > loss);
+    }
+}
@@ -0,0 +1,417 @@
+use std::f64::consts::PI;
+
+use nalgebra::{DMatrix, Vector3};
+use num_complex::Complex;
+
+use crate::{
+    consts::{FREE_SPACE_IMPEDANCE, SPEED_OF_LIGHT},
+    fdtd::{
+        simulation::{FieldComponent, Grid, Point, Region},
+        three_d::Axis,
+        FdtdError,
+    },
+    mom::far_field::spherical_units,
+    pattern::RadiationPattern,
+    util::hz_to_angular_freq,
+};
+
+/// Tangential field component on a patch, averaged from the samples around it
+#[derive(Debug, Clone, PartialEq)]
+struct Tangential {
+    component: FieldComponent,
+    /// Samples and their weights
+    stencil: Vec<(Point, f64)>,
+}
+
+/// One face cell of the surface, sampled at its center
+#[derive(Debug, Clone, PartialEq)]
+struct Patch {
+    /// Center in meters from the center of the box
+    position: Vector3<f64>,
+    /// Outward normal
+    normal: Vector3<f64>,
+    electric: Vec<Tangential>,
+    magnetic: Vec<Tangential>,
+}
+
+/// Equivalent currents J = n x H and M = -n x E on a patch, per unit area
+struct Currents {
+    position: Vector3<f64>,
+    electric: Vector3<Complex<f64>>,
+    magnetic: Vector3<Complex<f64>>,
+}
+
+/// Closed surface on the faces of a box, transforming the tangential fields on it at a set of
+/// frequencies as the simulation runs. The equivalent currents on it radiate the far field of
+/// everything inside, so the surface must enclose every source and lie in vacuum
+#[derive(Debug, Clone)]
+pub(crate) struct HuygensSurface {
+    frequencies: Vec<f64>,
+    three_d: bool,
+    /// Area of a patch in square meters, on a 2D grid its length in meters
+    area: f64,
+    patches: Vec<Patch>,
+    /// Transformed fields, one row per frequency and one entry per patch
+    electric: Vec<Vec<Vector3<Complex<f64>>>>,
+    magnetic: Vec<Vec<Vector3<Complex<f64>>>>,
+}
+
+fn electric(axis: Axis) -> FieldComponent {
+    [FieldComponent::Ex, FieldComponent::Ey, FieldComponent::Ez][axis.index()]
+}
+
+fn magnetic(axis: Axis) -> FieldComponent {
+    [FieldComponent::Hx, FieldComponent::Hy, FieldComponent::Hz][axis.index()]
+}
+
+/// Samples of `component` averaged onto `position`, given in half cells along every axis. Only
+/// the `active` axes count
+fn stencil(component: FieldComponent, position: [usize; 3], active: &[Axis]) -> Vec<(Point, f64)> {
+    let mut samples: Vec<([usize; 3], f64)> = vec![([0; 3], 1.0)];
+    for &axis in active {
+        // the electric field sits half a cell along its own axis, the magnetic field along the others
+        let half: usize = (component.is_electric() == (component.axis() == axis)) as usize;
+        let offset: usize = position[axis.index()] - half;
+        let indices: Vec<usize> = match offset % 2 {
+            0 => vec![offset / 2],
+            _ => vec![offset / 2, offset / 2 + 1],
+        };
+        let mut next: Vec<([usize; 3], f64)> = Vec::with_capacity(samples.len() * indices.len());
+        for (point, weight) in samples {
+            for &i in indices.iter() {
+                let mut point: [usize; 3] = point;
+                point[axis.index()] = i;
+                next.push((point, weight / indices.len() as f64));
+            }
+        }
+        samples = next;
+    }
+    samples.into_iter().map(|(p, w)| ((p[0], p[1], p[2]), w)).collect()
+}
+
+fn dot(a: &Vector3<Complex<f64>>, b: &Vector3<f64>) -> Complex<f64> {
+    a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+impl HuygensSurface {
+    /// Surface on the faces through the corners `region.start` and `region.end - 1`
+    pub(crate) fn new(region: Region, frequencies: &[f64], grid: &Grid) -> Result<Self, FdtdError> {
+        let active: Vec<Axis> = match grid {
+            Grid::OneD(_) => return Err(FdtdError::InvalidPlacement("far field needs a 2D or 3D grid".to_string())),
+            Grid::TwoD(_) => vec![Axis::X, Axis::Y],
+            Grid::ThreeD(_) => Axis::ALL.to_vec(),
+        };
+        if frequencies.is_empty() {
+            return Err(FdtdError::InvalidPlacement("far field needs frequencies to transform at".to_string()));
+        }
+        let (start, end, size) = (region.start, region.end, grid.size());
+        let (start, end, size) = (
+            [start.0, start.1, start.2],
+            [end.0, end.1, end.2],
+            [size.0, size.1, size.2],
+        );
+        for &axis in active.iter() {
+            let (lo, hi, n) = (start[axis.index()], end[axis.index()], size[axis.index()]);
+            if lo < 1 || hi < lo + 2 || hi + 1 > n {
+                return Err(FdtdError::InvalidPlacement(format!("far field box {region:?} must be inside the walls")));
+            }
+        }
+
+        let dx: f64 = grid.cell_size();
+        // twice the center of the box in cells
+        let center: [usize; 3] = [0, 1, 2].map(|i| start[i] + end[i] - 1);
+        let carried = |component: FieldComponent| grid.value(component, (0, 0, 0)).is_some();
+        let mut patches: Vec<Patch> = Vec::new();
+        for &normal in active.iter() {
+            let faces: [(usize, f64); 2] = [(start[normal.index()], -1.0), (end[normal.index()] - 1, 1.0)];
+            for (face, sign) in faces {
+                // cells of the face along the other axes
+                let range = |axis: Axis| match axis {
+                    _ if axis == normal => face..face + 1,
+                    _ if active.contains(&axis) => start[axis.index()]..end[axis.index()] - 1,
+                    _ => 0..1,
+                };
+                for x in range(Axis::X) {
+                    for y in range(Axis::Y) {
+                        for z in range(Axis::Z) {
+                            let position: [usize; 3] = Axis::ALL.map(|axis| {
+                                let i: usize = [x, y, z][axis.index()];
+                                match axis {
+                                    _ if axis == normal => 2 * i,
+                                    _ if active.contains(&axis) => 2 * i + 1,
+                                    _ => 0,
+                                }
+                            });
+                            let tangential = |field: fn(Axis) -> FieldComponent| {
+                                Axis::ALL
+                                    .into_iter()
+                                    .filter(|&axis| axis != normal && carried(field(axis)))
+                                    .map(|axis| Tangential {
+                                        component: field(axis),
+                                        stencil: stencil(field(axis), position, &active),
+                                    })
+                                    .collect::<Vec<Tangential>>()
+                            };
+                            let offset = |i: usize| match active.contains(&Axis::ALL[i]) {
+                                true => (position[i] as f64 - center[i] as f64) / 2.0 * dx,
+                                false => 0.0,
+                            };
+                            let mut unit: Vector3<f64> = Vector3::zeros();
+                            unit[normal.index()] = sign;
+                            patches.push(Patch {
+                                position: Vector3::new(offset(0), offset(1), offset(2)),
+                                normal: unit,
+                                electric: tangential(electric),
+                                magnetic: tangential(magnetic),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        let zeros = || vec![vec![Vector3::zeros(); patches.len()]; frequencies.len()];
+        Ok(Self {
+            frequencies: frequencies.to_vec(),
+            three_d: active.len() == 3,
+            area: dx.powi(active.len() as i32 - 1),
+            electric: zeros(),
+            magnetic: zeros(),
+            patches,
+        })
+    }
+
+    /// Adds the fields of `grid` after its latest step, the magnetic field half a step earlier
+    pub(crate) fn step(&mut self, grid: &Grid) {
+        let dt: f64 = grid.time_step();
+        let t: f64 = grid.time() as f64 * dt;
+        let sample = |tangential: &[Tangential]| {
+            let mut field: Vector3<f64> = Vector3::zeros();
+            for t in tangential {
+                field[t.component.axis().index()] =
+                    t.stencil.iter().map(|&(p, w)| w * grid.value(t.component, p).unwrap_or(0.0)).sum();
+            }
+            field
+        };
+        let fields: Vec<(Vector3<f64>, Vector3<f64>)> =
+            self.patches.iter().map(|p| (sample(&p.electric), sample(&p.magnetic))).collect();
+        for (i, &frequency) in self.frequencies.iter().enumerate() {
+            let omega: f64 = hz_to_angular_freq(frequency);
+            let e_phase: Complex<f64> = dt * Complex::from_polar(1.0, -omega * t);
+            let h_phase: Complex<f64> = dt * Complex::from_polar(1.0, -omega * (t - 0.5 * dt));
+            for (j, (e, h)) in fields.iter().enumerate() {
+                self.electric[i][j] += e.map(|c| e_phase * c);
+                self.magnetic[i][j] += h.map(|c| h_phase * c);
+            }
+        }
+    }
+
+    pub(crate) fn frequencies(&self) -> &[f64] {
+        &self.frequencies
+    }
+
+    /// Far field at frequency number `index` on every combination of `thetas` and `phis` in
+    /// radians. A 2D grid only has the plane theta = pi/2 and ignores `thetas`, its pattern holds
+    /// sqrt(rho) * E in volts/sqrt(meter) and twice the power radiated per meter so the directivity
+    /// comes out as 2 pi U / P. The fields are divided by `reference`, the transform of the
+    /// driving signal, and the powers by its magnitude squared, `input_power` included, which
+    /// turns transforms of the transient into phasors. Without an `input_power` it is taken as
+    /// the radiated power
+    pub(crate) fn pattern(
+        &self,
+        index: usize,
+        thetas: &[f64],
+        phis: &[f64],
+        reference: Complex<f64>,
+        input_power: Option<f64>,
+    ) -> RadiationPattern {
+        let frequency: f64 = self.frequencies[index];
+        let k: f64 = hz_to_angular_freq(frequency) / SPEED_OF_LIGHT;
+        let currents: Vec<Currents> = self
+            .patches
+            .iter()
+            .zip(self.electric[index].iter().zip(self.magnetic[index].iter()))
+            .map(|(patch, (e, h))| {
+                let normal: Vector3<Complex<f64>> = patch.normal.map(|c| Complex::new(c, 0.0));
+                Currents {
+                    position: patch.position,
+                    electric: normal.cross(h),
+                    magnetic: -normal.cross(e),
+                }
+            })
+            .collect();
+        // far out the Green's function is e^(-jkr) / (4 pi r) in 3D, and in 2D the line source
+        // -j/4 H0(2)(k rho) tends to e^(-j pi/4) e^(-jk rho) / sqrt(8 pi k rho)
+        let coefficient: Complex<f64> = match self.three_d {
+            true => Complex::new(0.0, -k / (4.0 * PI)),
+            false => Complex::new(0.0, -1.0) * Complex::from_polar((k / (8.0 * PI)).sqrt(), -PI / 4.0),
+        };
+        let thetas: Vec<f64> = match self.three_d {
+            true => thetas.to_vec(),
+            false => vec![PI / 2.0],
+        };
+
+        let mut e_theta: DMatrix<Complex<f64>> = DMatrix::zeros(thetas.len(), phis.len());
+        let mut e_phi: DMatrix<Complex<f64>> = DMatrix::zeros(thetas.len(), phis.len());
+        for (i, &theta) in thetas.iter().enumerate() {
+            for (j, &phi) in phis.iter().enumerate() {
+                let [r_hat, theta_hat, phi_hat] = spherical_units(theta, phi);
+                let mut n: Vector3<Complex<f64>> = Vector3::zeros();
+                let mut l: Vector3<Complex<f64>> = Vector3::zeros();
+                for c in currents.iter() {
+                    let phase: Complex<f64> = Complex::new(0.0, k * r_hat.dot(&c.position)).exp() * self.area;
+                    n += c.electric * phase;
+                    l += c.magnetic * phase;
+                }
+                let eta: f64 = FREE_SPACE_IMPEDANCE;
+                e_theta[(i, j)] = coefficient * (dot(&l, &phi_hat) + eta * dot(&n, &theta_hat)) / reference;
+                e_phi[(i, j)] = -coefficient * (dot(&l, &theta_hat) - eta * dot(&n, &phi_hat)) / reference;
+            }
+        }
+
+        // Poynting flux out through the surface
+        let flux: f64 = self
+            .patches
+            .iter()
+            .zip(self.electric[index].iter().zip(self.magnetic[index].iter()))
+            .map(|(patch, (e, h))| 0.5 * dot(&e.cross(&h.map(|c| c.conj())), &patch.normal).re * self.area)
+            .sum();
+        let scale: f64 = reference.norm_sqr();
+        let radiated_power: f64 = if self.three_d { flux } else { 2.0 * flux } / scale;
+        RadiationPattern {
+            frequency,
+            thetas,
+            phis: phis.to_vec(),
+            e_theta,
+            e_phi,
+            radiated_power,
+            input_power: input_power.map_or(radiated_power, |p| p / scale),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use num_complex::Complex;
+
+    use crate::{
+        consts::FREE_SPACE_IMPEDANCE,
+        fdtd::{
+            cpml::Cpml,
+            simulation::{Boundary, FieldComponent, Region, Simulation, Source},
+            three_d::{Axis, LumpedPort, ThinWire},
+            two_d::Polarization,
+            waveform::{Gaussian, Ricker},
+            FdtdError,
+        },
+        pattern::{angles, PatternSummary, RadiationPattern},
+    };
+
+    fn layer(thickness: usize) -> Boundary {
+        Boundary::Cpml(Cpml {
+            thickness,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_line_sources() {
+        // 1.5 GHz is 20 cells to the wavelength
+        let build = |points: &[(usize, usize, usize)]| {
+            let mut builder = Simulation::two_d(61, 61, 0.01, Polarization::Tmz)
+                .boundary(layer(8))
+                .frequencies(&[1.5e9])
+                .far_field(Region::new((18, 18, 0), (43, 43, 1)));
+            for &point in points {
+                builder = builder.source(Source::new(FieldComponent::Ez, point, Ricker::new(1.5e9)));
+            }
+            let mut sim: Simulation = builder.build().unwrap();
+            sim.run(400).unwrap();
+            sim.radiation_patterns(&[0.0], &angles(0.0, PI / 36.0, 72)).remove(0)
+        };
+        let single: RadiationPattern = build(&[(30, 30, 0)]);
+        let gains: Vec<f64> = (0..72).map(|j| single.gain_dbi(0, j)).collect();
+        assert_eq!(single.thetas, vec![PI / 2.0]);
+        assert!(gains.iter().all(|g| g.abs() < 0.1));
+
+        // half a wavelength apart along x, in phase
+        let pair: RadiationPattern = build(&[(25, 30, 0), (35, 30, 0)]);
+        let summary: PatternSummary = pair.summary().unwrap();
+        dbg!(summary, pair.gain_dbi(0, 0));
+        assert!((summary.max_phi - PI / 2.0).abs() < 1e-9 || (summary.max_phi - 3.0 * PI / 2.0).abs() < 1e-9);
+        assert!(pair.gain_dbi(0, 0) < -20.0 && pair.gain_dbi(0, 36) < -20.0);
+    }
+
+    #[test]
+    fn test_point_source_is_short_dipole() {
+        let mut sim: Simulation = Simulation::three_d(25, 25, 25, 0.01)
+            .boundary(layer(5))
+            .source(Source::new(FieldComponent::Ez, (12, 12, 12), Ricker::new(1.5e9)))
+            .frequencies(&[1.5e9])
+            .far_field(Region::new((7, 7, 7), (18, 18, 18)))
+            .build()
+            .unwrap();
+        sim.run(200).unwrap();
+        let step: f64 = PI / 36.0;
+        let pattern: RadiationPattern = sim.radiation_patterns(&angles(0.0, step, 37), &angles(0.0, step, 72)).remove(0);
+        let summary: PatternSummary = pattern.summary().unwrap();
+        dbg!(summary, pattern.gain_dbi(0, 0));
+        // directivity 1.5 sin^2(theta), no gain figure without ports
+        assert_eq!(pattern.efficiency(), 1.0);
+        assert!((summary.max_gain - 1.76).abs() < 0.1);
+        assert!((summary.max_theta - PI / 2.0).abs() < 1e-9);
+        assert!((summary.elevation_beamwidth.unwrap().to_degrees() - 90.0).abs() < 2.0);
+        assert!(summary.azimuth_beamwidth.is_none());
+        assert!(pattern.gain_dbi(0, 0) < -20.0);
+    }
+
+    #[test]
+    fn test_half_wave_dipole() {
+        // the wire of the impedance test in dft, resonant near 272 MHz
+        let mut sim: Simulation = Simulation::three_d(17, 17, 37, 0.02)
+            .boundary(layer(4))
+            .wire(ThinWire {
+                axis: Axis::Z,
+                start: (8, 8, 6),
+                cells: 25,
+                radius: 1e-3,
+            })
+            .port(LumpedPort::new(Axis::Z, (8, 8, 18), 50.0), Gaussian::new(1e9))
+            .frequencies(&[272e6])
+            .far_field(Region::new((5, 5, 5), (12, 12, 32)))
+            .build()
+            .unwrap();
+        sim.run(700).unwrap();
+        let step: f64 = PI / 36.0;
+        let pattern: RadiationPattern = sim.radiation_patterns(&angles(0.0, step, 37), &[0.0, PI / 2.0]).remove(0);
+        let summary: PatternSummary = pattern.summary().unwrap();
+        dbg!(summary, pattern.efficiency());
+        // nothing in the grid is lossy, so all the power from the port leaves through the surface
+        assert!((pattern.efficiency() - 1.0).abs() < 0.05);
+        assert!((summary.max_gain - 2.15).abs() < 0.2);
+        assert!((summary.elevation_beamwidth.unwrap().to_degrees() - 78.0).abs() < 3.0);
+
+        // per volt across the port like a MoM pattern, so times the impedance it is the field per
+        // ampere at the feed, eta / (2 pi) broadside of a resonant half-wave dipole
+        let z: Complex<f64> = sim.port_spectra()[0].impedance()[0];
+        dbg!(z, pattern.e_theta[(18, 0)] * z);
+        assert!((pattern.input_power - 0.5 * z.inv().re).abs() < 1e-9 * pattern.input_power);
+        assert!(((pattern.e_theta[(18, 0)] * z).norm() / (FREE_SPACE_IMPEDANCE / (2.0 * PI)) - 1.0).abs() < 0.03);
+    }
+
+    #[test]
+    fn test_invalid_surfaces() {
+        let tmz = || Simulation::two_d(31, 31, 0.01, Polarization::Tmz).frequencies(&[1e9]);
+        let inside: Region = Region::new((5, 5, 0), (26, 26, 1));
+        assert!(tmz().far_field(inside).build().is_ok());
+        assert!(matches!(
+            Simulation::two_d(31, 31, 0.01, Polarization::Tmz).far_field(inside).build(),
+            Err(FdtdError::InvalidPlacement(_))
+        ));
+        assert!(tmz().far_field(Region::new((0, 5, 0), (26, 26, 1))).build().is_err());
+        assert!(tmz().far_field(Region::new((5, 5, 0), (31, 26, 1))).build().is_err());
+        assert!(Simulation::one_d(31, 0.01).frequencies(&[1e9]).far_field(inside).build().is_err());
+    }
+}
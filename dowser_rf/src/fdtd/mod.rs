@@ -1,6 +1,54 @@
-use std::{f64::consts::PI, fs::{self, OpenOptions}, io::{BufReader, BufWriter, Write}, ops::{Index, IndexMut}, path::Path};
+use std::{f64::consts::PI, fmt, io, ops::{Index, IndexMut}};
+
+use num_complex::Complex;
+
+use crate::consts::{FREE_SPACE_PERMEABILITY, FREE_SPACE_PERMITTIVITY};
+
+use self::dispersion::{Pole, MAX_POLES};
+
+pub mod cpml;
+pub mod dft;
+pub mod dispersion;
+pub mod far_field;
+pub mod one_d;
+pub mod output;
+pub mod simulation;
+pub mod tfsf;
+pub mod three_d;
+pub mod two_d;
+pub mod waveform;
+
+#[derive(Debug)]
+pub enum FdtdError {
+    /// The Courant number is above the stability limit of the grid, holds both
+    Unstable(f64, f64),
+    /// Something is placed outside the grid or on a component it does not have
+    InvalidPlacement(String),
+    /// Writing an output failed
+    Io(io::Error),
+}
+
+impl fmt::Display for FdtdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FdtdError::Unstable(courant, limit) => {
+                write!(f, "Courant number {courant} is above the stability limit {limit}")
+            }
+            FdtdError::InvalidPlacement(reason) => write!(f, "invalid placement: {reason}"),
+            FdtdError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for FdtdError {}
 
-trait Field {
+impl From<io::Error> for FdtdError {
+    fn from(err: io::Error) -> Self {
+        FdtdError::Io(err)
+    }
+}
+
+pub trait Field {
     type Index;
     fn get(&self, idx: Self::Index) -> &f64;
     fn get_mut(&mut self, idx: Self::Index) -> &mut f64;
@@ -8,6 +56,99 @@ trait Field {
     fn max_index(&self) -> Self::Index;
 }
 
+/// Linear, isotropic material filling a cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    /// Relative permittivity, at frequencies far above any poles
+    pub permittivity: f64,
+    /// Relative permeability
+    pub permeability: f64,
+    /// Electric conductivity in siemens/meter
+    pub conductivity: f64,
+    /// Magnetic loss in ohms/meter, only useful for matched absorbers
+    pub magnetic_conductivity: f64,
+    /// Frequency dependent terms added to the relative permittivity
+    pub poles: [Option<Pole>; MAX_POLES],
+}
+
+impl Material {
+    pub const VACUUM: Material = Material {
+        permittivity: 1.0,
+        permeability: 1.0,
+        conductivity: 0.0,
+        magnetic_conductivity: 0.0,
+        poles: [None; MAX_POLES],
+    };
+
+    /// Non-magnetic dielectric, like soil or water
+    pub fn dielectric(permittivity: f64, conductivity: f64) -> Self {
+        Self {
+            permittivity,
+            conductivity,
+            ..Self::VACUUM
+        }
+    }
+
+    /// Sea water, which keeps its static permittivity and conductivity throughout HF. The water
+    /// relaxation sits near 17 GHz
+    pub fn sea_water() -> Self {
+        Self::dielectric(81.0, 5.0)
+    }
+
+    /// Wet ground, a single Debye pole around 5 MHz taking the permittivity from about 33 at
+    /// 1.8 MHz down to 20 at 30 MHz while the loss grows from 0.01 to 0.014 S/m
+    pub fn wet_soil() -> Self {
+        Self::dispersive(20.0, 0.01, 15.0, 3.2e-8)
+    }
+
+    /// Dry sand, a weak Debye pole like the one of wet soil with little conduction
+    pub fn dry_sand() -> Self {
+        Self::dispersive(3.0, 1e-4, 1.0, 3.2e-8)
+    }
+
+    /// Non-magnetic dielectric with a single Debye pole, `permittivity` is the high frequency
+    /// value and `delta` the rise to the static one
+    pub fn dispersive(permittivity: f64, conductivity: f64, delta: f64, relaxation_time: f64) -> Self {
+        let mut material: Self = Self::dielectric(permittivity, conductivity);
+        material.poles[0] = Some(Pole::Debye {
+            delta,
+            relaxation_time,
+        });
+        material
+    }
+
+    /// Complex relative permittivity at `frequency` in Hz, conductivity included, for e^(j omega t)
+    pub fn permittivity_at(&self, frequency: f64) -> Complex<f64> {
+        let omega: f64 = 2.0 * PI * frequency;
+        let poles: Complex<f64> = self.poles.iter().flatten().map(|p| p.susceptibility(omega)).sum();
+        self.permittivity + poles - Complex::i() * self.conductivity / (omega * FREE_SPACE_PERMITTIVITY)
+    }
+
+    /// Coefficients multiplying the old electric field and the curl of the magnetic field in the
+    /// update of an electric component, with the conductive loss averaged over the time step.
+    /// The polarization of the poles is added separately
+    pub fn electric_coefficients(&self, time_step: f64, cell_size: f64) -> (f64, f64) {
+        let permittivity: f64 = self.permittivity * FREE_SPACE_PERMITTIVITY;
+        let loss: f64 = (self.conductivity * time_step / 2.0 + self.implicit_permittivity(time_step)) / permittivity;
+        ((1.0 - loss) / (1.0 + loss), time_step / (permittivity * cell_size) / (1.0 + loss))
+    }
+
+    /// Coefficients multiplying the old magnetic field and the curl of the electric field in the
+    /// update of a magnetic component
+    pub fn magnetic_coefficients(&self, time_step: f64, cell_size: f64) -> (f64, f64) {
+        let permeability: f64 = self.permeability * FREE_SPACE_PERMEABILITY;
+        let loss: f64 = self.magnetic_conductivity * time_step / (2.0 * permeability);
+        ((1.0 - loss) / (1.0 + loss), time_step / (permeability * cell_size) / (1.0 + loss))
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::VACUUM
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct OneDField {
     array: Box<[f64]>,
 }
@@ -23,20 +164,6 @@ impl OneDField {
             array: initial.to_vec().into_boxed_slice(),
         }
     }
-    pub fn snapshot(field: &OneDField, snapshot_idx: usize, new: bool) {
-        let path = &format!("snap.csv");
-        let mut file;
-        if new {
-            file = fs::File::create(path).unwrap();
-            
-        } else {
-            file = OpenOptions::new().append(true).open(path).unwrap();
-        }
-        let mut buf_writer = BufWriter::new(file);
-        for node in field.array.iter().enumerate() {
-            writeln!(&mut buf_writer, "{snapshot_idx},{0},{1}", node.0, node.1).unwrap();
-        }
-    }
 }
 
 impl Field for OneDField {
@@ -68,80 +195,51 @@ impl IndexMut<usize> for OneDField {
     }
 }
 
-pub fn harmonic_source(t: f64, x: f64, courant: f64, ppw: f64) -> f64 {
-    f64::sin(2.0 * PI / ppw * (courant * t - x))
-}
-
-pub fn source_function(t: f64, x: f64, courant: f64) -> f64 {
-    (-((t + 0.5 - x / courant / 100.0).powf(2.0))).exp()
-}
-
 #[cfg(test)]
 mod tests {
-    use crate::{consts::FREE_SPACE_IMPEDANCE, fdtd::{harmonic_source, source_function}};
+    use crate::{
+        consts::SPEED_OF_LIGHT,
+        fdtd::{
+            cpml::Cpml,
+            output::{Recorder, Snapshot},
+            simulation::{Boundary, FieldComponent, Probe, Region, Simulation, Source},
+        },
+    };
 
-    use super::OneDField;
+    use super::Material;
 
     #[test]
     fn fdtd_1d() {
-        const SIZE: usize = 200;
-        const LOSS: f64 = 0.0253146;
-        const LOSS_LAYER: usize = 180;
-        const TFSF_BOUNDARY: usize = 50;
-        let mut e_field: OneDField = OneDField::new_zeroed(SIZE);
-        let mut h_field: OneDField = OneDField::new_zeroed(SIZE - 1);
-        //let mut eps_r: OneDField = OneDField::from_initial_state(&[4.0; SIZE]);
-        let mut eps_r: f64 = 9.0;
-        let mut ceze: OneDField = OneDField::new_zeroed(SIZE);
-        let mut cezh: OneDField = OneDField::new_zeroed(SIZE);
-        let mut chyh: OneDField = OneDField::new_zeroed(SIZE - 1);
-        let mut chye: OneDField = OneDField::new_zeroed(SIZE - 1);
-        let courant: f64 = 1.0;
-        let max_time: usize = 250;
-        let ppw: f64 = 40.0;
-
-        let mut x: f64 = (cezh[0] * chye[0]).sqrt();
-        let abc_left = (x - 1.0) / (x + 1.0);
-        x = (cezh[SIZE - 1] * chye[SIZE - 1]).sqrt();
-        let abc_right = (x - 1.0) / (x + 1.0);
-        let mut old_left: f64 = 0.0;
-        let mut old_right: f64 = 0.0;
-
-        for m in 1..SIZE {
-            if m < 100 {
-                ceze[m] = 1.0;
-                cezh[m] = FREE_SPACE_IMPEDANCE;
-            } else {
-                ceze[m] = 1.0;
-                cezh[m] = FREE_SPACE_IMPEDANCE / eps_r;
-            }
-        }
-
-        for m in 1..(SIZE - 1) {
-            chyh[m] = 1.0;
-            chye[m] = 1.0 / FREE_SPACE_IMPEDANCE;
-        }
-
-        for q in 0..max_time {
-            // ABC for h[size-1]
-            h_field[SIZE - 1] = h_field[SIZE - 2];
-            for m in 0..(SIZE - 1) {
-                h_field[m] = chyh[m] * h_field[m] + chye[m] * (e_field[m + 1] - e_field[m]);
-            }
-            h_field[TFSF_BOUNDARY] -= source_function(q as f64, 0.0, courant);
-            e_field[TFSF_BOUNDARY + 1] += source_function(q as f64 + 0.5, -0.5, courant);
-            // ABS for e[0]
-            e_field[0] = e_field[1];
-            for m in 1..SIZE {
-                e_field[m] = ceze[m] * e_field[m] + cezh[m] * (h_field[m] - h_field[m - 1]);
-            }
-
-            e_field[0] = old_left + abc_left * (e_field[1] - e_field[0]);
-            old_left = e_field[1];
-            e_field[SIZE - 1] = old_right + abc_right * (e_field[SIZE - 2] - e_field[SIZE - 1]);
-            old_right = e_field[SIZE - 2];
-
-            OneDField::snapshot(&e_field, q, q == 0);
-        }
+        // a pulse in vacuum hits glass-like eps_r = 9 halfway along the grid, which reflects
+        // (1 - 3) / (1 + 3) of it and passes on 1 + that
+        let cell_size: f64 = 0.01;
+        let time_step: f64 = cell_size / SPEED_OF_LIGHT;
+        let mut sim: Simulation = Simulation::one_d(200, cell_size)
+            .material(Region::new((100, 0, 0), (200, 1, 1)), Material::dielectric(9.0, 0.0))
+            .boundary(Boundary::Cpml(Cpml::default()))
+            .source(Source::new(FieldComponent::Ez, (15, 0, 0), move |t: f64| {
+                (-((t / time_step - 60.0) / 16.0).powi(2)).exp()
+            }))
+            .probe(Probe::point(FieldComponent::Ez, (45, 0, 0)))
+            .probe(Probe::point(FieldComponent::Ez, (130, 0, 0)))
+            .snapshot(Snapshot::new(FieldComponent::Ez, Region::new((0, 0, 0), (200, 1, 1)), Recorder::default()))
+            .build()
+            .unwrap();
+        sim.run(320).unwrap();
+        sim.finish().unwrap();
+
+        let largest = |series: &[f64]| series.iter().fold(0.0, |a: f64, b| a.max(b.abs()));
+        let front: Vec<f64> = sim.probes[0].series(0);
+        let (incident, reflected) = (largest(&front[..150]), largest(&front[150..]));
+        let transmitted: f64 = largest(&sim.probes[1].series(0));
+        dbg!(incident, reflected, transmitted);
+        assert!((reflected / incident - 0.5).abs() < 0.02);
+        assert!((transmitted / incident - 0.5).abs() < 0.05);
+        // the reflection comes back inverted
+        assert_eq!(front[150..].iter().copied().fold(0.0, f64::min), -reflected);
+        // the whole grid after every step, the probes among it
+        let recorder: &Recorder = sim.snapshots[0].output().unwrap();
+        assert_eq!(recorder.frames.len(), 320);
+        assert_eq!(recorder.frames[149][45], front[149]);
     }
 }
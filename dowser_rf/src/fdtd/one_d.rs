@@ -0,0 +1,150 @@
+use std::ops::Range;
+
+use crate::{
+    consts::SPEED_OF_LIGHT,
+    fdtd::{
+        cpml::{Cpml, Layer},
+        dispersion::Dispersion,
+        Field, Material, OneDField,
+    },
+};
+
+/// Courant number at the stability limit of a 1D grid, where a wave moves exactly one cell per
+/// step
+pub const COURANT_1D: f64 = 1.0;
+
+/// One field component with the coefficients of its update equation on every sample
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub field: OneDField,
+    /// Multiplies the old value
+    own: OneDField,
+    /// Multiplies the derivative of the other field
+    curl: OneDField,
+    /// Absorbing layers at both ends
+    layer: Option<Layer<OneDField>>,
+    dispersion: Dispersion<usize>,
+}
+
+impl Component {
+    fn new(size: usize) -> Self {
+        Self {
+            field: OneDField::new_zeroed(size),
+            own: OneDField::from_initial_state(&vec![1.0; size]),
+            curl: OneDField::new_zeroed(size),
+            layer: None,
+            dispersion: Dispersion::new(),
+        }
+    }
+}
+
+/// 1D Yee grid of a plane wave travelling along x, with Ez on the n cell corners and Hy halfway
+/// between them. Ez at both ends is held at zero, a perfect conductor, which can be lined with an
+/// absorbing layer
+#[derive(Debug, Clone)]
+pub struct OneDSimulation {
+    /// Cell size in meters
+    pub cell_size: f64,
+    /// Time step in seconds
+    pub time_step: f64,
+    /// Number of steps taken so far
+    pub time: usize,
+    pub ez: Component,
+    pub hy: Component,
+    /// Material of every cell, indexed like the grid corners
+    materials: Vec<Material>,
+    cpml: Option<Cpml>,
+}
+
+impl OneDSimulation {
+    /// Vacuum filled grid of `n` cell corners, at least one as `Simulation::one_d` checks,
+    /// stepped at the Courant limit
+    pub(crate) fn new(n: usize, cell_size: f64) -> Self {
+        let mut simulation: Self = Self {
+            cell_size,
+            time_step: COURANT_1D * cell_size / SPEED_OF_LIGHT,
+            time: 0,
+            ez: Component::new(n),
+            hy: Component::new(n - 1),
+            materials: vec![Material::VACUUM; n],
+            cpml: None,
+        };
+        simulation.refresh(0..n);
+        simulation
+    }
+
+    /// Number of cell corners
+    pub fn size(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn material(&self, m: usize) -> Material {
+        self.materials[m]
+    }
+
+    /// Fills the cells in the given range of corner indices with `material`
+    pub fn set_material(&mut self, xs: Range<usize>, material: Material) {
+        self.materials[xs.clone()].fill(material);
+        self.refresh(xs);
+    }
+
+    /// Steps with `courant` times the cell size over the speed of light, at most 1
+    pub fn set_courant(&mut self, courant: f64) {
+        self.time_step = courant * self.cell_size / SPEED_OF_LIGHT;
+        self.refresh(0..self.size());
+        if let Some(cpml) = self.cpml {
+            self.set_cpml(&cpml);
+        }
+    }
+
+    /// Lines both ends with `cpml`
+    pub fn set_cpml(&mut self, cpml: &Cpml) {
+        let (n, dx, dt) = (self.size(), self.cell_size, self.time_step);
+        self.ez.layer = Some(cpml.layer(n, false, dx, dt, OneDField::new_zeroed));
+        self.hy.layer = Some(cpml.layer(n, true, dx, dt, OneDField::new_zeroed));
+        self.cpml = Some(*cpml);
+    }
+
+    /// Recomputes the coefficients of the samples belonging to the given cells
+    fn refresh(&mut self, xs: Range<usize>) {
+        for m in xs {
+            let material: Material = self.material(m);
+            let electric: (f64, f64) = material.electric_coefficients(self.time_step, self.cell_size);
+            let magnetic: (f64, f64) = material.magnetic_coefficients(self.time_step, self.cell_size);
+            for (component, (own, curl)) in [(&mut self.ez, electric), (&mut self.hy, magnetic)] {
+                if m <= component.field.max_index() {
+                    component.own[m] = own;
+                    component.curl[m] = curl;
+                }
+            }
+        }
+        self.ez.dispersion.clear();
+        for m in 1..self.size() - 1 {
+            self.ez.dispersion.push(m, &self.materials[m], self.time_step, self.cell_size);
+        }
+    }
+
+    /// Advances the fields by one time step, magnetic field first
+    pub fn step(&mut self) {
+        let (ez, hy) = (&mut self.ez, &mut self.hy);
+        // Hy += dEz/dx
+        for m in 0..=hy.field.max_index() {
+            let mut curl: f64 = ez.field[m + 1] - ez.field[m];
+            if let Some(layer) = &mut hy.layer {
+                curl = layer.stretch(m, |slot| slot, curl);
+            }
+            hy.field[m] = hy.own[m] * hy.field[m] + hy.curl[m] * curl;
+        }
+        // Ez += dHy/dx away from the ends
+        ez.dispersion.before(&ez.field);
+        for m in 1..ez.field.max_index() {
+            let mut curl: f64 = hy.field[m] - hy.field[m - 1];
+            if let Some(layer) = &mut ez.layer {
+                curl = layer.stretch(m, |slot| slot, curl);
+            }
+            ez.field[m] = ez.own[m] * ez.field[m] + ez.curl[m] * curl;
+        }
+        ez.dispersion.after(&mut ez.field);
+        self.time += 1;
+    }
+}
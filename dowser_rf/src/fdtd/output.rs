@@ -0,0 +1,355 @@
+use std::{
+    any::Any,
+    fmt,
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::fdtd::{
+    simulation::{FieldComponent, Point, Region},
+    FdtdError,
+};
+
+/// Destination of the frames a snapshot takes of the field
+pub trait Output: Any {
+    /// Called once before the first frame with the points every frame holds, x slowest and z
+    /// fastest, and their number along each axis
+    fn start(&mut self, points: &[Point], shape: [usize; 3]) -> Result<(), FdtdError>;
+    /// Values at the points taken after step `step`
+    fn frame(&mut self, step: usize, values: &[f64]) -> Result<(), FdtdError>;
+    /// Completes the output once the simulation is over
+    fn finish(&mut self) -> Result<(), FdtdError> {
+        Ok(())
+    }
+}
+
+/// Writes one line per point and frame, `step,x,y,z,value`, below a header
+#[derive(Debug)]
+pub struct CsvOutput<W: Write> {
+    writer: W,
+    points: Vec<Point>,
+}
+
+impl CsvOutput<BufWriter<File>> {
+    /// Creates or truncates the file at `path`
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, FdtdError> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> CsvOutput<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            points: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write + 'static> Output for CsvOutput<W> {
+    fn start(&mut self, points: &[Point], _: [usize; 3]) -> Result<(), FdtdError> {
+        self.points = points.to_vec();
+        writeln!(self.writer, "step,x,y,z,value")?;
+        Ok(())
+    }
+
+    fn frame(&mut self, step: usize, values: &[f64]) -> Result<(), FdtdError> {
+        for ((x, y, z), value) in self.points.iter().zip(values.iter()) {
+            writeln!(self.writer, "{step},{x},{y},{z},{value:e}")?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), FdtdError> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// Writes the frames as a little endian f64 NumPy array shaped (frames, ...) followed by the axes
+/// of the snapshot that have more than one point. The frame count in the header is only right
+/// after `finish`
+#[derive(Debug)]
+pub struct NpyOutput<W: Write + Seek> {
+    writer: W,
+    shape: Vec<usize>,
+    frames: usize,
+}
+
+impl NpyOutput<BufWriter<File>> {
+    /// Creates or truncates the file at `path`, which by convention ends in `.npy`
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, FdtdError> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write + Seek> NpyOutput<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            shape: Vec::new(),
+            frames: 0,
+        }
+    }
+
+    /// Writes the header, the frame count padded to the width of any count so the header keeps
+    /// its length when it is rewritten
+    fn write_header(&mut self) -> Result<(), FdtdError> {
+        let axes: String = self.shape.iter().map(|n| format!(" {n},")).collect();
+        let mut header: String =
+            format!("{{'descr': '<f8', 'fortran_order': False, 'shape': ({:>20},{axes}), }}", self.frames);
+        // magic, version and header length come first, a newline ends the header and the data
+        // starts on a multiple of 64 bytes
+        let length: usize = (10 + header.len() + 1).div_ceil(64) * 64 - 10;
+        header.push_str(&" ".repeat(length - header.len() - 1));
+        header.push('\n');
+        self.writer.write_all(b"\x93NUMPY\x01\x00")?;
+        self.writer.write_all(&(length as u16).to_le_bytes())?;
+        self.writer.write_all(header.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek + 'static> Output for NpyOutput<W> {
+    fn start(&mut self, _: &[Point], shape: [usize; 3]) -> Result<(), FdtdError> {
+        self.shape = shape.into_iter().filter(|&n| n > 1).collect();
+        self.write_header()
+    }
+
+    fn frame(&mut self, _: usize, values: &[f64]) -> Result<(), FdtdError> {
+        for value in values.iter() {
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), FdtdError> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.writer.seek(SeekFrom::End(0))?;
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// Keeps the frames in memory
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recorder {
+    pub points: Vec<Point>,
+    pub shape: [usize; 3],
+    /// Step after which every frame was taken
+    pub steps: Vec<usize>,
+    pub frames: Vec<Vec<f64>>,
+}
+
+impl Output for Recorder {
+    fn start(&mut self, points: &[Point], shape: [usize; 3]) -> Result<(), FdtdError> {
+        self.points = points.to_vec();
+        self.shape = shape;
+        Ok(())
+    }
+
+    fn frame(&mut self, step: usize, values: &[f64]) -> Result<(), FdtdError> {
+        self.steps.push(step);
+        self.frames.push(values.to_vec());
+        Ok(())
+    }
+}
+
+/// Takes frames of one field component over a region, every `every` steps and every `stride`
+/// samples along each axis
+#[derive(Debug)]
+pub struct Snapshot {
+    pub component: FieldComponent,
+    pub region: Region,
+    pub every: usize,
+    pub stride: usize,
+    output: Box<dyn Output>,
+}
+
+impl Snapshot {
+    /// Frame after every step of every sample in `region`
+    pub fn new(component: FieldComponent, region: Region, output: impl Output) -> Self {
+        Self {
+            component,
+            region,
+            every: 1,
+            stride: 1,
+            output: Box::new(output),
+        }
+    }
+
+    /// Takes a frame every `steps` steps
+    pub fn every(mut self, steps: usize) -> Self {
+        self.every = steps;
+        self
+    }
+
+    /// Keeps every `cells`-th sample along each axis, starting from the region start
+    pub fn stride(mut self, cells: usize) -> Self {
+        self.stride = cells;
+        self
+    }
+
+    /// Points of the region a frame holds and their number along each axis
+    pub fn points(&self) -> (Vec<Point>, [usize; 3]) {
+        let (start, end) = (self.region.start, self.region.end);
+        let xs: Vec<usize> = (start.0..end.0).step_by(self.stride).collect();
+        let ys: Vec<usize> = (start.1..end.1).step_by(self.stride).collect();
+        let zs: Vec<usize> = (start.2..end.2).step_by(self.stride).collect();
+        let mut points: Vec<Point> = Vec::with_capacity(xs.len() * ys.len() * zs.len());
+        for &x in xs.iter() {
+            for &y in ys.iter() {
+                for &z in zs.iter() {
+                    points.push((x, y, z));
+                }
+            }
+        }
+        (points, [xs.len(), ys.len(), zs.len()])
+    }
+
+    /// The output, if it is an `O`
+    pub fn output<O: Output>(&self) -> Option<&O> {
+        let output: &dyn Any = &*self.output;
+        output.downcast_ref::<O>()
+    }
+
+    pub(crate) fn output_mut(&mut self) -> &mut dyn Output {
+        &mut *self.output
+    }
+}
+
+impl fmt::Debug for dyn Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Output")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::{self, Write},
+        path::PathBuf,
+    };
+
+    use crate::fdtd::{
+        simulation::{FieldComponent, Probe, Region, Simulation, Source},
+        two_d::Polarization,
+        FdtdError,
+    };
+
+    use super::{CsvOutput, NpyOutput, Recorder, Snapshot};
+
+    fn pulse(t: f64) -> f64 {
+        (-((t - 1e-9) / 3e-10).powi(2)).exp()
+    }
+
+    fn simulation(snapshot: Snapshot) -> Simulation {
+        Simulation::two_d(21, 21, 0.05, Polarization::Tmz)
+            .source(Source::new(FieldComponent::Ez, (10, 10, 0), pulse))
+            .probe(Probe::point(FieldComponent::Ez, (12, 14, 0)))
+            .snapshot(snapshot)
+            .build()
+            .unwrap()
+    }
+
+    fn temporary(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("dowser_{}_{name}", std::process::id()))
+    }
+
+    #[test]
+    fn test_decimation() {
+        let region: Region = Region::new((4, 6, 0), (17, 15, 1));
+        let mut sim: Simulation =
+            simulation(Snapshot::new(FieldComponent::Ez, region, Recorder::default()).every(5).stride(4));
+        sim.run(42).unwrap();
+        let recorder: &Recorder = sim.snapshots[0].output().unwrap();
+        assert_eq!(recorder.shape, [4, 3, 1]);
+        assert_eq!(recorder.points[..4], [(4, 6, 0), (4, 10, 0), (4, 14, 0), (8, 6, 0)]);
+        assert_eq!(recorder.steps, [5, 10, 15, 20, 25, 30, 35, 40]);
+        // (12, 14) is the ninth point
+        let probe: Vec<f64> = sim.probes[0].series(0);
+        for (step, frame) in recorder.steps.iter().zip(recorder.frames.iter()) {
+            assert_eq!(frame[8], probe[step - 1]);
+        }
+        assert!(sim.snapshots[0].output::<CsvOutput<Vec<u8>>>().is_none());
+    }
+
+    #[test]
+    fn test_files() {
+        let region: Region = Region::new((0, 10, 0), (21, 11, 1));
+        let (csv, npy) = (temporary("line.csv"), temporary("line.npy"));
+        let mut sim: Simulation = Simulation::two_d(21, 21, 0.05, Polarization::Tmz)
+            .source(Source::new(FieldComponent::Ez, (10, 10, 0), pulse))
+            .snapshot(Snapshot::new(FieldComponent::Ez, region, CsvOutput::create(&csv).unwrap()).every(10))
+            .snapshot(Snapshot::new(FieldComponent::Ez, region, NpyOutput::create(&npy).unwrap()).every(10))
+            .snapshot(Snapshot::new(FieldComponent::Ez, region, Recorder::default()).every(10))
+            .build()
+            .unwrap();
+        sim.run(30).unwrap();
+        sim.finish().unwrap();
+        let frames: Vec<Vec<f64>> = sim.snapshots[2].output::<Recorder>().unwrap().frames.clone();
+
+        let text: String = fs::read_to_string(&csv).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 1 + 3 * 21);
+        assert_eq!(lines[0], "step,x,y,z,value");
+        let fields: Vec<&str> = lines[21 + 11].split(',').collect();
+        assert_eq!(fields[..4], ["20", "10", "10", "0"]);
+        assert_eq!(fields[4].parse::<f64>().unwrap(), frames[1][10]);
+
+        let bytes: Vec<u8> = fs::read(&npy).unwrap();
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let length: usize = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + length) % 64, 0);
+        let header: String = String::from_utf8(bytes[10..10 + length].to_vec()).unwrap();
+        let shape: String = header.split(['(', ')']).nth(1).unwrap().split_whitespace().collect();
+        assert_eq!(shape, "3,21,");
+        let data: Vec<f64> =
+            bytes[10 + length..].chunks(8).map(|b| f64::from_le_bytes(b.try_into().unwrap())).collect();
+        assert_eq!(data, frames.concat());
+        fs::remove_file(csv).unwrap();
+        fs::remove_file(npy).unwrap();
+    }
+
+    /// Writer that fails once it holds `capacity` bytes
+    struct Full {
+        capacity: usize,
+    }
+
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.capacity {
+                return Err(io::Error::new(io::ErrorKind::StorageFull, "full"));
+            }
+            self.capacity -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_errors_propagate() {
+        let missing = CsvOutput::create(temporary("missing").join("snap.csv"));
+        assert!(matches!(missing, Err(FdtdError::Io(_))));
+
+        let region: Region = Region::new((0, 0, 0), (21, 21, 1));
+        let full: CsvOutput<Full> = CsvOutput::new(Full { capacity: 1000 });
+        let mut sim: Simulation = simulation(Snapshot::new(FieldComponent::Ez, region, full));
+        assert!(matches!(sim.run(10), Err(FdtdError::Io(_))));
+        assert!(sim.grid.time() < 10);
+
+        let skipping = Simulation::two_d(21, 21, 0.05, Polarization::Tmz)
+            .snapshot(Snapshot::new(FieldComponent::Ez, region, Recorder::default()).every(0))
+            .build();
+        assert!(matches!(skipping, Err(FdtdError::InvalidPlacement(_))));
+        let outside = Simulation::two_d(21, 21, 0.05, Polarization::Tmz)
+            .snapshot(Snapshot::new(FieldComponent::Ez, Region::new((0, 0, 0), (22, 1, 1)), Recorder::default()))
+            .build();
+        assert!(outside.is_err());
+    }
+}
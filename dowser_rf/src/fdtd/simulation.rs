@@ -0,0 +1,770 @@
+use std::fmt;
+
+use num_complex::Complex;
+
+use crate::{
+    fdtd::{
+        cpml::Cpml,
+        dft::{PortSpectrum, RunningDft},
+        far_field::HuygensSurface,
+        one_d::{OneDSimulation, COURANT_1D},
+        output::Snapshot,
+        tfsf::{Injector, Tfsf},
+        three_d::{Axis, LumpedPort, ThinWire, ThreeDSimulation, COURANT_3D},
+        two_d::{Polarization, TwoDSimulation, COURANT_2D},
+        FdtdError, Field, Material,
+    },
+    pattern::RadiationPattern,
+};
+
+/// Grid corner (x, y, z), the coordinates a grid does not have are 0
+pub type Point = (usize, usize, usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldComponent {
+    Ex,
+    Ey,
+    Ez,
+    Hx,
+    Hy,
+    Hz,
+}
+
+impl FieldComponent {
+    pub fn axis(self) -> Axis {
+        match self {
+            FieldComponent::Ex | FieldComponent::Hx => Axis::X,
+            FieldComponent::Ey | FieldComponent::Hy => Axis::Y,
+            FieldComponent::Ez | FieldComponent::Hz => Axis::Z,
+        }
+    }
+    pub fn is_electric(self) -> bool {
+        matches!(self, FieldComponent::Ex | FieldComponent::Ey | FieldComponent::Ez)
+    }
+}
+
+/// Signal driving a source, in volts/meter or amperes/meter for a field and in volts for a port
+pub trait Waveform {
+    /// Value at `time` in seconds
+    fn value(&self, time: f64) -> f64;
+}
+
+impl<F: Fn(f64) -> f64> Waveform for F {
+    fn value(&self, time: f64) -> f64 {
+        self(time)
+    }
+}
+
+impl fmt::Debug for dyn Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Waveform")
+    }
+}
+
+/// Box of cell corners from `start` up to but not including `end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub start: Point,
+    pub end: Point,
+}
+
+impl Region {
+    pub fn new(start: Point, end: Point) -> Self {
+        Self { start, end }
+    }
+}
+
+/// Soft source, adding its waveform to one field sample after every step
+#[derive(Debug)]
+pub struct Source {
+    pub component: FieldComponent,
+    pub point: Point,
+    waveform: Box<dyn Waveform>,
+}
+
+impl Source {
+    pub fn new(component: FieldComponent, point: Point, waveform: impl Waveform + 'static) -> Self {
+        Self {
+            component,
+            point,
+            waveform: Box::new(waveform),
+        }
+    }
+}
+
+/// Records one field component at a set of samples after every step. The electric field is
+/// recorded at whole time steps and the magnetic field half a step earlier
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probe {
+    pub component: FieldComponent,
+    pub points: Vec<Point>,
+    /// Value at every point, one entry per step
+    pub samples: Vec<Vec<f64>>,
+}
+
+impl Probe {
+    pub fn point(component: FieldComponent, point: Point) -> Self {
+        Self::new(component, vec![point])
+    }
+
+    /// `count` samples along `axis` starting at `start`
+    pub fn line(component: FieldComponent, start: Point, axis: Axis, count: usize) -> Self {
+        let points: Vec<Point> = (0..count)
+            .map(|i| match axis {
+                Axis::X => (start.0 + i, start.1, start.2),
+                Axis::Y => (start.0, start.1 + i, start.2),
+                Axis::Z => (start.0, start.1, start.2 + i),
+            })
+            .collect();
+        Self::new(component, points)
+    }
+
+    fn new(component: FieldComponent, points: Vec<Point>) -> Self {
+        Self {
+            component,
+            points,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Time series at point `point` of the probe
+    pub fn series(&self, point: usize) -> Vec<f64> {
+        self.samples.iter().map(|s| s[point]).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Boundary {
+    /// Perfect conductor
+    #[default]
+    Conductor,
+    /// Conductor lined with an absorbing layer
+    Cpml(Cpml),
+}
+
+#[derive(Debug, Clone)]
+pub enum Grid {
+    OneD(Box<OneDSimulation>),
+    TwoD(Box<TwoDSimulation>),
+    ThreeD(Box<ThreeDSimulation>),
+}
+
+impl Grid {
+    /// Time step in seconds
+    pub fn time_step(&self) -> f64 {
+        match self {
+            Grid::OneD(grid) => grid.time_step,
+            Grid::TwoD(grid) => grid.time_step,
+            Grid::ThreeD(grid) => grid.time_step,
+        }
+    }
+
+    /// Cell size in meters
+    pub fn cell_size(&self) -> f64 {
+        match self {
+            Grid::OneD(grid) => grid.cell_size,
+            Grid::TwoD(grid) => grid.cell_size,
+            Grid::ThreeD(grid) => grid.cell_size,
+        }
+    }
+
+    /// Number of cell corners along each axis, 1 along the axes the grid does not have
+    pub fn size(&self) -> Point {
+        match self {
+            Grid::OneD(grid) => (grid.size(), 1, 1),
+            Grid::TwoD(grid) => (grid.size().0, grid.size().1, 1),
+            Grid::ThreeD(grid) => grid.size(),
+        }
+    }
+
+    /// Number of steps taken so far
+    pub fn time(&self) -> usize {
+        match self {
+            Grid::OneD(grid) => grid.time,
+            Grid::TwoD(grid) => grid.time,
+            Grid::ThreeD(grid) => grid.time,
+        }
+    }
+
+    pub fn step(&mut self) {
+        match self {
+            Grid::OneD(grid) => grid.step(),
+            Grid::TwoD(grid) => grid.step(),
+            Grid::ThreeD(grid) => grid.step(),
+        }
+    }
+
+    /// Sample of `component` at `point`, none when the grid has no such sample
+    pub fn value(&self, component: FieldComponent, point: Point) -> Option<f64> {
+        let (x, y, z) = point;
+        match self {
+            Grid::OneD(grid) => {
+                let field = match component {
+                    FieldComponent::Ez => &grid.ez.field,
+                    FieldComponent::Hy => &grid.hy.field,
+                    _ => return None,
+                };
+                (y == 0 && z == 0 && x <= field.max_index()).then(|| field[x])
+            }
+            Grid::TwoD(grid) => {
+                use FieldComponent::*;
+                let field = match (grid.polarization, component) {
+                    (Polarization::Tmz, Ez) | (Polarization::Tez, Hz) => &grid.z.field,
+                    (Polarization::Tmz, Hx) | (Polarization::Tez, Ex) => &grid.x.field,
+                    (Polarization::Tmz, Hy) | (Polarization::Tez, Ey) => &grid.y.field,
+                    _ => return None,
+                };
+                let (mx, my) = field.max_index();
+                (z == 0 && x <= mx && y <= my).then(|| field[(x, y)])
+            }
+            Grid::ThreeD(grid) => {
+                let field = match component.is_electric() {
+                    true => &grid.e[component.axis().index()].field,
+                    false => &grid.h[component.axis().index()].field,
+                };
+                let (mx, my, mz) = field.max_index();
+                (x <= mx && y <= my && z <= mz).then(|| field[point])
+            }
+        }
+    }
+
+    pub fn value_mut(&mut self, component: FieldComponent, point: Point) -> Option<&mut f64> {
+        self.value(component, point)?;
+        let (x, y, _) = point;
+        Some(match self {
+            Grid::OneD(grid) => match component.is_electric() {
+                true => &mut grid.ez.field[x],
+                false => &mut grid.hy.field[x],
+            },
+            Grid::TwoD(grid) => match component.axis() {
+                Axis::X => &mut grid.x.field[(x, y)],
+                Axis::Y => &mut grid.y.field[(x, y)],
+                Axis::Z => &mut grid.z.field[(x, y)],
+            },
+            Grid::ThreeD(grid) => match component.is_electric() {
+                true => &mut grid.e[component.axis().index()].field[point],
+                false => &mut grid.h[component.axis().index()].field[point],
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dimensions {
+    One(usize),
+    Two(usize, usize, Polarization),
+    Three(usize, usize, usize),
+}
+
+/// FDTD simulation put together by a `SimulationBuilder`, stepped with its sources driving it
+/// and its probes and snapshots recording
+#[derive(Debug)]
+pub struct Simulation {
+    pub grid: Grid,
+    pub probes: Vec<Probe>,
+    pub snapshots: Vec<Snapshot>,
+    sources: Vec<Source>,
+    injectors: Vec<Injector>,
+    /// Waveform of every port of a 3D grid
+    ports: Vec<Box<dyn Waveform>>,
+    /// Transforms of every port, when frequencies were given
+    spectra: Vec<PortSpectrum>,
+    /// Transform of the first soft source, or plane wave without any, when frequencies were given.
+    /// The far field is normalised by it when there are no ports
+    drive: Option<RunningDft>,
+    surface: Option<HuygensSurface>,
+}
+
+impl Simulation {
+    /// Grid of `n` cell corners along x carrying Ez and Hy
+    pub fn one_d(n: usize, cell_size: f64) -> SimulationBuilder {
+        SimulationBuilder::new(Dimensions::One(n), cell_size)
+    }
+
+    pub fn two_d(nx: usize, ny: usize, cell_size: f64, polarization: Polarization) -> SimulationBuilder {
+        SimulationBuilder::new(Dimensions::Two(nx, ny, polarization), cell_size)
+    }
+
+    pub fn three_d(nx: usize, ny: usize, nz: usize, cell_size: f64) -> SimulationBuilder {
+        SimulationBuilder::new(Dimensions::Three(nx, ny, nz), cell_size)
+    }
+
+    /// Time reached in seconds
+    pub fn time(&self) -> f64 {
+        self.grid.time() as f64 * self.grid.time_step()
+    }
+
+    /// Advances one time step, adding the sources to the fields they drive and recording the
+    /// probes and the snapshots due afterwards
+    pub fn step(&mut self) -> Result<(), FdtdError> {
+        let dt: f64 = self.grid.time_step();
+        if let Grid::ThreeD(grid) = &mut self.grid {
+            // the port voltage acts halfway through the step
+            let t: f64 = (grid.time as f64 + 0.5) * dt;
+            for (port, waveform) in grid.ports.iter_mut().zip(self.ports.iter()) {
+                port.voltage = waveform.value(t);
+            }
+        }
+        self.grid.step();
+        if let Grid::ThreeD(grid) = &self.grid {
+            // sampled halfway through the step like the port voltage
+            let t: f64 = (grid.time as f64 - 0.5) * dt;
+            for (port, spectrum) in grid.ports.iter().zip(self.spectra.iter_mut()) {
+                spectrum.voltage.add(t, *port.voltages.last().unwrap());
+                spectrum.current.add(t, *port.currents.last().unwrap());
+            }
+        }
+        let t: f64 = self.time();
+        for (i, source) in self.sources.iter().enumerate() {
+            let time: f64 = match source.component.is_electric() {
+                true => t,
+                false => t - 0.5 * dt,
+            };
+            let added: f64 = source.waveform.value(time);
+            if let Some(value) = self.grid.value_mut(source.component, source.point) {
+                *value += added;
+            }
+            if let (0, Some(drive)) = (i, &mut self.drive) {
+                drive.add(time, added);
+            }
+        }
+        for injector in self.injectors.iter_mut() {
+            injector.step(&mut self.grid);
+        }
+        let plane_wave: Option<&Injector> = self.injectors.first().filter(|_| self.sources.is_empty());
+        if let (Some(injector), Some(drive)) = (plane_wave, &mut self.drive) {
+            let (time, value) = injector.injected();
+            drive.add(time, value);
+        }
+        if let Some(surface) = &mut self.surface {
+            surface.step(&self.grid);
+        }
+        for probe in self.probes.iter_mut() {
+            let sample: Vec<f64> =
+                probe.points.iter().map(|&p| self.grid.value(probe.component, p).unwrap_or(0.0)).collect();
+            probe.samples.push(sample);
+        }
+        let step: usize = self.grid.time();
+        for snapshot in self.snapshots.iter_mut().filter(|s| step.is_multiple_of(s.every)) {
+            let values: Vec<f64> = snapshot
+                .points()
+                .0
+                .iter()
+                .map(|&p| self.grid.value(snapshot.component, p).unwrap_or(0.0))
+                .collect();
+            snapshot.output_mut().frame(step, &values)?;
+        }
+        Ok(())
+    }
+
+    pub fn run(&mut self, steps: usize) -> Result<(), FdtdError> {
+        for _ in 0..steps {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Completes the outputs of the snapshots, once no more steps follow
+    pub fn finish(&mut self) -> Result<(), FdtdError> {
+        for snapshot in self.snapshots.iter_mut() {
+            snapshot.output_mut().finish()?;
+        }
+        Ok(())
+    }
+
+    /// Ports of a 3D grid with their recorded voltages and currents
+    pub fn ports(&self) -> &[LumpedPort] {
+        match &self.grid {
+            Grid::ThreeD(grid) => &grid.ports,
+            _ => &[],
+        }
+    }
+
+    /// Transforms of the voltage and current of every port at the frequencies given to the
+    /// builder, empty without any
+    pub fn port_spectra(&self) -> &[PortSpectrum] {
+        &self.spectra
+    }
+
+    /// Far field patterns from the Huygens surface at every frequency given to the builder, on
+    /// every combination of `thetas` and `phis` in radians, empty without a surface. The input
+    /// power comes from the ports, or equals the radiated power without any. See
+    /// `SimulationBuilder::far_field` for 2D grids.
+    ///
+    /// The transforms of the transient are divided by the transform of what drives the grid, so
+    /// like a MoM pattern the fields and powers are those of a 1 V phasor across the first port.
+    /// Without ports the reference is the waveform of the first soft source, or of the first
+    /// plane wave, at an amplitude of 1
+    pub fn radiation_patterns(&self, thetas: &[f64], phis: &[f64]) -> Vec<RadiationPattern> {
+        let Some(surface) = &self.surface else { return Vec::new() };
+        (0..surface.frequencies().len())
+            .map(|i| {
+                let reference: Complex<f64> = match (self.spectra.first(), &self.drive) {
+                    (Some(port), _) => port.voltage.values()[i],
+                    (None, Some(drive)) => drive.values()[i],
+                    (None, None) => Complex::new(1.0, 0.0),
+                };
+                let input_power: Option<f64> = (!self.spectra.is_empty()).then(|| {
+                    self.spectra.iter().map(|s| 0.5 * (s.voltage.values()[i] * s.current.values()[i].conj()).re).sum()
+                });
+                surface.pattern(i, thetas, phis, reference, input_power)
+            })
+            .collect()
+    }
+}
+
+/// Collects the parts of a simulation, checking them all when it is built
+#[derive(Debug)]
+pub struct SimulationBuilder {
+    dimensions: Dimensions,
+    cell_size: f64,
+    courant: Option<f64>,
+    materials: Vec<(Region, Material)>,
+    boundary: Boundary,
+    sources: Vec<Source>,
+    plane_waves: Vec<Tfsf>,
+    probes: Vec<Probe>,
+    snapshots: Vec<Snapshot>,
+    wires: Vec<ThinWire>,
+    ports: Vec<(LumpedPort, Box<dyn Waveform>)>,
+    frequencies: Vec<f64>,
+    far_field: Option<Region>,
+}
+
+impl SimulationBuilder {
+    fn new(dimensions: Dimensions, cell_size: f64) -> Self {
+        Self {
+            dimensions,
+            cell_size,
+            courant: None,
+            materials: Vec::new(),
+            boundary: Boundary::default(),
+            sources: Vec::new(),
+            plane_waves: Vec::new(),
+            probes: Vec::new(),
+            snapshots: Vec::new(),
+            wires: Vec::new(),
+            ports: Vec::new(),
+            frequencies: Vec::new(),
+            far_field: None,
+        }
+    }
+
+    /// Steps with `courant` times the cell size over the speed of light instead of the
+    /// stability limit
+    pub fn courant(mut self, courant: f64) -> Self {
+        self.courant = Some(courant);
+        self
+    }
+
+    /// Fills `region` with `material`, later regions are laid over earlier ones
+    pub fn material(mut self, region: Region, material: Material) -> Self {
+        self.materials.push((region, material));
+        self
+    }
+
+    pub fn boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    pub fn source(mut self, source: Source) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    /// Plane wave injected on the faces of a box
+    pub fn plane_wave(mut self, tfsf: Tfsf) -> Self {
+        self.plane_waves.push(tfsf);
+        self
+    }
+
+    pub fn probe(mut self, probe: Probe) -> Self {
+        self.probes.push(probe);
+        self
+    }
+
+    pub fn snapshot(mut self, snapshot: Snapshot) -> Self {
+        self.snapshots.push(snapshot);
+        self
+    }
+
+    /// Thin wire, 3D grids only
+    pub fn wire(mut self, wire: ThinWire) -> Self {
+        self.wires.push(wire);
+        self
+    }
+
+    /// Lumped port with its source voltage, 3D grids only
+    pub fn port(mut self, port: LumpedPort, waveform: impl Waveform + 'static) -> Self {
+        self.ports.push((port, Box::new(waveform)));
+        self
+    }
+
+    /// Transforms the voltage and current of every port, and the fields on the Huygens surface, at
+    /// `frequencies` in Hz as the simulation runs
+    pub fn frequencies(mut self, frequencies: &[f64]) -> Self {
+        self.frequencies = frequencies.to_vec();
+        self
+    }
+
+    /// Huygens surface on the faces through the corners `region.start` and `region.end - 1`, for
+    /// far field patterns at the builder's frequencies, 2D and 3D grids only. It must enclose the
+    /// sources in vacuum, clear of the absorbing layer. On a 2D grid the pattern lies in the plane
+    /// theta = pi/2 and holds sqrt(rho) * E, with twice the power radiated per meter so that the
+    /// directivity is the 2D one, 2 pi U / P
+    pub fn far_field(mut self, region: Region) -> Self {
+        self.far_field = Some(region);
+        self
+    }
+
+    pub fn build(self) -> Result<Simulation, FdtdError> {
+        let (size, limit, axes) = match self.dimensions {
+            Dimensions::One(n) => ((n, 1, 1), COURANT_1D, 1),
+            Dimensions::Two(nx, ny, _) => ((nx, ny, 1), COURANT_2D, 2),
+            Dimensions::Three(nx, ny, nz) => ((nx, ny, nz), COURANT_3D, 3),
+        };
+        let cells: &[usize] = &[size.0, size.1, size.2][..axes];
+        if cells.contains(&0) {
+            return Err(FdtdError::InvalidPlacement(format!("grid of {cells:?} cells is empty")));
+        }
+        if self.cell_size.is_nan() || self.cell_size <= 0.0 {
+            return Err(FdtdError::InvalidPlacement(format!("cell size {} is not positive", self.cell_size)));
+        }
+        if let Boundary::Cpml(cpml) = self.boundary {
+            if cells.iter().any(|&n| 2 * cpml.thickness > n) {
+                let message: String = format!("CPML of {} cells does not fit in {cells:?} cells", cpml.thickness);
+                return Err(FdtdError::InvalidPlacement(message));
+            }
+        }
+        let courant: f64 = self.courant.unwrap_or(limit);
+        if courant > limit || courant <= 0.0 {
+            return Err(FdtdError::Unstable(courant, limit));
+        }
+        for (region, _) in self.materials.iter() {
+            if region.end.0 > size.0 || region.end.1 > size.1 || region.end.2 > size.2 {
+                return Err(FdtdError::InvalidPlacement(format!("region {region:?} outside the grid")));
+            }
+            if region.start.0 > region.end.0 || region.start.1 > region.end.1 || region.start.2 > region.end.2 {
+                return Err(FdtdError::InvalidPlacement(format!("region {region:?} starts past its end")));
+            }
+        }
+        let three_d: bool = matches!(self.dimensions, Dimensions::Three(..));
+        if !three_d && (!self.wires.is_empty() || !self.ports.is_empty()) {
+            return Err(FdtdError::InvalidPlacement("wires and ports need a 3D grid".to_string()));
+        }
+
+        let mut grid: Grid = match self.dimensions {
+            Dimensions::One(n) => {
+                let mut grid: OneDSimulation = OneDSimulation::new(n, self.cell_size);
+                grid.set_courant(courant);
+                for (region, material) in self.materials.iter() {
+                    grid.set_material(region.start.0..region.end.0, *material);
+                }
+                if let Boundary::Cpml(cpml) = self.boundary {
+                    grid.set_cpml(&cpml);
+                }
+                Grid::OneD(Box::new(grid))
+            }
+            Dimensions::Two(nx, ny, polarization) => {
+                let mut grid: TwoDSimulation = TwoDSimulation::new(nx, ny, self.cell_size, polarization);
+                grid.set_courant(courant);
+                for (region, material) in self.materials.iter() {
+                    grid.set_material(region.start.0..region.end.0, region.start.1..region.end.1, *material);
+                }
+                if let Boundary::Cpml(cpml) = self.boundary {
+                    grid.set_cpml(&cpml);
+                }
+                Grid::TwoD(Box::new(grid))
+            }
+            Dimensions::Three(nx, ny, nz) => {
+                let mut grid: ThreeDSimulation = ThreeDSimulation::new(nx, ny, nz, self.cell_size);
+                grid.set_courant(courant);
+                for (region, material) in self.materials.iter() {
+                    let (start, end) = (region.start, region.end);
+                    grid.set_material(start.0..end.0, start.1..end.1, start.2..end.2, *material);
+                }
+                if let Boundary::Cpml(cpml) = self.boundary {
+                    grid.set_cpml(&cpml);
+                }
+                Grid::ThreeD(Box::new(grid))
+            }
+        };
+
+        let placed = |grid: &Grid, component: FieldComponent, point: Point, what: &str| match grid
+            .value(component, point)
+        {
+            Some(_) => Ok(()),
+            None => Err(FdtdError::InvalidPlacement(format!("{what} on {component:?} at {point:?}"))),
+        };
+        for source in self.sources.iter() {
+            placed(&grid, source.component, source.point, "source")?;
+        }
+        for probe in self.probes.iter() {
+            for &point in probe.points.iter() {
+                placed(&grid, probe.component, point, "probe")?;
+            }
+        }
+        for snapshot in self.snapshots.iter() {
+            if snapshot.every == 0 || snapshot.stride == 0 {
+                return Err(FdtdError::InvalidPlacement(format!("{snapshot:?} skips every sample")));
+            }
+            for &point in snapshot.points().0.iter() {
+                placed(&grid, snapshot.component, point, "snapshot")?;
+            }
+        }
+        let mut waveforms: Vec<Box<dyn Waveform>> = Vec::with_capacity(self.ports.len());
+        if let Grid::ThreeD(three_d) = &mut grid {
+            for wire in self.wires {
+                three_d.add_wire(wire)?;
+            }
+            for (port, waveform) in self.ports {
+                three_d.add_port(port)?;
+                waveforms.push(waveform);
+            }
+        }
+        let injectors: Vec<Injector> =
+            self.plane_waves.into_iter().map(|tfsf| Injector::new(tfsf, &grid)).collect::<Result<_, _>>()?;
+        let spectra: Vec<PortSpectrum> = match self.frequencies.is_empty() {
+            true => Vec::new(),
+            false => waveforms.iter().map(|_| PortSpectrum::new(&self.frequencies, grid.time_step())).collect(),
+        };
+        let surface: Option<HuygensSurface> =
+            self.far_field.map(|region| HuygensSurface::new(region, &self.frequencies, &grid)).transpose()?;
+        let driven: bool = !self.sources.is_empty() || !injectors.is_empty();
+        let drive: Option<RunningDft> =
+            (driven && !self.frequencies.is_empty()).then(|| RunningDft::new(&self.frequencies, grid.time_step()));
+        let mut snapshots: Vec<Snapshot> = self.snapshots;
+        for snapshot in snapshots.iter_mut() {
+            let (points, shape) = snapshot.points();
+            snapshot.output_mut().start(&points, shape)?;
+        }
+        Ok(Simulation {
+            grid,
+            probes: self.probes,
+            snapshots,
+            sources: self.sources,
+            injectors,
+            ports: waveforms,
+            spectra,
+            drive,
+            surface,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fdtd::{
+        cpml::Cpml,
+        three_d::{Axis, LumpedPort, ThinWire},
+        two_d::Polarization,
+        FdtdError, Material,
+    };
+
+    use super::{Boundary, FieldComponent, Probe, Region, Simulation, Source};
+
+    fn pulse(t: f64) -> f64 {
+        (-((t - 1e-9) / 3e-10).powi(2)).exp()
+    }
+
+    #[test]
+    fn test_builder_checks() {
+        let unstable = Simulation::two_d(20, 20, 0.01, Polarization::Tmz).courant(0.8).build();
+        assert!(matches!(unstable, Err(FdtdError::Unstable(_, _))));
+        let outside = Simulation::one_d(20, 0.01).probe(Probe::point(FieldComponent::Ez, (20, 0, 0))).build();
+        assert!(matches!(outside, Err(FdtdError::InvalidPlacement(_))));
+        // TMz has no Hz
+        let missing = Simulation::two_d(20, 20, 0.01, Polarization::Tmz)
+            .source(Source::new(FieldComponent::Hz, (5, 5, 0), pulse))
+            .build();
+        assert!(missing.is_err());
+        let wire: ThinWire = ThinWire {
+            axis: Axis::Z,
+            start: (5, 5, 0),
+            cells: 2,
+            radius: 1e-3,
+        };
+        assert!(Simulation::two_d(20, 20, 0.01, Polarization::Tmz).wire(wire).build().is_err());
+        assert!(Simulation::three_d(10, 10, 10, 0.01).wire(ThinWire { cells: 20, ..wire }).build().is_err());
+        for radius in [0.0, -1e-3, 5e-3, f64::NAN] {
+            let thick = Simulation::three_d(10, 10, 10, 0.01).wire(ThinWire { radius, ..wire }).build();
+            assert!(matches!(thick, Err(FdtdError::InvalidPlacement(_))));
+        }
+        for resistance in [0.0, -50.0, f64::NAN] {
+            let port: LumpedPort = LumpedPort::new(Axis::Z, (5, 5, 5), resistance);
+            let shorted = Simulation::three_d(10, 10, 10, 0.01).port(port, pulse).build();
+            assert!(matches!(shorted, Err(FdtdError::InvalidPlacement(_))));
+        }
+
+        for empty in [Simulation::one_d(0, 0.01), Simulation::two_d(20, 0, 0.01, Polarization::Tmz)] {
+            assert!(matches!(empty.build(), Err(FdtdError::InvalidPlacement(_))));
+        }
+        assert!(matches!(Simulation::three_d(10, 0, 10, 0.01).build(), Err(FdtdError::InvalidPlacement(_))));
+        let backwards: Region = Region::new((10, 0, 0), (5, 1, 1));
+        let reversed = Simulation::one_d(20, 0.01).material(backwards, Material::sea_water()).build();
+        assert!(matches!(reversed, Err(FdtdError::InvalidPlacement(_))));
+        for cell_size in [0.0, -0.01, f64::NAN] {
+            assert!(matches!(Simulation::one_d(20, cell_size).build(), Err(FdtdError::InvalidPlacement(_))));
+        }
+        // the default CPML is 10 cells thick on both sides
+        let crowded =
+            Simulation::two_d(40, 19, 0.01, Polarization::Tmz).boundary(Boundary::Cpml(Cpml::default())).build();
+        assert!(matches!(crowded, Err(FdtdError::InvalidPlacement(_))));
+        assert!(Simulation::one_d(20, 0.01).boundary(Boundary::Cpml(Cpml::default())).build().is_ok());
+
+        let slow: Simulation = Simulation::one_d(20, 0.01).courant(0.5).build().unwrap();
+        let fast: Simulation = Simulation::one_d(20, 0.01).build().unwrap();
+        assert_eq!(slow.grid.time_step(), 0.5 * fast.grid.time_step());
+    }
+
+    #[test]
+    fn test_line_probe_sees_symmetric_wave() {
+        let mut sim: Simulation = Simulation::two_d(61, 61, 0.05, Polarization::Tmz)
+            .courant(0.5)
+            .boundary(Boundary::Cpml(Cpml::default()))
+            .source(Source::new(FieldComponent::Ez, (30, 30, 0), pulse))
+            .probe(Probe::line(FieldComponent::Ez, (20, 30, 0), Axis::X, 21))
+            .probe(Probe::point(FieldComponent::Hy, (30, 30, 0)))
+            .build()
+            .unwrap();
+        sim.run(150).unwrap();
+        assert_eq!(sim.grid.time(), 150);
+        assert!((sim.time() - 150.0 * sim.grid.time_step()).abs() < 1e-20);
+
+        let line: &Probe = &sim.probes[0];
+        assert_eq!(line.samples.len(), 150);
+        let (left, right) = (line.series(0), line.series(20));
+        let peak: f64 = left.iter().fold(0.0, |a: f64, b| a.max(b.abs()));
+        assert!(peak > 0.0);
+        assert!(left.iter().zip(right.iter()).all(|(l, r)| (l - r).abs() <= 1e-12 * peak));
+        // the source point itself is loudest
+        assert!(line.series(10).iter().fold(0.0, |a: f64, b| a.max(b.abs())) > peak);
+        assert!(sim.probes[1].series(0).iter().any(|&h| h != 0.0));
+    }
+
+    #[test]
+    fn test_port_drives_wire() {
+        let mut sim: Simulation = Simulation::three_d(21, 21, 31, 0.01)
+            .boundary(Boundary::Cpml(Cpml {
+                thickness: 5,
+                ..Default::default()
+            }))
+            .wire(ThinWire {
+                axis: Axis::Z,
+                start: (10, 10, 8),
+                cells: 14,
+                radius: 5e-4,
+            })
+            .port(LumpedPort::new(Axis::Z, (10, 10, 15), 50.0), pulse)
+            .build()
+            .unwrap();
+        sim.run(100).unwrap();
+        let port: &LumpedPort = &sim.ports()[0];
+        assert_eq!(port.currents.len(), 100);
+        assert!(port.currents.iter().any(|i| i.abs() > 1e-3));
+        // the voltage source sits in series with 50 ohms
+        let peak: usize = (0..100).max_by(|&a, &b| port.currents[a].abs().total_cmp(&port.currents[b].abs())).unwrap();
+        assert!(port.voltages[peak].abs() < 1.0);
+    }
+}
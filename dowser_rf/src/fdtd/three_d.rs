@@ -0,0 +1,674 @@
+use std::ops::{Index, IndexMut, Range};
+
+use crate::{
+    consts::{FREE_SPACE_PERMITTIVITY, SPEED_OF_LIGHT},
+    fdtd::{
+        cpml::{Cpml, Layer},
+        dispersion::Dispersion,
+        FdtdError, Field, Material,
+    },
+};
+
+/// Courant number at the stability limit of a cubic 3D grid, 1 / sqrt(3)
+pub const COURANT_3D: f64 = 0.5773502691896258;
+
+/// Field sampled on a box shaped grid, indexed by (x, y, z)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreeDField {
+    array: Box<[f64]>,
+    size: (usize, usize, usize),
+}
+
+impl ThreeDField {
+    pub fn new_zeroed(nx: usize, ny: usize, nz: usize) -> Self {
+        Self::new_filled(nx, ny, nz, 0.0)
+    }
+    pub fn new_filled(nx: usize, ny: usize, nz: usize, value: f64) -> Self {
+        Self {
+            array: vec![value; nx * ny * nz].into_boxed_slice(),
+            size: (nx, ny, nz),
+        }
+    }
+    /// Number of samples along x, y and z
+    pub fn size(&self) -> (usize, usize, usize) {
+        self.size
+    }
+    fn offset(&self, (x, y, z): (usize, usize, usize)) -> usize {
+        debug_assert!(x < self.size.0 && y < self.size.1 && z < self.size.2);
+        (x * self.size.1 + y) * self.size.2 + z
+    }
+}
+
+impl Field for ThreeDField {
+    type Index = (usize, usize, usize);
+
+    fn get(&self, idx: Self::Index) -> &f64 {
+        &self.array[self.offset(idx)]
+    }
+
+    fn get_mut(&mut self, idx: Self::Index) -> &mut f64 {
+        let offset: usize = self.offset(idx);
+        &mut self.array[offset]
+    }
+    fn max_index(&self) -> Self::Index {
+        (self.size.0 - 1, self.size.1 - 1, self.size.2 - 1)
+    }
+}
+
+impl Index<(usize, usize, usize)> for ThreeDField {
+    type Output = f64;
+
+    fn index(&self, index: (usize, usize, usize)) -> &Self::Output {
+        self.get(index)
+    }
+}
+
+impl IndexMut<(usize, usize, usize)> for ThreeDField {
+    fn index_mut(&mut self, index: (usize, usize, usize)) -> &mut Self::Output {
+        self.get_mut(index)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+    /// The next two axes in cyclic order, so that (self, b, c) is right handed
+    pub fn others(self) -> (Axis, Axis) {
+        match self {
+            Axis::X => (Axis::Y, Axis::Z),
+            Axis::Y => (Axis::Z, Axis::X),
+            Axis::Z => (Axis::X, Axis::Y),
+        }
+    }
+}
+
+/// Moves `idx` one sample forward along `axis`
+fn forward((x, y, z): (usize, usize, usize), axis: Axis) -> (usize, usize, usize) {
+    match axis {
+        Axis::X => (x + 1, y, z),
+        Axis::Y => (x, y + 1, z),
+        Axis::Z => (x, y, z + 1),
+    }
+}
+
+/// Moves `idx` one sample back along `axis`
+fn backward((x, y, z): (usize, usize, usize), axis: Axis) -> (usize, usize, usize) {
+    match axis {
+        Axis::X => (x - 1, y, z),
+        Axis::Y => (x, y - 1, z),
+        Axis::Z => (x, y, z - 1),
+    }
+}
+
+/// `idx` with its coordinate along `axis` replaced by `value`
+fn with((x, y, z): (usize, usize, usize), axis: Axis, value: usize) -> (usize, usize, usize) {
+    match axis {
+        Axis::X => (value, y, z),
+        Axis::Y => (x, value, z),
+        Axis::Z => (x, y, value),
+    }
+}
+
+fn along((x, y, z): (usize, usize, usize), axis: Axis) -> usize {
+    match axis {
+        Axis::X => x,
+        Axis::Y => y,
+        Axis::Z => z,
+    }
+}
+
+/// One field component with the coefficients of its update equation on every sample. A
+/// component along a, with (a, b, c) right handed, is updated from the derivative along b of the
+/// other field's c component (`curl[0]`) and the derivative along c of its b component (`curl[1]`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub field: ThreeDField,
+    own: ThreeDField,
+    curl: [ThreeDField; 2],
+    /// Absorbing layers seen by the two derivatives
+    layers: [Option<Layer<ThreeDField>>; 2],
+    /// Polarization of the samples in dispersive materials, electric components only
+    dispersion: Dispersion<(usize, usize, usize)>,
+}
+
+impl Component {
+    fn new((nx, ny, nz): (usize, usize, usize)) -> Self {
+        Self {
+            field: ThreeDField::new_zeroed(nx, ny, nz),
+            own: ThreeDField::new_filled(nx, ny, nz, 1.0),
+            curl: [ThreeDField::new_zeroed(nx, ny, nz), ThreeDField::new_zeroed(nx, ny, nz)],
+            layers: [None, None],
+            dispersion: Dispersion::new(),
+        }
+    }
+    /// Difference `difference` along `axis` for derivative `slot` at `idx`, stretched inside the
+    /// absorbing layers
+    fn stretch(&mut self, slot: usize, idx: (usize, usize, usize), axis: Axis, difference: f64) -> f64 {
+        match &mut self.layers[slot] {
+            Some(layer) => layer.stretch(along(idx, axis), |s| with(idx, axis, s), difference),
+            None => difference,
+        }
+    }
+    fn contains(&self, (x, y, z): (usize, usize, usize)) -> bool {
+        let (nx, ny, nz) = self.field.size();
+        x < nx && y < ny && z < nz
+    }
+}
+
+/// Perfectly conducting wire much thinner than a cell, running along grid edges. The electric
+/// field along it is shorted, and the magnetic field circling it follows the 1/r variation
+/// around a thin wire instead of the linear one the grid assumes (Umashankar and Taflove)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThinWire {
+    pub axis: Axis,
+    /// Grid corner the wire starts at
+    pub start: (usize, usize, usize),
+    /// Number of cell edges the wire covers
+    pub cells: usize,
+    /// Radius in meters, below half a cell
+    pub radius: f64,
+}
+
+impl ThinWire {
+    /// Electric field edges the wire covers
+    fn edges(&self) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        (0..self.cells).scan(self.start, |idx, _| {
+            let current: (usize, usize, usize) = *idx;
+            *idx = forward(current, self.axis);
+            Some(current)
+        })
+    }
+}
+
+/// Voltage source in series with a resistance across one cell edge, to drive and load antennas.
+/// The voltage and current are recorded every step, both half a step after the step begins
+#[derive(Debug, Clone, PartialEq)]
+pub struct LumpedPort {
+    pub axis: Axis,
+    /// Index of the electric field edge, the port points along `axis`
+    pub edge: (usize, usize, usize),
+    /// Series resistance in ohms, must be positive
+    pub resistance: f64,
+    /// Source voltage in volts applied during the next step, raising the potential along `axis`
+    pub voltage: f64,
+    /// Voltage across the port in volts after every step
+    pub voltages: Vec<f64>,
+    /// Current in amperes out of the positive end of the port after every step
+    pub currents: Vec<f64>,
+    /// Multiplies the source voltage in the field update
+    drive: f64,
+}
+
+impl LumpedPort {
+    pub fn new(axis: Axis, edge: (usize, usize, usize), resistance: f64) -> Self {
+        Self {
+            axis,
+            edge,
+            resistance,
+            voltage: 0.0,
+            voltages: Vec::new(),
+            currents: Vec::new(),
+            drive: 0.0,
+        }
+    }
+}
+
+/// 3D Yee grid of cubic cells. The electric components sit on the cell edges and the magnetic
+/// components on the cell faces, `e[a]` and `h[a]` holding the ones along axis a. With n
+/// corners along an axis there are n - 1 cells. The tangential electric field on the outer
+/// faces is held at zero, a perfect conductor, which can be lined with an absorbing layer
+#[derive(Debug, Clone)]
+pub struct ThreeDSimulation {
+    /// Cell size in meters
+    pub cell_size: f64,
+    /// Time step in seconds, at the Courant limit
+    pub time_step: f64,
+    /// Number of steps taken so far
+    pub time: usize,
+    pub e: [Component; 3],
+    pub h: [Component; 3],
+    pub ports: Vec<LumpedPort>,
+    wires: Vec<ThinWire>,
+    /// Material of every cell, indexed like the grid corners
+    materials: Vec<Material>,
+    cpml: Option<Cpml>,
+    size: (usize, usize, usize),
+}
+
+impl ThreeDSimulation {
+    /// Vacuum filled grid of `nx` by `ny` by `nz` cell corners, all at least one, as checked by
+    /// `Simulation::three_d`
+    pub(crate) fn new(nx: usize, ny: usize, nz: usize, cell_size: f64) -> Self {
+        let e = Axis::ALL.map(|a| Component::new(shrink((nx, ny, nz), &[a])));
+        let h = Axis::ALL.map(|a| {
+            let (b, c) = a.others();
+            Component::new(shrink((nx, ny, nz), &[b, c]))
+        });
+        let mut simulation: Self = Self {
+            cell_size,
+            time_step: COURANT_3D * cell_size / SPEED_OF_LIGHT,
+            time: 0,
+            e,
+            h,
+            ports: Vec::new(),
+            wires: Vec::new(),
+            cpml: None,
+            materials: vec![Material::VACUUM; nx * ny * nz],
+            size: (nx, ny, nz),
+        };
+        simulation.refresh(0..nx, 0..ny, 0..nz);
+        simulation
+    }
+
+    /// Number of cell corners along x, y and z
+    pub fn size(&self) -> (usize, usize, usize) {
+        self.size
+    }
+
+    pub fn material(&self, idx: (usize, usize, usize)) -> Material {
+        self.materials[(idx.0 * self.size.1 + idx.1) * self.size.2 + idx.2]
+    }
+
+    /// Fills the cells in the given ranges of corner indices with `material`
+    pub fn set_material(&mut self, xs: Range<usize>, ys: Range<usize>, zs: Range<usize>, material: Material) {
+        for x in xs.clone() {
+            for y in ys.clone() {
+                for z in zs.clone() {
+                    self.materials[(x * self.size.1 + y) * self.size.2 + z] = material;
+                }
+            }
+        }
+        self.refresh(xs, ys, zs);
+    }
+
+    /// Adds a wire, which must be thinner than half a cell and run along edges of the grid
+    pub fn add_wire(&mut self, wire: ThinWire) -> Result<(), FdtdError> {
+        if wire.radius.is_nan() || wire.radius <= 0.0 || wire.radius >= 0.5 * self.cell_size {
+            return Err(FdtdError::InvalidPlacement(format!("{wire:?} is not thinner than half a cell")));
+        }
+        if let Some(edge) = wire.edges().find(|&edge| !self.e[wire.axis.index()].contains(edge)) {
+            return Err(FdtdError::InvalidPlacement(format!("wire on {:?} at {edge:?}", wire.axis)));
+        }
+        self.wires.push(wire);
+        self.apply_structures();
+        Ok(())
+    }
+
+    /// Adds a port, returning its index in `ports`. A port on a wire edge cuts a feed gap into it
+    pub fn add_port(&mut self, port: LumpedPort) -> Result<usize, FdtdError> {
+        if port.resistance.is_nan() || port.resistance <= 0.0 {
+            return Err(FdtdError::InvalidPlacement(format!("port at {:?} needs a positive resistance", port.edge)));
+        }
+        if !self.e[port.axis.index()].contains(port.edge) {
+            return Err(FdtdError::InvalidPlacement(format!("port on {:?} at {:?}", port.axis, port.edge)));
+        }
+        self.ports.push(port);
+        self.apply_structures();
+        Ok(self.ports.len() - 1)
+    }
+
+    /// Lines the walls with `cpml`, leaving the interior `2 * cpml.thickness` cells smaller
+    pub fn set_cpml(&mut self, cpml: &Cpml) {
+        let corners: [usize; 3] = [self.size.0, self.size.1, self.size.2];
+        // the electric field sits on the corners along its derivatives, the magnetic field
+        // halfway between them
+        for (components, half) in [(&mut self.e, false), (&mut self.h, true)] {
+            for a in Axis::ALL {
+                let (b, c) = a.others();
+                let component: &mut Component = &mut components[a.index()];
+                let size: (usize, usize, usize) = component.field.size();
+                for (slot, axis) in [(0, b), (1, c)] {
+                    component.layers[slot] =
+                        Some(cpml.layer(corners[axis.index()], half, self.cell_size, self.time_step, |slots| {
+                            let (nx, ny, nz) = with(size, axis, slots);
+                            ThreeDField::new_zeroed(nx, ny, nz)
+                        }));
+                }
+            }
+        }
+        self.cpml = Some(*cpml);
+    }
+
+    /// Steps with `courant` times the cell size over the speed of light, at most 1 / sqrt(3)
+    pub fn set_courant(&mut self, courant: f64) {
+        self.time_step = courant * self.cell_size / SPEED_OF_LIGHT;
+        let (nx, ny, nz) = self.size;
+        self.refresh(0..nx, 0..ny, 0..nz);
+        if let Some(cpml) = self.cpml {
+            self.set_cpml(&cpml);
+        }
+    }
+
+    /// Recomputes the coefficients of every sample belonging to the given cells
+    fn refresh(&mut self, xs: Range<usize>, ys: Range<usize>, zs: Range<usize>) {
+        for x in xs {
+            for y in ys.clone() {
+                for z in zs.clone() {
+                    let idx: (usize, usize, usize) = (x, y, z);
+                    let material: Material = self.material(idx);
+                    let electric: (f64, f64) = material.electric_coefficients(self.time_step, self.cell_size);
+                    let magnetic: (f64, f64) = material.magnetic_coefficients(self.time_step, self.cell_size);
+                    let components = self.e.iter_mut().map(|c| (c, electric));
+                    for (component, (own, curl)) in components.chain(self.h.iter_mut().map(|c| (c, magnetic))) {
+                        if component.contains(idx) {
+                            component.own[idx] = own;
+                            component.curl[0][idx] = curl;
+                            component.curl[1][idx] = curl;
+                        }
+                    }
+                }
+            }
+        }
+        self.apply_structures();
+    }
+
+    /// Writes the wires and then the ports over the material coefficients
+    fn apply_structures(&mut self) {
+        let mut scaled = Vec::new();
+        for wire in self.wires.iter() {
+            let (b, c) = wire.axis.others();
+            let scale: f64 = 2.0 / (self.cell_size / wire.radius).ln();
+            for edge in wire.edges() {
+                let e: &mut Component = &mut self.e[wire.axis.index()];
+                e.own[edge] = 0.0;
+                e.curl[0][edge] = 0.0;
+                e.curl[1][edge] = 0.0;
+                e.field[edge] = 0.0;
+                // the magnetic field on both sides of the wire, only the derivative taken across
+                // the wire sees its 1/r field
+                for (around, across, slot) in [(c, b, 1), (b, c, 0)] {
+                    let mut sides: Vec<(usize, usize, usize)> = vec![edge];
+                    if along(edge, across) > 0 {
+                        sides.push(backward(edge, across));
+                    }
+                    for side in sides.into_iter().filter(|&s| self.h[around.index()].contains(s)) {
+                        let (_, curl) = self.material(side).magnetic_coefficients(self.time_step, self.cell_size);
+                        scaled.push((around, slot, side, scale * curl));
+                    }
+                }
+            }
+        }
+        for (around, slot, side, curl) in scaled {
+            self.h[around.index()].curl[slot][side] = curl;
+        }
+
+        for p in 0..self.ports.len() {
+            let (axis, edge, resistance) = (self.ports[p].axis, self.ports[p].edge, self.ports[p].resistance);
+            let material: Material = self.material(edge);
+            let (own, curl) = material.electric_coefficients(self.time_step, self.cell_size);
+            // the resistor adds to the loss of the edge
+            let loss: f64 = (1.0 - own) / (1.0 + own);
+            let permittivity: f64 = material.permittivity * FREE_SPACE_PERMITTIVITY;
+            let beta: f64 = self.time_step / (2.0 * resistance * permittivity * self.cell_size);
+            let e: &mut Component = &mut self.e[axis.index()];
+            e.own[edge] = (1.0 - loss - beta) / (1.0 + loss + beta);
+            e.curl[0][edge] = curl * (1.0 + loss) / (1.0 + loss + beta);
+            e.curl[1][edge] = curl * (1.0 + loss) / (1.0 + loss + beta);
+            self.ports[p].drive = -2.0 * beta / self.cell_size / (1.0 + loss + beta);
+        }
+        self.track_dispersion();
+    }
+
+    /// Finds the electric samples in dispersive materials, away from the outer faces, the wires
+    /// and the ports
+    fn track_dispersion(&mut self) {
+        for a in Axis::ALL {
+            let e: &mut Component = &mut self.e[a.index()];
+            e.dispersion.clear();
+            let (nx, ny, nz) = e.field.size();
+            let range = |axis: Axis, n: usize| match axis == a {
+                true => 0..n,
+                false => 1..n - 1,
+            };
+            for x in range(Axis::X, nx) {
+                for y in range(Axis::Y, ny) {
+                    for z in range(Axis::Z, nz) {
+                        let idx: (usize, usize, usize) = (x, y, z);
+                        let shorted: bool = e.curl[0][idx] == 0.0;
+                        let port: bool = self.ports.iter().any(|p| p.axis == a && p.edge == idx);
+                        if !shorted && !port {
+                            let material: Material = self.materials[(x * self.size.1 + y) * self.size.2 + z];
+                            e.dispersion.push(idx, &material, self.time_step, self.cell_size);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Advances the fields by one time step, magnetic field first
+    pub fn step(&mut self) {
+        let previous: Vec<f64> = self.ports.iter().map(|p| self.e[p.axis.index()].field[p.edge]).collect();
+        for a in Axis::ALL {
+            self.update_h(a);
+        }
+        for a in Axis::ALL {
+            self.update_e(a);
+        }
+        for (port, old) in self.ports.iter_mut().zip(previous) {
+            let e: &mut Component = &mut self.e[port.axis.index()];
+            e.field[port.edge] += port.drive * port.voltage;
+            let voltage: f64 = -0.5 * (e.field[port.edge] + old) * self.cell_size;
+            port.voltages.push(voltage);
+            port.currents.push((port.voltage - voltage) / port.resistance);
+        }
+        self.time += 1;
+    }
+
+    /// H_a -= dE_c/db - dE_b/dc
+    fn update_h(&mut self, a: Axis) {
+        let (b, c) = a.others();
+        let (e_b, e_c) = (&self.e[b.index()].field, &self.e[c.index()].field);
+        let h: &mut Component = &mut self.h[a.index()];
+        let (nx, ny, nz) = h.field.size();
+        for x in 0..nx {
+            for y in 0..ny {
+                for z in 0..nz {
+                    let idx: (usize, usize, usize) = (x, y, z);
+                    let d_c: f64 = h.stretch(0, idx, b, e_c[forward(idx, b)] - e_c[idx]);
+                    let d_b: f64 = h.stretch(1, idx, c, e_b[forward(idx, c)] - e_b[idx]);
+                    h.field[idx] = h.own[idx] * h.field[idx] - h.curl[0][idx] * d_c + h.curl[1][idx] * d_b;
+                }
+            }
+        }
+    }
+
+    /// E_a += dH_c/db - dH_b/dc on the edges inside the grid
+    fn update_e(&mut self, a: Axis) {
+        let (b, c) = a.others();
+        let (h_b, h_c) = (&self.h[b.index()].field, &self.h[c.index()].field);
+        let e: &mut Component = &mut self.e[a.index()];
+        let (nx, ny, nz) = e.field.size();
+        let range = |axis: Axis, n: usize| match axis == a {
+            true => 0..n,
+            false => 1..n - 1,
+        };
+        e.dispersion.before(&e.field);
+        for x in range(Axis::X, nx) {
+            for y in range(Axis::Y, ny) {
+                for z in range(Axis::Z, nz) {
+                    let idx: (usize, usize, usize) = (x, y, z);
+                    let d_c: f64 = e.stretch(0, idx, b, h_c[idx] - h_c[backward(idx, b)]);
+                    let d_b: f64 = e.stretch(1, idx, c, h_b[idx] - h_b[backward(idx, c)]);
+                    e.field[idx] = e.own[idx] * e.field[idx] + e.curl[0][idx] * d_c - e.curl[1][idx] * d_b;
+                }
+            }
+        }
+        e.dispersion.after(&mut e.field);
+    }
+}
+
+/// Size of a component grid, one sample shorter along every axis it sits halfway along
+fn shrink((nx, ny, nz): (usize, usize, usize), axes: &[Axis]) -> (usize, usize, usize) {
+    let less = |axis: Axis, n: usize| if axes.contains(&axis) { n - 1 } else { n };
+    (less(Axis::X, nx), less(Axis::Y, ny), less(Axis::Z, nz))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        consts::SPEED_OF_LIGHT,
+        fdtd::{cpml::Cpml, FdtdError, Field, Material},
+    };
+
+    use super::{Axis, LumpedPort, ThinWire, ThreeDField, ThreeDSimulation};
+
+    /// Smooth ramp from 0 to 1 over `steps`
+    fn ramp(step: usize, steps: usize) -> f64 {
+        let t: f64 = (step as f64 / steps as f64).min(1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    #[test]
+    fn test_three_d_field_indexing() {
+        let mut field: ThreeDField = ThreeDField::new_zeroed(2, 3, 4);
+        field[(1, 2, 3)] = 5.0;
+        *field.get_mut((0, 1, 2)) += 1.0;
+        assert_eq!(field.max_index(), (1, 2, 3));
+        assert_eq!(*field.get((1, 2, 3)), 5.0);
+        assert_eq!(field[(0, 1, 2)], 1.0);
+        assert_eq!(field.array.iter().sum::<f64>(), 6.0);
+    }
+
+    #[test]
+    fn test_pulse_spreads_at_light_speed() {
+        let mut sim: ThreeDSimulation = ThreeDSimulation::new(41, 41, 41, 0.01);
+        // (step, value) of the largest Ez 6 and 12 cells out along x and along y
+        let mut peaks: [(usize, f64); 2] = [(0, 0.0); 2];
+        for q in 0..90 {
+            sim.e[2].field[(20, 20, 20)] += (-((q as f64 - 25.0) / 8.0).powi(2)).exp();
+            sim.step();
+            for (peak, x) in peaks.iter_mut().zip([26, 32]) {
+                let value: f64 = sim.e[2].field[(x, 20, 20)].abs();
+                if value > peak.1 {
+                    *peak = (q, value);
+                }
+            }
+            let scale: f64 = peaks[0].1.max(1e-300);
+            assert!((sim.e[2].field[(32, 20, 20)] - sim.e[2].field[(20, 32, 20)]).abs() <= 1e-12 * scale);
+            assert!((sim.e[2].field[(32, 20, 20)] - sim.e[2].field[(8, 20, 20)]).abs() <= 1e-12 * scale);
+        }
+        let travel: f64 = 6.0 * sim.cell_size / SPEED_OF_LIGHT / sim.time_step;
+        let lag: f64 = peaks[1].0 as f64 - peaks[0].0 as f64;
+        dbg!(lag, travel);
+        assert!((lag - travel).abs() < 2.0);
+    }
+
+    #[test]
+    fn test_open_port_charges_to_source_voltage() {
+        // an open port is a small capacitor charging through its resistance, slowly enough
+        // that it follows the source
+        let mut sim: ThreeDSimulation = ThreeDSimulation::new(21, 21, 21, 0.01);
+        let port: usize = sim.add_port(LumpedPort::new(Axis::Z, (10, 10, 10), 50.0)).unwrap();
+        for q in 0..600 {
+            sim.ports[port].voltage = ramp(q, 300);
+            sim.step();
+        }
+        let port: &LumpedPort = &sim.ports[port];
+        dbg!(port.voltages[599], port.currents[599]);
+        assert!((port.voltages[599] - 1.0).abs() < 0.02);
+        assert!(port.currents[599].abs() < 1e-3);
+        assert_eq!(port.voltages.len(), 600);
+    }
+
+    #[test]
+    fn test_thin_wire_shorts_and_carries_current() {
+        let run = |radius: f64| {
+            let mut sim: ThreeDSimulation = ThreeDSimulation::new(31, 31, 41, 0.01);
+            sim.add_wire(ThinWire {
+                axis: Axis::Z,
+                start: (15, 15, 10),
+                cells: 20,
+                radius,
+            })
+            .unwrap();
+            let port: usize = sim.add_port(LumpedPort::new(Axis::Z, (15, 15, 20), 50.0)).unwrap();
+            for q in 0..120 {
+                sim.ports[port].voltage = (-((q as f64 - 40.0) / 12.0).powi(2)).exp();
+                sim.step();
+            }
+            // the field along the wire away from the gap stays shorted
+            assert_eq!(sim.e[2].field[(15, 15, 12)], 0.0);
+            assert!(sim.e[2].field[(16, 15, 12)] != 0.0);
+            sim.ports[port].currents.iter().fold(0.0, |a: f64, &i| a.max(i.abs()))
+        };
+        let (thin, thick) = (run(1e-4), run(2e-3));
+        dbg!(thin, thick);
+        // a thicker wire has less inductance and takes more current from the same pulse
+        assert!(thick > 1.1 * thin);
+        assert!(thin > 0.0);
+    }
+
+    #[test]
+    fn test_rejects_wires_and_ports_it_cannot_hold() {
+        let mut sim: ThreeDSimulation = ThreeDSimulation::new(11, 11, 11, 0.01);
+        let wire: ThinWire = ThinWire {
+            axis: Axis::Z,
+            start: (5, 5, 2),
+            cells: 6,
+            radius: 1e-3,
+        };
+        for radius in [0.0, 5e-3, 1e-2, f64::NAN] {
+            assert!(matches!(sim.add_wire(ThinWire { radius, ..wire }), Err(FdtdError::InvalidPlacement(_))));
+        }
+        // 10 corners along z past 2 leave 8 edges, and no z edge starts on the last corner
+        assert!(sim.add_wire(ThinWire { cells: 9, ..wire }).is_err());
+        assert!(sim.add_port(LumpedPort::new(Axis::Z, (5, 5, 10), 50.0)).is_err());
+        assert!(sim.add_port(LumpedPort::new(Axis::Z, (5, 5, 5), 0.0)).is_err());
+        assert!(sim.wires.is_empty() && sim.ports.is_empty());
+
+        sim.add_wire(ThinWire { cells: 8, ..wire }).unwrap();
+        assert_eq!(sim.add_port(LumpedPort::new(Axis::Z, (5, 5, 5), 50.0)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_material_sets_coefficients() {
+        let mut sim: ThreeDSimulation = ThreeDSimulation::new(21, 21, 21, 0.01);
+        sim.set_material(0..21, 0..21, 0..21, Material::dielectric(4.0, 0.0));
+        let (own, curl) = Material::dielectric(4.0, 0.0).electric_coefficients(sim.time_step, sim.cell_size);
+        assert_eq!(sim.e[0].own[(3, 3, 3)], own);
+        assert_eq!(sim.e[0].curl[1][(3, 3, 3)], curl);
+    }
+
+    #[test]
+    fn test_cpml_absorbs() {
+        // Ez broadside to a point source 6 cells out, compared with a grid large enough that
+        // nothing comes back from its walls in time. The source is a derivative of a Gaussian so
+        // it leaves no static charge behind
+        let run = |size: usize, cpml: Option<Cpml>| {
+            let mut sim: ThreeDSimulation = ThreeDSimulation::new(size, size, size, 0.01);
+            if let Some(cpml) = cpml {
+                sim.set_cpml(&cpml);
+            }
+            let center: usize = size / 2;
+            (0..60)
+                .map(|q| {
+                    let t: f64 = (q as f64 - 15.0) / 4.0;
+                    sim.e[2].field[(center, center, center)] += -t * (-t * t).exp();
+                    sim.step();
+                    sim.e[2].field[(center, center + 6, center)]
+                })
+                .collect::<Vec<f64>>()
+        };
+        let reference: Vec<f64> = run(51, None);
+        let cpml: Cpml = Cpml {
+            thickness: 8,
+            ..Default::default()
+        };
+        let absorbed: Vec<f64> = run(31, Some(cpml));
+        let peak: f64 = reference.iter().fold(0.0, |a: f64, b| a.max(b.abs()));
+        let error: f64 = reference.iter().zip(absorbed.iter()).fold(0.0, |a: f64, (r, b)| a.max((r - b).abs()));
+        dbg!(20.0 * (error / peak).log10());
+        assert!(20.0 * (error / peak).log10() < -40.0);
+    }
+}
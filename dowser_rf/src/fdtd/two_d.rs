@@ -0,0 +1,480 @@
+use std::{
+    f64::consts::SQRT_2,
+    ops::{Index, IndexMut, Range},
+};
+
+use crate::{
+    consts::SPEED_OF_LIGHT,
+    fdtd::{
+        cpml::{Cpml, Layer},
+        dispersion::Dispersion,
+        Field, Material,
+    },
+};
+
+/// Courant number at the stability limit of a square 2D grid
+pub const COURANT_2D: f64 = 1.0 / SQRT_2;
+
+/// Field sampled on a rectangular grid, indexed by (x, y)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoDField {
+    array: Box<[f64]>,
+    size: (usize, usize),
+}
+
+impl TwoDField {
+    pub fn new_zeroed(nx: usize, ny: usize) -> Self {
+        Self::new_filled(nx, ny, 0.0)
+    }
+    pub fn new_filled(nx: usize, ny: usize, value: f64) -> Self {
+        Self {
+            array: vec![value; nx * ny].into_boxed_slice(),
+            size: (nx, ny),
+        }
+    }
+    /// Number of samples along x and y
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+    fn offset(&self, (x, y): (usize, usize)) -> usize {
+        debug_assert!(x < self.size.0 && y < self.size.1);
+        x * self.size.1 + y
+    }
+}
+
+impl Field for TwoDField {
+    type Index = (usize, usize);
+
+    fn get(&self, idx: Self::Index) -> &f64 {
+        &self.array[self.offset(idx)]
+    }
+
+    fn get_mut(&mut self, idx: Self::Index) -> &mut f64 {
+        let offset: usize = self.offset(idx);
+        &mut self.array[offset]
+    }
+    fn max_index(&self) -> Self::Index {
+        (self.size.0 - 1, self.size.1 - 1)
+    }
+}
+
+impl Index<(usize, usize)> for TwoDField {
+    type Output = f64;
+
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        self.get(index)
+    }
+}
+
+impl IndexMut<(usize, usize)> for TwoDField {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        self.get_mut(index)
+    }
+}
+
+/// Field components solved for in a 2D simulation, everything is uniform along z
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polarization {
+    /// Ez, Hx and Hy: the electric field is normal to the plane, like a vertical wire or tower
+    /// seen in cross-section
+    Tmz,
+    /// Hz, Ex and Ey: the electric field lies in the plane
+    Tez,
+}
+
+/// One field component with the coefficients of its update equation on every sample
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub field: TwoDField,
+    /// Multiplies the old value
+    own: TwoDField,
+    /// Multiplies the curl of the other field
+    curl: TwoDField,
+    /// Absorbing layers seen by the derivatives along x and along y
+    along_x: Option<Layer<TwoDField>>,
+    along_y: Option<Layer<TwoDField>>,
+    /// Polarization of the samples in dispersive materials, electric components only
+    dispersion: Dispersion<(usize, usize)>,
+}
+
+impl Component {
+    /// Curl from the differences along x and y, stretched inside the absorbing layers
+    fn stretch(&mut self, (m, n): (usize, usize), along_x: f64, along_y: f64) -> f64 {
+        let along_x: f64 = match &mut self.along_x {
+            Some(layer) => layer.stretch(m, |slot| (slot, n), along_x),
+            None => along_x,
+        };
+        let along_y: f64 = match &mut self.along_y {
+            Some(layer) => layer.stretch(n, |slot| (m, slot), along_y),
+            None => along_y,
+        };
+        along_x - along_y
+    }
+
+    fn new(nx: usize, ny: usize) -> Self {
+        Self {
+            field: TwoDField::new_zeroed(nx, ny),
+            own: TwoDField::new_filled(nx, ny, 1.0),
+            curl: TwoDField::new_zeroed(nx, ny),
+            along_x: None,
+            along_y: None,
+            dispersion: Dispersion::new(),
+        }
+    }
+}
+
+/// 2D Yee grid of square cells, with Ez at the cell corners in TMz and Hz at the cell centers in
+/// TEz. The components are named after their direction only:
+///
+/// | field | TMz | TEz |
+/// |-------|-----|-----|
+/// | `z`   | Ez, nx by ny | Hz, nx - 1 by ny - 1 |
+/// | `x`   | Hx, nx by ny - 1 | Ex, nx - 1 by ny |
+/// | `y`   | Hy, nx - 1 by ny | Ey, nx by ny - 1 |
+///
+/// The tangential electric field on the outer edge is held at zero, a perfect conductor, which
+/// can be lined with an absorbing layer.
+#[derive(Debug, Clone)]
+pub struct TwoDSimulation {
+    pub polarization: Polarization,
+    /// Cell size in meters
+    pub cell_size: f64,
+    /// Time step in seconds, at the Courant limit
+    pub time_step: f64,
+    /// Number of steps taken so far
+    pub time: usize,
+    pub z: Component,
+    pub x: Component,
+    pub y: Component,
+    /// Material of every cell, indexed like the grid corners
+    materials: Vec<Material>,
+    cpml: Option<Cpml>,
+    size: (usize, usize),
+}
+
+impl TwoDSimulation {
+    /// Vacuum filled grid of `nx` by `ny` cell corners, both at least one, as checked by
+    /// `Simulation::two_d`
+    pub(crate) fn new(nx: usize, ny: usize, cell_size: f64, polarization: Polarization) -> Self {
+        let (z, x, y) = match polarization {
+            Polarization::Tmz => (Component::new(nx, ny), Component::new(nx, ny - 1), Component::new(nx - 1, ny)),
+            Polarization::Tez => (
+                Component::new(nx - 1, ny - 1),
+                Component::new(nx - 1, ny),
+                Component::new(nx, ny - 1),
+            ),
+        };
+        let mut simulation: Self = Self {
+            polarization,
+            cell_size,
+            time_step: COURANT_2D * cell_size / SPEED_OF_LIGHT,
+            time: 0,
+            z,
+            x,
+            y,
+            cpml: None,
+            materials: vec![Material::VACUUM; nx * ny],
+            size: (nx, ny),
+        };
+        for m in 0..nx {
+            for n in 0..ny {
+                simulation.refresh(m, n);
+            }
+        }
+        simulation.track_dispersion();
+        simulation
+    }
+
+    /// Number of cell corners along x and y
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    pub fn material(&self, m: usize, n: usize) -> Material {
+        self.materials[m * self.size.1 + n]
+    }
+
+    /// Fills the cells in the given ranges of corner indices with `material`
+    pub fn set_material(&mut self, xs: Range<usize>, ys: Range<usize>, material: Material) {
+        for m in xs {
+            for n in ys.clone() {
+                self.materials[m * self.size.1 + n] = material;
+                self.refresh(m, n);
+            }
+        }
+        self.track_dispersion();
+    }
+
+    /// Lines the walls with `cpml`, leaving the interior `2 * cpml.thickness` cells smaller
+    pub fn set_cpml(&mut self, cpml: &Cpml) {
+        let (nx, ny) = self.size;
+        let (dx, dt) = (self.cell_size, self.time_step);
+        // the transverse components of TMz sit halfway between the corners along their
+        // derivative, and Hz of TEz halfway along both
+        let half: bool = self.polarization == Polarization::Tmz;
+        let (x_size, y_size, z_size) = (self.x.field.size(), self.y.field.size(), self.z.field.size());
+        self.x.along_y = Some(cpml.layer(ny, half, dx, dt, |slots| TwoDField::new_zeroed(x_size.0, slots)));
+        self.y.along_x = Some(cpml.layer(nx, half, dx, dt, |slots| TwoDField::new_zeroed(slots, y_size.1)));
+        self.z.along_x = Some(cpml.layer(nx, !half, dx, dt, |slots| TwoDField::new_zeroed(slots, z_size.1)));
+        self.z.along_y = Some(cpml.layer(ny, !half, dx, dt, |slots| TwoDField::new_zeroed(z_size.0, slots)));
+        self.cpml = Some(*cpml);
+    }
+
+    /// Steps with `courant` times the cell size over the speed of light, at most 1 / sqrt(2)
+    pub fn set_courant(&mut self, courant: f64) {
+        self.time_step = courant * self.cell_size / SPEED_OF_LIGHT;
+        for m in 0..self.size.0 {
+            for n in 0..self.size.1 {
+                self.refresh(m, n);
+            }
+        }
+        self.track_dispersion();
+        if let Some(cpml) = self.cpml {
+            self.set_cpml(&cpml);
+        }
+    }
+
+    /// Finds the electric samples in dispersive materials, away from the outer edge
+    fn track_dispersion(&mut self) {
+        let (nx, ny) = self.size;
+        let (dt, dx) = (self.time_step, self.cell_size);
+        for component in [&mut self.z, &mut self.x, &mut self.y] {
+            component.dispersion.clear();
+        }
+        for m in 0..nx {
+            for n in 0..ny {
+                let material: Material = self.materials[m * ny + n];
+                let inner_x: bool = m > 0 && m < nx - 1;
+                let inner_y: bool = n > 0 && n < ny - 1;
+                match self.polarization {
+                    Polarization::Tmz if inner_x && inner_y => self.z.dispersion.push((m, n), &material, dt, dx),
+                    Polarization::Tmz => {}
+                    Polarization::Tez => {
+                        if m < nx - 1 && inner_y {
+                            self.x.dispersion.push((m, n), &material, dt, dx);
+                        }
+                        if n < ny - 1 && inner_x {
+                            self.y.dispersion.push((m, n), &material, dt, dx);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Recomputes the coefficients of the samples belonging to cell (m, n)
+    fn refresh(&mut self, m: usize, n: usize) {
+        let material: Material = self.material(m, n);
+        let electric: (f64, f64) = material.electric_coefficients(self.time_step, self.cell_size);
+        let magnetic: (f64, f64) = material.magnetic_coefficients(self.time_step, self.cell_size);
+        let (z, transverse) = match self.polarization {
+            Polarization::Tmz => (electric, magnetic),
+            Polarization::Tez => (magnetic, electric),
+        };
+        for (component, (own, curl)) in [(&mut self.z, z), (&mut self.x, transverse), (&mut self.y, transverse)] {
+            let (nx, ny) = component.field.size();
+            if m < nx && n < ny {
+                component.own[(m, n)] = own;
+                component.curl[(m, n)] = curl;
+            }
+        }
+    }
+
+    /// Advances the fields by one time step, magnetic field first
+    pub fn step(&mut self) {
+        match self.polarization {
+            Polarization::Tmz => {
+                self.update_x();
+                self.update_y();
+                self.update_z();
+            }
+            Polarization::Tez => {
+                self.update_z();
+                self.update_x();
+                self.update_y();
+            }
+        }
+        self.time += 1;
+    }
+
+    /// Hx -= dEz/dy in TMz, Ex += dHz/dy in TEz
+    fn update_x(&mut self) {
+        let (nx, ny) = self.x.field.size();
+        let (sign, rows) = match self.polarization {
+            Polarization::Tmz => (-1.0, 0..ny),
+            Polarization::Tez => (1.0, 1..ny - 1),
+        };
+        let x: &mut Component = &mut self.x;
+        let z: &TwoDField = &self.z.field;
+        x.dispersion.before(&x.field);
+        for m in 0..nx {
+            for n in rows.clone() {
+                let mut curl: f64 = match self.polarization {
+                    Polarization::Tmz => z[(m, n + 1)] - z[(m, n)],
+                    Polarization::Tez => z[(m, n)] - z[(m, n - 1)],
+                };
+                if let Some(layer) = &mut x.along_y {
+                    curl = layer.stretch(n, |slot| (m, slot), curl);
+                }
+                x.field[(m, n)] = x.own[(m, n)] * x.field[(m, n)] + sign * x.curl[(m, n)] * curl;
+            }
+        }
+        x.dispersion.after(&mut x.field);
+    }
+
+    /// Hy += dEz/dx in TMz, Ey -= dHz/dx in TEz
+    fn update_y(&mut self) {
+        let (nx, ny) = self.y.field.size();
+        let (sign, columns) = match self.polarization {
+            Polarization::Tmz => (1.0, 0..nx),
+            Polarization::Tez => (-1.0, 1..nx - 1),
+        };
+        let y: &mut Component = &mut self.y;
+        let z: &TwoDField = &self.z.field;
+        y.dispersion.before(&y.field);
+        for m in columns {
+            for n in 0..ny {
+                let mut curl: f64 = match self.polarization {
+                    Polarization::Tmz => z[(m + 1, n)] - z[(m, n)],
+                    Polarization::Tez => z[(m, n)] - z[(m - 1, n)],
+                };
+                if let Some(layer) = &mut y.along_x {
+                    curl = layer.stretch(m, |slot| (slot, n), curl);
+                }
+                y.field[(m, n)] = y.own[(m, n)] * y.field[(m, n)] + sign * y.curl[(m, n)] * curl;
+            }
+        }
+        y.dispersion.after(&mut y.field);
+    }
+
+    /// Ez += dHy/dx - dHx/dy in TMz on the inner corners, Hz -= dEy/dx - dEx/dy in TEz
+    fn update_z(&mut self) {
+        let (nx, ny) = self.z.field.size();
+        let (x, y) = (&self.x.field, &self.y.field);
+        let z: &mut Component = &mut self.z;
+        z.dispersion.before(&z.field);
+        match self.polarization {
+            Polarization::Tmz => {
+                for m in 1..nx - 1 {
+                    for n in 1..ny - 1 {
+                        let curl: f64 = z.stretch((m, n), y[(m, n)] - y[(m - 1, n)], x[(m, n)] - x[(m, n - 1)]);
+                        z.field[(m, n)] = z.own[(m, n)] * z.field[(m, n)] + z.curl[(m, n)] * curl;
+                    }
+                }
+            }
+            Polarization::Tez => {
+                for m in 0..nx {
+                    for n in 0..ny {
+                        let curl: f64 = z.stretch((m, n), y[(m + 1, n)] - y[(m, n)], x[(m, n + 1)] - x[(m, n)]);
+                        z.field[(m, n)] = z.own[(m, n)] * z.field[(m, n)] - z.curl[(m, n)] * curl;
+                    }
+                }
+            }
+        }
+        z.dispersion.after(&mut z.field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        consts::SPEED_OF_LIGHT,
+        fdtd::{cpml::Cpml, Field, Material},
+    };
+
+    use super::{Polarization, TwoDField, TwoDSimulation};
+
+    /// Gaussian pulse about 20 steps wide, peaking at step 60
+    fn pulse(step: usize) -> f64 {
+        (-((step as f64 - 60.0) / 20.0).powi(2)).exp()
+    }
+
+    #[test]
+    fn test_two_d_field_indexing() {
+        let mut field: TwoDField = TwoDField::new_zeroed(3, 4);
+        field[(2, 1)] = 5.0;
+        *field.get_mut((0, 3)) += 1.0;
+        assert_eq!(field.max_index(), (2, 3));
+        assert_eq!(*field.get((2, 1)), 5.0);
+        assert_eq!(field[(0, 3)], 1.0);
+    }
+
+    #[test]
+    fn test_pulse_spreads_at_light_speed() {
+        // Ez sits on the corners and Hz in the middle of the cells, so the walls are symmetric
+        // about the source with an odd grid for one and an even grid for the other
+        for (polarization, size) in [(Polarization::Tmz, 121), (Polarization::Tez, 122)] {
+            let mut sim: TwoDSimulation = TwoDSimulation::new(size, size, 0.01, polarization);
+            // (step, value) of the largest field 20 and 40 cells away
+            let mut peaks: [(usize, f64); 2] = [(0, 0.0); 2];
+            for q in 0..180 {
+                sim.z.field[(60, 60)] += pulse(q);
+                sim.step();
+                for (peak, n) in peaks.iter_mut().zip([80, 100]) {
+                    if sim.z.field[(60, n)].abs() > peak.1 {
+                        *peak = (q, sim.z.field[(60, n)].abs());
+                    }
+                }
+                // symmetric about the source
+                let scale: f64 = peaks[0].1.max(1e-300);
+                assert!((sim.z.field[(60, 100)] - sim.z.field[(100, 60)]).abs() <= 1e-12 * scale);
+                assert!((sim.z.field[(60, 100)] - sim.z.field[(60, 20)]).abs() <= 1e-12 * scale);
+            }
+            // the peak takes the light travel time to cover the 20 cells between the probes
+            let travel: f64 = 20.0 * sim.cell_size / SPEED_OF_LIGHT / sim.time_step;
+            let lag: f64 = peaks[1].0 as f64 - peaks[0].0 as f64;
+            dbg!(polarization, lag, travel);
+            assert!((lag - travel).abs() < 2.0);
+        }
+    }
+
+    #[test]
+    fn test_conductive_soil_attenuates() {
+        // the lower half of the grid is wet soil, the source sits on its surface
+        let run = |soil: Material| {
+            let mut sim: TwoDSimulation = TwoDSimulation::new(161, 121, 0.05, Polarization::Tmz);
+            sim.set_material(0..161, 0..60, soil);
+            let mut peak: (f64, f64) = (0.0, 0.0);
+            for q in 0..220 {
+                sim.z.field[(80, 62)] += pulse(q);
+                sim.step();
+                peak.0 = peak.0.max(sim.z.field[(80, 20)].abs());
+                peak.1 = peak.1.max(sim.z.field[(80, 102)].abs());
+            }
+            peak
+        };
+        let dry: (f64, f64) = run(Material::VACUUM);
+        let wet: (f64, f64) = run(Material::dielectric(30.0, 0.05));
+        dbg!(dry, wet);
+        assert!(wet.0 < 0.1 * dry.0);
+        assert!(wet.1 > 0.0);
+    }
+
+    #[test]
+    fn test_cpml_absorbs() {
+        // the grid without a layer is large enough that its walls stay out of sight, the
+        // difference to it is what the layer reflects
+        for polarization in [Polarization::Tmz, Polarization::Tez] {
+            let run = |size: usize, cpml: Option<Cpml>| {
+                let mut sim: TwoDSimulation = TwoDSimulation::new(size, size, 0.01, polarization);
+                if let Some(cpml) = cpml {
+                    sim.set_cpml(&cpml);
+                }
+                let center: usize = size / 2;
+                (0..260)
+                    .map(|q| {
+                        sim.z.field[(center, center)] += pulse(q);
+                        sim.step();
+                        sim.z.field[(center + 5, center + 30)]
+                    })
+                    .collect::<Vec<f64>>()
+            };
+            let reference: Vec<f64> = run(401, None);
+            let absorbed: Vec<f64> = run(81, Some(Cpml::default()));
+            let peak: f64 = reference.iter().fold(0.0, |a: f64, b| a.max(b.abs()));
+            let error: f64 = reference.iter().zip(absorbed.iter()).fold(0.0, |a: f64, (r, b)| a.max((r - b).abs()));
+            dbg!(polarization, 20.0 * (error / peak).log10());
+            assert!(20.0 * (error / peak).log10() < -40.0);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::f64::consts::PI;
+
+use crate::fdtd::simulation::Waveform;
+
+/// Widths a pulse is delayed by so it starts from about 1e-7 of its peak
+const DELAY_WIDTHS: f64 = 4.0;
+
+/// Width tau of exp(-(t / tau)^2) whose spectrum has fallen by 20 dB at `frequency` off its peak
+fn width(frequency: f64) -> f64 {
+    10f64.ln().sqrt() / (PI * frequency)
+}
+
+/// amplitude exp(-((t - delay) / width)^2)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gaussian {
+    pub amplitude: f64,
+    /// Width in seconds
+    pub width: f64,
+    /// Time of the peak in seconds
+    pub delay: f64,
+}
+
+impl Gaussian {
+    /// Unit pulse whose spectrum runs from DC to `bandwidth` in Hz within 20 dB
+    pub fn new(bandwidth: f64) -> Self {
+        let width: f64 = width(bandwidth);
+        Self {
+            amplitude: 1.0,
+            width,
+            delay: DELAY_WIDTHS * width,
+        }
+    }
+}
+
+impl Waveform for Gaussian {
+    fn value(&self, time: f64) -> f64 {
+        self.amplitude * (-((time - self.delay) / self.width).powi(2)).exp()
+    }
+}
+
+/// First derivative of a Gaussian scaled to a peak of `amplitude`, a pulse without DC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DifferentiatedGaussian {
+    pub amplitude: f64,
+    /// Width in seconds
+    pub width: f64,
+    /// Time of the zero crossing in seconds
+    pub delay: f64,
+}
+
+impl DifferentiatedGaussian {
+    /// Unit pulse whose spectrum peaks at `peak_frequency` in Hz
+    pub fn new(peak_frequency: f64) -> Self {
+        let width: f64 = 1.0 / (PI * 2f64.sqrt() * peak_frequency);
+        Self {
+            amplitude: 1.0,
+            width,
+            delay: DELAY_WIDTHS * width,
+        }
+    }
+}
+
+impl Waveform for DifferentiatedGaussian {
+    fn value(&self, time: f64) -> f64 {
+        let u: f64 = (time - self.delay) / self.width;
+        // the extremes at u = -+1/sqrt(2) reach sqrt(2/e)
+        -self.amplitude * (2.0 * std::f64::consts::E).sqrt() * u * (-u * u).exp()
+    }
+}
+
+/// Second derivative of a Gaussian, the Mexican hat, amplitude (1 - 2 u^2) exp(-u^2)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ricker {
+    pub amplitude: f64,
+    /// Width in seconds
+    pub width: f64,
+    /// Time of the peak in seconds
+    pub delay: f64,
+}
+
+impl Ricker {
+    /// Unit pulse whose spectrum peaks at `peak_frequency` in Hz
+    pub fn new(peak_frequency: f64) -> Self {
+        let width: f64 = 1.0 / (PI * peak_frequency);
+        Self {
+            amplitude: 1.0,
+            width,
+            delay: DELAY_WIDTHS * width,
+        }
+    }
+}
+
+impl Waveform for Ricker {
+    fn value(&self, time: f64) -> f64 {
+        let u2: f64 = ((time - self.delay) / self.width).powi(2);
+        self.amplitude * (1.0 - 2.0 * u2) * (-u2).exp()
+    }
+}
+
+/// Gaussian envelope on a cosine carrier, for exciting one band
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModulatedGaussian {
+    pub amplitude: f64,
+    /// Carrier frequency in Hz
+    pub frequency: f64,
+    /// Width of the envelope in seconds
+    pub width: f64,
+    /// Time of the envelope peak in seconds
+    pub delay: f64,
+}
+
+impl ModulatedGaussian {
+    /// Unit pulse centered on `frequency` whose spectrum is `bandwidth` wide within 20 dB, both in
+    /// Hz
+    pub fn new(frequency: f64, bandwidth: f64) -> Self {
+        let width: f64 = width(bandwidth / 2.0);
+        Self {
+            amplitude: 1.0,
+            frequency,
+            width,
+            delay: DELAY_WIDTHS * width,
+        }
+    }
+
+    /// Unit pulse spanning the band from `low` to `high` in Hz within 20 dB, like 7.0e6 to 7.3e6
+    pub fn band(low: f64, high: f64) -> Self {
+        Self::new((low + high) / 2.0, high - low)
+    }
+}
+
+impl Waveform for ModulatedGaussian {
+    fn value(&self, time: f64) -> f64 {
+        let t: f64 = time - self.delay;
+        self.amplitude * (-(t / self.width).powi(2)).exp() * (2.0 * PI * self.frequency * t).cos()
+    }
+}
+
+/// Sine starting from zero whose amplitude rises over `ramp` as a raised cosine, for driving to a
+/// steady state without exciting far off frequencies
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RampedSinusoid {
+    pub amplitude: f64,
+    /// Frequency in Hz
+    pub frequency: f64,
+    /// Rise time in seconds
+    pub ramp: f64,
+}
+
+impl RampedSinusoid {
+    /// Unit sine at `frequency` in Hz reaching full amplitude after `cycles` periods
+    pub fn new(frequency: f64, cycles: f64) -> Self {
+        Self {
+            amplitude: 1.0,
+            frequency,
+            ramp: cycles / frequency,
+        }
+    }
+}
+
+impl Waveform for RampedSinusoid {
+    fn value(&self, time: f64) -> f64 {
+        let envelope: f64 = match time {
+            t if t <= 0.0 => 0.0,
+            t if t < self.ramp => 0.5 * (1.0 - (PI * t / self.ramp).cos()),
+            _ => 1.0,
+        };
+        self.amplitude * envelope * (2.0 * PI * self.frequency * time).sin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use num_complex::Complex;
+
+    use crate::fdtd::simulation::Waveform;
+
+    use super::{DifferentiatedGaussian, Gaussian, ModulatedGaussian, RampedSinusoid, Ricker};
+
+    /// |Fourier transform| at `frequency`, from 0 to `duration`
+    fn spectrum(waveform: &impl Waveform, frequency: f64, duration: f64) -> f64 {
+        let steps: usize = 20000;
+        let dt: f64 = duration / steps as f64;
+        let sum: Complex<f64> = (0..steps)
+            .map(|n| {
+                let t: f64 = n as f64 * dt;
+                waveform.value(t) * Complex::from_polar(1.0, -2.0 * PI * frequency * t)
+            })
+            .sum();
+        (sum * dt).norm()
+    }
+
+    fn decibels(ratio: f64) -> f64 {
+        20.0 * ratio.log10()
+    }
+
+    #[test]
+    fn test_gaussian_bandwidth() {
+        let gaussian: Gaussian = Gaussian::new(30e6);
+        assert!(gaussian.value(0.0) < 1e-6);
+        assert_eq!(gaussian.value(gaussian.delay), 1.0);
+        let duration: f64 = 2.0 * gaussian.delay;
+        let dc: f64 = spectrum(&gaussian, 0.0, duration);
+        assert!((decibels(spectrum(&gaussian, 30e6, duration) / dc) + 20.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_derivative_pulses_peak() {
+        let ricker: Ricker = Ricker::new(10e6);
+        let differentiated: DifferentiatedGaussian = DifferentiatedGaussian::new(10e6);
+        assert_eq!(ricker.value(ricker.delay), 1.0);
+        let extreme: f64 = differentiated.delay - differentiated.width / 2f64.sqrt();
+        assert!((differentiated.value(extreme) - 1.0).abs() < 1e-12);
+        for (waveform, delay) in [(&ricker as &dyn Waveform, ricker.delay), (&differentiated, differentiated.delay)] {
+            let duration: f64 = 2.0 * delay;
+            let at = |f: f64| spectrum(&|t: f64| waveform.value(t), f, duration);
+            assert!(at(0.0) < 1e-3 * at(10e6));
+            assert!(at(10e6) > at(9e6) && at(10e6) > at(11e6));
+        }
+    }
+
+    #[test]
+    fn test_modulated_gaussian_covers_band() {
+        let pulse: ModulatedGaussian = ModulatedGaussian::band(7.0e6, 7.3e6);
+        assert_eq!(pulse.frequency, 7.15e6);
+        let duration: f64 = 2.0 * pulse.delay;
+        let center: f64 = spectrum(&pulse, 7.15e6, duration);
+        for edge in [7.0e6, 7.3e6] {
+            assert!((decibels(spectrum(&pulse, edge, duration) / center) + 20.0).abs() < 0.1);
+        }
+        assert!(spectrum(&pulse, 3.5e6, duration) < 1e-6 * center);
+    }
+
+    #[test]
+    fn test_ramped_sinusoid() {
+        let sine: RampedSinusoid = RampedSinusoid::new(1e6, 3.0);
+        assert_eq!(sine.value(-1e-7), 0.0);
+        assert_eq!(sine.ramp, 3e-6);
+        assert!(sine.value(0.25e-6).abs() < 0.1);
+        assert!((sine.value(5.25e-6) - 1.0).abs() < 1e-12);
+    }
+}
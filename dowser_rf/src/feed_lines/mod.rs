@@ -1 +1 @@
-mod coax_line;
+pub(crate) mod coax_line;
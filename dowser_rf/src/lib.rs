@@ -1,9 +1,19 @@
+// the circuit models are not exported yet, so most of them are only reached from tests
+#[allow(dead_code)]
 mod consts;
+#[allow(dead_code)]
 mod antenna;
+#[allow(dead_code)]
 mod antennas;
+#[allow(dead_code)]
 mod feed_line;
+#[allow(dead_code)]
 mod feed_lines;
+pub mod pattern;
+#[allow(dead_code)]
 mod propagation;
+pub mod quadrature;
+#[allow(dead_code)]
 mod util;
-mod fdtd;
-mod mom;
\ No newline at end of file
+pub mod fdtd;
+pub mod mom;
@@ -0,0 +1,288 @@
+use std::fmt;
+
+use num_complex::Complex;
+
+use crate::mom::{
+    excitation::VoltageSource,
+    geometry::WireGeometry,
+    load::Load,
+    solver::ThinWireSolver,
+    MomError,
+};
+
+/// Segment length to radius ratio below which the thin-wire kernel stops being accurate, NEC
+/// recommends staying above 8 for errors under 1%
+pub const THIN_WIRE_LIMIT: f64 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConvergenceSettings {
+    /// Largest relative change of the feedpoint impedance between two passes that counts as
+    /// converged
+    pub tolerance: f64,
+    /// Factor the segment count of every wire grows by from one pass to the next
+    pub refinement: f64,
+    /// Number of solves before giving up, the starting model included
+    pub max_passes: usize,
+}
+
+impl Default for ConvergenceSettings {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.01,
+            refinement: 2.0,
+            max_passes: 6,
+        }
+    }
+}
+
+/// One pass of a convergence study
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvergenceStep {
+    /// Segment count of every wire
+    pub segments: Vec<usize>,
+    /// Feedpoint impedance in ohms
+    pub impedance: Complex<f64>,
+    /// Relative change of the impedance from the previous pass, NaN for the first
+    pub change: f64,
+    /// Smallest segment length to radius ratio of any wire
+    pub thin_wire_ratio: f64,
+}
+
+impl ConvergenceStep {
+    /// Segments are too short for their radius to trust the thin-wire kernel
+    pub fn violates_thin_wire(&self) -> bool {
+        self.thin_wire_ratio < THIN_WIRE_LIMIT
+    }
+}
+
+/// Feedpoint impedance of a model at ever finer segmentation. Printing it gives the table and a
+/// warning for every pass that broke the thin-wire limit
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvergenceStudy {
+    pub steps: Vec<ConvergenceStep>,
+    /// The last change was within the tolerance
+    pub converged: bool,
+}
+
+impl ConvergenceStudy {
+    /// Refines every wire of `geometry` until the impedance seen by `source` settles. The source
+    /// and the loads follow the part of the wire they were placed on, and segment counts keep
+    /// their parity so a center feed stays in the center
+    pub fn run(
+        solver: &ThinWireSolver,
+        frequency: f64,
+        geometry: &WireGeometry,
+        source: VoltageSource,
+        settings: &ConvergenceSettings,
+    ) -> Result<Self, MomError> {
+        geometry.validate()?;
+        geometry
+            .segment_index(source.wire, source.segment)
+            .ok_or(MomError::InvalidSource(source.wire, source.segment))?;
+        let mut steps: Vec<ConvergenceStep> = Vec::with_capacity(settings.max_passes);
+        let mut counts: Vec<usize> = geometry.wires.iter().map(|w| w.segments).collect();
+        for _ in 0..settings.max_passes {
+            let model: WireGeometry = resegment(geometry, &counts);
+            let original: usize = geometry.wires[source.wire].segments;
+            let fed: VoltageSource = VoltageSource {
+                segment: containing_segment(source.segment, original, counts[source.wire]),
+                ..source
+            };
+            let impedance: Complex<f64> = solver
+                .solve(frequency, &model, &fed.into())?
+                .input_impedance()
+                .ok_or(MomError::InvalidSource(fed.wire, fed.segment))?;
+            let change: f64 = match steps.last() {
+                // an impedance that stays exactly zero has settled, rather than dividing 0 by 0
+                Some(previous) if impedance == previous.impedance => 0.0,
+                Some(previous) => (impedance - previous.impedance).norm() / impedance.norm(),
+                None => f64::NAN,
+            };
+            steps.push(ConvergenceStep {
+                segments: counts.clone(),
+                impedance,
+                change,
+                thin_wire_ratio: model.wires.iter().map(|w| w.thin_wire_ratio()).fold(f64::INFINITY, f64::min),
+            });
+            if change <= settings.tolerance {
+                return Ok(Self { steps, converged: true });
+            }
+            counts = counts.iter().map(|&n| refine(n, settings.refinement)).collect();
+        }
+        Ok(Self {
+            steps,
+            converged: false,
+        })
+    }
+
+    /// Impedance of the finest pass, `None` when the settings allowed no pass at all
+    pub fn impedance(&self) -> Option<Complex<f64>> {
+        self.steps.last().map(|s| s.impedance)
+    }
+}
+
+impl fmt::Display for ConvergenceStudy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:>10} {:>12} {:>12} {:>10} {:>8}", "segments", "R (ohm)", "X (ohm)", "change", "dl/a")?;
+        for step in self.steps.iter() {
+            writeln!(
+                f,
+                "{:>10} {:>12.3} {:>12.3} {:>10.2e} {:>8.1}",
+                step.segments.iter().sum::<usize>(),
+                step.impedance.re,
+                step.impedance.im,
+                step.change,
+                step.thin_wire_ratio
+            )?;
+        }
+        for step in self.steps.iter().filter(|s| s.violates_thin_wire()) {
+            writeln!(
+                f,
+                "warning: with {} segments the shortest is {:.1} radii long, below the thin-wire limit of {THIN_WIRE_LIMIT}",
+                step.segments.iter().sum::<usize>(),
+                step.thin_wire_ratio
+            )?;
+        }
+        match self.converged {
+            true => write!(f, "converged"),
+            false => write!(f, "not converged"),
+        }
+    }
+}
+
+fn refine(segments: usize, factor: f64) -> usize {
+    let refined: usize = ((segments as f64 * factor).round() as usize).max(segments + 1);
+    match (refined - segments).is_multiple_of(2) {
+        true => refined,
+        false => refined + 1,
+    }
+}
+
+/// Segment of a wire split `to` ways whose center lies in `segment` of the wire split `from` ways
+fn containing_segment(segment: usize, from: usize, to: usize) -> usize {
+    (((segment as f64 + 0.5) / from as f64 * to as f64) as usize).min(to - 1)
+}
+
+/// Copy of `geometry` with new segment counts. Lumped loads move with the center of their
+/// segments, distributed loads keep covering the same length of wire
+fn resegment(geometry: &WireGeometry, counts: &[usize]) -> WireGeometry {
+    let mut model: WireGeometry = geometry.clone();
+    for (wire, &segments) in model.wires.iter_mut().zip(counts) {
+        wire.segments = segments;
+    }
+    model.loads = geometry
+        .loads
+        .iter()
+        .map(|load| {
+            let from: usize = geometry.wires[load.wire].segments;
+            let to: usize = counts[load.wire];
+            let segments = load.segments.map(|(first, last)| match load.kind.is_distributed() {
+                true => {
+                    let first: usize = (first as f64 / from as f64 * to as f64).round() as usize;
+                    let last: usize = ((last + 1) as f64 / from as f64 * to as f64).round() as usize;
+                    (first.min(to - 1), last.saturating_sub(1).max(first).min(to - 1))
+                }
+                false => (containing_segment(first, from, to), containing_segment(last, from, to)),
+            });
+            Load { segments, ..*load }
+        })
+        .collect();
+    model
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use num_complex::Complex;
+
+    use crate::{
+        consts::SPEED_OF_LIGHT,
+        mom::{
+            excitation::VoltageSource,
+            geometry::{StraightWire, WireGeometry},
+            solver::ThinWireSolver,
+            MomError,
+        },
+    };
+
+    use super::{containing_segment, refine, ConvergenceSettings, ConvergenceStudy};
+
+    fn dipole(f: f64, radius: f64) -> WireGeometry {
+        let half: f64 = SPEED_OF_LIGHT / f / 4.0;
+        StraightWire::new(Vector3::new(0.0, 0.0, -half), Vector3::new(0.0, 0.0, half), radius, 5).into()
+    }
+
+    const FEED: VoltageSource = VoltageSource {
+        wire: 0,
+        segment: 2,
+        voltage: Complex::new(1.0, 0.0),
+    };
+
+    #[test]
+    fn test_refinement_keeps_feed_centered() {
+        assert_eq!(refine(5, 2.0), 11);
+        assert_eq!(refine(1, 2.0), 3);
+        assert_eq!(refine(10, 1.5), 16);
+        assert_eq!(containing_segment(2, 5, 11), 5);
+        assert_eq!(containing_segment(0, 1, 3), 1);
+    }
+
+    #[test]
+    fn test_dipole_converges() {
+        let f: f64 = 14.2e6;
+        let settings: ConvergenceSettings = ConvergenceSettings::default();
+        let study: ConvergenceStudy =
+            ConvergenceStudy::run(&ThinWireSolver::default(), f, &dipole(f, 1e-3), FEED, &settings).unwrap();
+        println!("{study}");
+
+        assert!(study.converged);
+        assert!(study.steps.len() > 2);
+        assert!(study.steps[study.steps.len() - 1].change <= settings.tolerance);
+        assert!(study.steps.iter().all(|s| !s.violates_thin_wire()));
+        let impedance: Complex<f64> = study.impedance().unwrap();
+        assert!(impedance.re > 70.0 && impedance.re < 90.0);
+    }
+
+    #[test]
+    fn test_thick_wire_warns() {
+        // 5 cm radius, refining soon makes the segments stubbier than the thin-wire limit
+        let f: f64 = 14.2e6;
+        let settings: ConvergenceSettings = ConvergenceSettings {
+            tolerance: 1e-6,
+            max_passes: 4,
+            ..Default::default()
+        };
+        let study: ConvergenceStudy =
+            ConvergenceStudy::run(&ThinWireSolver::default(), f, &dipole(f, 0.05), FEED, &settings).unwrap();
+        let table: String = study.to_string();
+        println!("{table}");
+
+        assert!(!study.converged);
+        assert_eq!(study.steps.len(), 4);
+        assert!(!study.steps[0].violates_thin_wire());
+        assert!(study.steps[3].violates_thin_wire());
+        assert!(table.contains("warning: with 47 segments"));
+    }
+
+    #[test]
+    fn test_checks_inputs_before_solving() {
+        let f: f64 = 14.2e6;
+        let solver: ThinWireSolver = ThinWireSolver::default();
+        let settings: ConvergenceSettings = ConvergenceSettings::default();
+        let off_wire: VoltageSource = VoltageSource { segment: 5, ..FEED };
+        let result = ConvergenceStudy::run(&solver, f, &dipole(f, 1e-3), off_wire, &settings);
+        assert_eq!(result.unwrap_err(), MomError::InvalidSource(0, 5));
+        let no_segments: WireGeometry =
+            StraightWire::new(Vector3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 5.0), 1e-3, 0).into();
+        let result = ConvergenceStudy::run(&solver, f, &no_segments, FEED, &settings);
+        assert!(matches!(result, Err(MomError::InvalidGeometry(_))));
+
+        let none: ConvergenceSettings = ConvergenceSettings {
+            max_passes: 0,
+            ..Default::default()
+        };
+        let study: ConvergenceStudy = ConvergenceStudy::run(&solver, f, &dipole(f, 1e-3), FEED, &none).unwrap();
+        assert!(!study.converged);
+        assert_eq!(study.impedance(), None);
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use nalgebra::Vector3;
+use num_complex::Complex;
+
+use crate::{
+    mom::{
+        geometry::{Mesh, WireGeometry},
+        solver::MomSolution,
+    },
+    util::hz_to_angular_freq,
+};
+
+/// Current and charge at one segment of the user geometry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SegmentSample {
+    pub wire: usize,
+    pub segment: usize,
+    /// Segment center in meters
+    pub position: Vector3<f64>,
+    /// Current in amperes at the segment center, positive from the start to the end of the wire
+    pub current: Complex<f64>,
+    /// Line charge density in coulombs/meter, averaged over the segment
+    pub charge: Complex<f64>,
+}
+
+/// Current and charge along every wire of a solved structure, for plotting and debugging
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurrentDistribution {
+    /// Frequency in Hz
+    pub frequency: f64,
+    /// Every segment, wire after wire
+    pub samples: Vec<SegmentSample>,
+}
+
+impl CurrentDistribution {
+    /// Charges follow from continuity: every basis function takes charge out of its minus
+    /// segment and puts it in its plus segment at the rate of its current
+    pub fn new(geometry: &WireGeometry, solution: &MomSolution) -> Self {
+        let mesh: Mesh = Mesh::new(geometry);
+        let omega: f64 = hz_to_angular_freq(solution.frequency);
+        let mut charges: Vec<Complex<f64>> = vec![Complex::new(0.0, 0.0); mesh.segments.len()];
+        for (basis, &current) in mesh.bases.iter().zip(solution.currents.iter()) {
+            let q: Complex<f64> = current / Complex::new(0.0, omega);
+            charges[basis.minus] -= q;
+            charges[basis.plus] += q;
+        }
+
+        // wire i owns charge segments first + 0..=n: the half length end segments lie
+        // inside its end segments, the others are split evenly between two segments
+        let mut samples: Vec<SegmentSample> = Vec::with_capacity(geometry.segments());
+        let mut first: usize = 0;
+        for (w, wire) in geometry.wires.iter().enumerate() {
+            let n: usize = wire.segments;
+            for i in 0..n {
+                let before: Complex<f64> = if i == 0 { charges[first] } else { charges[first + i] / 2.0 };
+                let after: Complex<f64> = if i == n - 1 { charges[first + n] } else { charges[first + i + 1] / 2.0 };
+                samples.push(SegmentSample {
+                    wire: w,
+                    segment: i,
+                    position: wire.segment_center(i),
+                    current: solution.segment_current(w, i),
+                    charge: (before + after) / wire.segment_length(),
+                });
+            }
+            first += n + 1;
+        }
+        Self {
+            frequency: solution.frequency,
+            samples,
+        }
+    }
+
+    /// Samples along one wire, from its start to its end
+    pub fn wire(&self, wire: usize) -> impl Iterator<Item = &SegmentSample> {
+        self.samples.iter().filter(move |s| s.wire == wire)
+    }
+
+    /// Writes one line per segment with a header, phases are in degrees
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "wire,segment,x,y,z,current_re,current_im,current_magnitude,current_phase,charge_re,charge_im,charge_magnitude,charge_phase"
+        )?;
+        for s in self.samples.iter() {
+            writeln!(
+                writer,
+                "{},{},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e},{:e}",
+                s.wire,
+                s.segment,
+                s.position.x,
+                s.position.y,
+                s.position.z,
+                s.current.re,
+                s.current.im,
+                s.current.norm(),
+                s.current.arg().to_degrees(),
+                s.charge.re,
+                s.charge.im,
+                s.charge.norm(),
+                s.charge.arg().to_degrees()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes an object holding the frequency and an array of segments, phases are in degrees.
+    /// JSON has no NaN or infinity, so non-finite values are written as null
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let number = |x: f64| match x.is_finite() {
+            true => format!("{x:e}"),
+            false => "null".to_string(),
+        };
+        let complex = |z: Complex<f64>| {
+            format!(
+                "{{\"re\": {}, \"im\": {}, \"magnitude\": {}, \"phase\": {}}}",
+                number(z.re),
+                number(z.im),
+                number(z.norm()),
+                number(z.arg().to_degrees())
+            )
+        };
+        writeln!(writer, "{{")?;
+        writeln!(writer, "  \"frequency\": {},", number(self.frequency))?;
+        writeln!(writer, "  \"segments\": [")?;
+        for (i, s) in self.samples.iter().enumerate() {
+            let separator: &str = if i + 1 == self.samples.len() { "" } else { "," };
+            writeln!(
+                writer,
+                "    {{\"wire\": {}, \"segment\": {}, \"position\": [{}, {}, {}], \"current\": {}, \"charge\": {}}}{separator}",
+                s.wire,
+                s.segment,
+                number(s.position.x),
+                number(s.position.y),
+                number(s.position.z),
+                complex(s.current),
+                complex(s.charge)
+            )?;
+        }
+        writeln!(writer, "  ]")?;
+        writeln!(writer, "}}")
+    }
+
+    pub fn save_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer: io::BufWriter<fs::File> = io::BufWriter::new(fs::File::create(path)?);
+        self.write_csv(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer: io::BufWriter<fs::File> = io::BufWriter::new(fs::File::create(path)?);
+        self.write_json(&mut writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use num_complex::Complex;
+
+    use crate::{
+        consts::SPEED_OF_LIGHT,
+        mom::{
+            excitation::VoltageSource,
+            geometry::{StraightWire, WireGeometry},
+            solver::{MomSolution, ThinWireSolver},
+        },
+        util::hz_to_angular_freq,
+    };
+
+    use super::CurrentDistribution;
+
+    #[test]
+    fn test_dipole_distribution() {
+        let f: f64 = 14.2e6;
+        let half: f64 = SPEED_OF_LIGHT / f / 4.0;
+        let wire: StraightWire = StraightWire::new(Vector3::new(0.0, 0.0, -half), Vector3::new(0.0, 0.0, half), 1e-3, 21);
+        let geometry: WireGeometry = wire.into();
+        let source: VoltageSource = VoltageSource {
+            wire: 0,
+            segment: 10,
+            voltage: Complex::new(1.0, 0.0),
+        };
+        let solution: MomSolution = ThinWireSolver::default().solve(f, &geometry, &source.into()).unwrap();
+        let distribution: CurrentDistribution = CurrentDistribution::new(&geometry, &solution);
+        let s = &distribution.samples;
+        assert_eq!(s.len(), 21);
+        assert_eq!(distribution.wire(0).count(), 21);
+        assert!((s[10].position - Vector3::zeros()).norm() < 1e-12);
+        assert_eq!(s[10].current, solution.input_impedance().unwrap().inv());
+
+        // the current is even about the feed, so the charge is odd and sums to zero
+        let total: Complex<f64> = s.iter().map(|s| s.charge).sum();
+        assert!(total.norm() < 1e-9 * s[0].charge.norm());
+        assert!((s[0].charge + s[20].charge).norm() < 1e-6 * s[0].charge.norm());
+        assert!(s[0].charge.norm() > s[5].charge.norm());
+
+        // continuity along the wire, q = -dI/dl / (j omega)
+        let omega: f64 = hz_to_angular_freq(f);
+        let derivative: Complex<f64> = (s[6].current - s[4].current) / (2.0 * wire.segment_length());
+        assert!((s[5].charge + derivative / Complex::new(0.0, omega)).norm() < 1e-9 * s[5].charge.norm());
+
+        let mut csv: Vec<u8> = Vec::new();
+        distribution.write_csv(&mut csv).unwrap();
+        let csv: String = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 22);
+        assert_eq!(csv.lines().nth(1).unwrap().split(',').count(), 13);
+
+        let mut json: Vec<u8> = Vec::new();
+        distribution.write_json(&mut json).unwrap();
+        let json: String = String::from_utf8(json).unwrap();
+        assert_eq!(json.matches("\"segment\":").count(), 21);
+        assert!(json.trim_end().ends_with("]\n}"));
+
+        let mut broken: CurrentDistribution = distribution.clone();
+        broken.samples[3].current = Complex::new(f64::NAN, f64::INFINITY);
+        let mut json: Vec<u8> = Vec::new();
+        broken.write_json(&mut json).unwrap();
+        let json: String = String::from_utf8(json).unwrap();
+        assert!(json.contains("\"re\": null, \"im\": null"));
+        assert!(!json.contains("NaN") && !json.contains("inf"));
+    }
+}
@@ -0,0 +1,211 @@
+use nalgebra::{DMatrix, DVector, Vector3};
+
+use crate::{
+    consts::FREE_SPACE_PERMITTIVITY,
+    mom::{
+        geometry::WireGeometry,
+        ground::mirror,
+        solver::{psi, Kernel},
+        MomError,
+    },
+    quadrature::GaussLegendre,
+    util::LuFactorization,
+};
+
+/// Relative accuracy of the potential averaged over a segment, the self terms peak sharply
+const AVERAGE_TOLERANCE: f64 = 1e-6;
+
+/// Line charge on every segment of a structure held at fixed potentials
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChargeDistribution {
+    /// Charge density in coulombs/meter on every segment, wire after wire
+    pub densities: DVector<f64>,
+    /// Index of the first segment of every wire
+    pub offsets: Vec<usize>,
+    /// Segment length of every wire in meters
+    pub segment_lengths: Vec<f64>,
+}
+
+impl ChargeDistribution {
+    /// Charge in coulombs on `wire`
+    pub fn wire_charge(&self, wire: usize) -> f64 {
+        let end: usize = self.offsets.get(wire + 1).copied().unwrap_or(self.densities.len());
+        self.densities.rows_range(self.offsets[wire]..end).sum() * self.segment_lengths[wire]
+    }
+    /// Charge in coulombs on the whole structure
+    pub fn total_charge(&self) -> f64 {
+        (0..self.offsets.len()).map(|w| self.wire_charge(w)).sum()
+    }
+}
+
+/// Charge on `geometry` with every wire held at its entry of `potentials` in volts, relative to
+/// infinity or to the ground. Every segment carries a uniform line charge and the potential is
+/// matched on average over every segment. Any ground is a perfect conductor at DC
+pub fn charge_distribution(geometry: &WireGeometry, potentials: &[f64]) -> Result<ChargeDistribution, MomError> {
+    if potentials.len() != geometry.wires.len() {
+        return Err(MomError::InvalidGeometry(format!(
+            "{} potentials given for {} wires",
+            potentials.len(),
+            geometry.wires.len()
+        )));
+    }
+    let lu: LuFactorization<f64> = LuFactorization::new(potential_matrix(geometry)?)?;
+    let offsets: Vec<usize> = geometry.offsets();
+    let v_vector: DVector<f64> = DVector::from_fn(geometry.segments(), |i, _| {
+        potentials[offsets.iter().rposition(|&o| o <= i).unwrap_or(0)]
+    });
+    Ok(ChargeDistribution {
+        densities: lu.solve(&v_vector)?,
+        offsets,
+        segment_lengths: geometry.wires.iter().map(|w| w.segment_length()).collect(),
+    })
+}
+
+/// Self-capacitance in farads of the whole structure as one conductor
+pub fn capacitance(geometry: &WireGeometry) -> Result<f64, MomError> {
+    Ok(charge_distribution(geometry, &vec![1.0; geometry.wires.len()])?.total_charge())
+}
+
+/// Maxwell capacitance matrix in farads between conductors made of whole wires, `conductors`
+/// gives the conductor of every wire. Entry (i, j) is the charge on conductor i with conductor j
+/// at 1 V and all others at 0 V, so the off diagonal entries are negative
+pub fn capacitance_matrix(geometry: &WireGeometry, conductors: &[usize]) -> Result<DMatrix<f64>, MomError> {
+    if conductors.len() != geometry.wires.len() {
+        return Err(MomError::InvalidGeometry(format!(
+            "{} conductors given for {} wires",
+            conductors.len(),
+            geometry.wires.len()
+        )));
+    }
+    let count: usize = conductors.iter().max().map_or(0, |&c| c + 1);
+    let lu: LuFactorization<f64> = LuFactorization::new(potential_matrix(geometry)?)?;
+    let offsets: Vec<usize> = geometry.offsets();
+    let conductor_of = |segment: usize| conductors[offsets.iter().rposition(|&o| o <= segment).unwrap_or(0)];
+
+    let drives: DMatrix<f64> = DMatrix::from_fn(geometry.segments(), count, |i, j| match conductor_of(i) == j {
+        true => 1.0,
+        false => 0.0,
+    });
+    let densities: DMatrix<f64> = lu.solve_columns(&drives)?;
+    let mut matrix: DMatrix<f64> = DMatrix::zeros(count, count);
+    for (w, wire) in geometry.wires.iter().enumerate() {
+        for segment in offsets[w]..offsets[w] + wire.segments {
+            for j in 0..count {
+                matrix[(conductors[w], j)] += densities[(segment, j)] * wire.segment_length();
+            }
+        }
+    }
+    Ok(matrix)
+}
+
+/// Capacitance in farads between the two conductors of `conductors` (0 and 1), with any ground
+/// counted as a third
+pub fn mutual_capacitance(geometry: &WireGeometry, conductors: &[usize]) -> Result<f64, MomError> {
+    let matrix: DMatrix<f64> = capacitance_matrix(geometry, conductors)?;
+    if matrix.nrows() != 2 {
+        return Err(MomError::InvalidGeometry(format!("{} conductors instead of 2", matrix.nrows())));
+    }
+    Ok(-0.5 * (matrix[(0, 1)] + matrix[(1, 0)]))
+}
+
+/// Potential averaged over every segment for a unit line charge on every other one, with the
+/// negative image charges of a ground
+fn potential_matrix(geometry: &WireGeometry) -> Result<DMatrix<f64>, MomError> {
+    geometry.validate()?;
+    let segments: Vec<(Vector3<f64>, Vector3<f64>, f64)> = geometry
+        .wires
+        .iter()
+        .flat_map(|wire| {
+            let step: Vector3<f64> = (wire.end - wire.start) / wire.segments as f64;
+            (0..wire.segments).map(move |i| {
+                let start: Vector3<f64> = wire.start + step * i as f64;
+                (start, start + step, wire.radius)
+            })
+        })
+        .collect();
+    let rule: GaussLegendre = GaussLegendre::new(8);
+    let images: bool = !geometry.ground.is_free_space();
+
+    Ok(DMatrix::from_fn(segments.len(), segments.len(), |obs, src| {
+        let (start, end, radius) = segments[src];
+        let (obs_start, obs_end, _) = segments[obs];
+        let potential = |t: f64| {
+            let point: Vector3<f64> = obs_start + (obs_end - obs_start) * t;
+            let mut p: f64 = psi(start, end, radius, point, 0.0, Kernel::Reduced).re;
+            if images {
+                p -= psi(mirror(start), mirror(end), radius, point, 0.0, Kernel::Reduced).re;
+            }
+            p
+        };
+        rule.integrate_adaptive(0.0, 1.0, AVERAGE_TOLERANCE, potential) * (end - start).norm() / FREE_SPACE_PERMITTIVITY
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{LN_2, PI};
+
+    use nalgebra::{DMatrix, Vector3};
+
+    use crate::{
+        consts::FREE_SPACE_PERMITTIVITY,
+        mom::{
+            geometry::{StraightWire, WireGeometry},
+            ground::Ground,
+        },
+    };
+
+    use super::{capacitance, capacitance_matrix, charge_distribution, mutual_capacitance, ChargeDistribution};
+
+    #[test]
+    fn test_straight_wire_capacitance() {
+        // 1m long, 1mm radius
+        let wire: StraightWire = StraightWire::new(Vector3::zeros(), Vector3::new(1.0, 0.0, 0.0), 0.001, 20);
+        let geometry: WireGeometry = wire.into();
+        let charge: ChargeDistribution = charge_distribution(&geometry, &[1.0]).unwrap();
+        let c: f64 = capacitance(&geometry).unwrap();
+        dbg!(c);
+
+        // the charge crowds towards the ends
+        assert!(charge.densities[0] > 1.2 * charge.densities[10]);
+        assert!((charge.total_charge() - c).abs() < 1e-9 * c);
+        // slender body estimate 2 pi eps0 L / ln(L / a) (1 + (1 - ln 2) / ln(L / a))
+        let log: f64 = (wire.length() / wire.radius).ln();
+        let estimate: f64 = 2.0 * PI * FREE_SPACE_PERMITTIVITY * wire.length() / log * (1.0 + (1.0 - LN_2) / log);
+        assert!((c / estimate - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_two_wire_line() {
+        // 10 cm apart, 10 m long, close to an infinite two-wire line
+        let (length, spacing, radius): (f64, f64, f64) = (10.0, 0.1, 1e-3);
+        let mut geometry: WireGeometry = WireGeometry::new();
+        for y in [0.0, spacing] {
+            geometry.add_wire(StraightWire::new(Vector3::new(0.0, y, 0.0), Vector3::new(length, y, 0.0), radius, 40));
+        }
+        let matrix: DMatrix<f64> = capacitance_matrix(&geometry, &[0, 1]).unwrap();
+        dbg!(&matrix);
+        assert!(matrix[(0, 0)] > 0.0 && matrix[(0, 1)] < 0.0);
+        assert!((matrix[(0, 1)] - matrix[(1, 0)]).abs() < 1e-3 * matrix[(0, 0)]);
+
+        // driven differentially, the charge follows the two-wire line capacitance
+        let mutual: f64 = mutual_capacitance(&geometry, &[0, 1]).unwrap();
+        let differential: f64 = 0.5 * (matrix[(0, 0)] + mutual);
+        let line: f64 = PI * FREE_SPACE_PERMITTIVITY * length / (spacing / (2.0 * radius)).acosh();
+        dbg!(differential, line);
+        assert!((differential / line - 1.0).abs() < 0.05);
+        assert!(mutual_capacitance(&geometry, &[0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_ground_raises_capacitance() {
+        let mut geometry: WireGeometry =
+            StraightWire::new(Vector3::new(0.0, 0.0, 0.5), Vector3::new(2.0, 0.0, 0.5), 1e-3, 20).into();
+        let free: f64 = capacitance(&geometry).unwrap();
+        geometry.ground = Ground::Perfect;
+        let grounded: f64 = capacitance(&geometry).unwrap();
+        dbg!(free, grounded);
+        assert!(grounded > 1.05 * free);
+        assert!(charge_distribution(&geometry, &[1.0, 2.0]).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use nalgebra::{DVector, Vector3};
+use num_complex::Complex;
+
+use crate::{
+    consts::SPEED_OF_LIGHT,
+    mom::{
+        geometry::{Mesh, WireGeometry},
+        ground::mirror,
+        MomError,
+    },
+};
+
+/// Delta-gap voltage source placed across the center of a segment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoltageSource {
+    /// Index of the driven wire
+    pub wire: usize,
+    /// Index of the driven segment on `wire`
+    pub segment: usize,
+    /// Complex source voltage in volts, the phase sets the relative drive in an array
+    pub voltage: Complex<f64>,
+}
+
+/// Incident plane wave, used for receive-mode and scattering analysis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaneWave {
+    /// Direction the wave arrives from in radians, the wave travels towards the origin
+    pub theta: f64,
+    pub phi: f64,
+    /// Electric field components along the theta and phi unit vectors of the arrival direction,
+    /// in volts/meter at the origin
+    pub e_theta: Complex<f64>,
+    pub e_phi: Complex<f64>,
+}
+
+impl PlaneWave {
+    /// Linearly polarized wave of `amplitude` volts/meter, `eta` is the angle in radians between
+    /// the theta unit vector and the electric field, as on a NEC EX card
+    pub fn linear(theta: f64, phi: f64, eta: f64, amplitude: f64) -> Self {
+        Self {
+            theta,
+            phi,
+            e_theta: Complex::new(amplitude * eta.cos(), 0.0),
+            e_phi: Complex::new(amplitude * eta.sin(), 0.0),
+        }
+    }
+
+    /// Incident electric field at `point` for wavenumber `k`
+    pub fn field(&self, point: Vector3<f64>, k: f64) -> Vector3<Complex<f64>> {
+        let (sin_t, cos_t) = self.theta.sin_cos();
+        let (sin_p, cos_p) = self.phi.sin_cos();
+        let r_hat: Vector3<f64> = Vector3::new(sin_t * cos_p, sin_t * sin_p, cos_t);
+        let theta_hat: Vector3<f64> = Vector3::new(cos_t * cos_p, cos_t * sin_p, -sin_t);
+        let phi_hat: Vector3<f64> = Vector3::new(-sin_p, cos_p, 0.0);
+
+        // travelling along -r_hat, so the phase advances with r_hat . point
+        let phase: Complex<f64> = Complex::new(0.0, k * r_hat.dot(&point)).exp();
+        (theta_hat.map(|c| self.e_theta * c) + phi_hat.map(|c| self.e_phi * c)) * phase
+    }
+}
+
+/// Everything driving the structure at once, the solution is the superposition of all of it
+#[derive(Debug, Clone, Default)]
+pub struct Excitation {
+    pub sources: Vec<VoltageSource>,
+    pub plane_waves: Vec<PlaneWave>,
+}
+
+impl Excitation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add_source(&mut self, source: VoltageSource) {
+        self.sources.push(source);
+    }
+    pub fn add_plane_wave(&mut self, wave: PlaneWave) {
+        self.plane_waves.push(wave);
+    }
+}
+
+impl From<VoltageSource> for Excitation {
+    fn from(source: VoltageSource) -> Self {
+        Self {
+            sources: vec![source],
+            plane_waves: Vec::new(),
+        }
+    }
+}
+
+impl From<PlaneWave> for Excitation {
+    fn from(wave: PlaneWave) -> Self {
+        Self {
+            sources: Vec::new(),
+            plane_waves: vec![wave],
+        }
+    }
+}
+
+/// Tests the incident field with every basis function, giving the right hand side of the system.
+/// Over ground a plane wave arriving from above is joined by its reflection
+pub(crate) fn excitation_vector(
+    geometry: &WireGeometry,
+    mesh: &Mesh,
+    k: f64,
+    excitation: &Excitation,
+) -> Result<DVector<Complex<f64>>, MomError> {
+    let mut v_vector: DVector<Complex<f64>> = DVector::zeros(mesh.bases.len());
+    for source in excitation.sources.iter() {
+        let idx: usize = geometry
+            .segment_index(source.wire, source.segment)
+            .ok_or(MomError::InvalidSource(source.wire, source.segment))?;
+        v_vector[idx] += source.voltage;
+    }
+    for wave in excitation.plane_waves.iter() {
+        let (vertical, horizontal) = geometry.ground.reflection(k * SPEED_OF_LIGHT, wave.theta.cos().max(0.0));
+        let reflected: PlaneWave = PlaneWave {
+            e_theta: vertical * wave.e_theta,
+            e_phi: -horizontal * wave.e_phi,
+            ..*wave
+        };
+        for (idx, basis) in mesh.bases.iter().enumerate() {
+            for half in mesh.halves(basis) {
+                let mut field: Vector3<Complex<f64>> = wave.field(half.center(), k);
+                if !geometry.ground.is_free_space() {
+                    let image: Vector3<Complex<f64>> = reflected.field(mirror(half.center()), k);
+                    field += Vector3::new(-image.x, -image.y, image.z);
+                }
+                let dl: Vector3<f64> = half.vector();
+                v_vector[idx] += field.x * dl.x + field.y * dl.y + field.z * dl.z;
+            }
+        }
+    }
+    Ok(v_vector)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use nalgebra::Vector3;
+    use num_complex::Complex;
+
+    use crate::{
+        consts::SPEED_OF_LIGHT,
+        mom::{
+            far_field::far_field,
+            geometry::{StraightWire, WireGeometry},
+            solver::{MomSolution, ThinWireSolver},
+        },
+    };
+
+    use super::{Excitation, PlaneWave, VoltageSource};
+
+    fn vertical_dipole(f: f64, x: f64) -> StraightWire {
+        let half: f64 = SPEED_OF_LIGHT / f / 4.0;
+        StraightWire::new(Vector3::new(x, 0.0, -half), Vector3::new(x, 0.0, half), 1e-3, 21)
+    }
+
+    #[test]
+    fn test_phased_pair() {
+        let f: f64 = 14.2e6;
+        let spacing: f64 = SPEED_OF_LIGHT / f / 4.0;
+        let mut geometry: WireGeometry = WireGeometry::new();
+        geometry.add_wire(vertical_dipole(f, 0.0));
+        geometry.add_wire(vertical_dipole(f, spacing));
+
+        // quarter wave apart and in quadrature, the far element lags so the beam points along +x
+        let sources: [VoltageSource; 2] = [
+            VoltageSource {
+                wire: 0,
+                segment: 10,
+                voltage: Complex::new(1.0, 0.0),
+            },
+            VoltageSource {
+                wire: 1,
+                segment: 10,
+                voltage: Complex::new(0.0, -1.0),
+            },
+        ];
+        let solver: ThinWireSolver = ThinWireSolver::default();
+        let mut excitation: Excitation = Excitation::new();
+        sources.iter().for_each(|&s| excitation.add_source(s));
+        let both: MomSolution = solver.solve(f, &geometry, &excitation).unwrap();
+        let first: MomSolution = solver.solve(f, &geometry, &sources[0].into()).unwrap();
+        let second: MomSolution = solver.solve(f, &geometry, &sources[1].into()).unwrap();
+
+        // superposition
+        let sum = &first.currents + &second.currents;
+        assert!((&both.currents - sum).norm() < 1e-9 * both.currents.norm());
+
+        // mutual coupling makes the active impedances differ
+        let z = both.source_impedances();
+        let y = both.source_admittances();
+        dbg!(&z, first.input_impedance().unwrap());
+        assert!((z[0] - z[1]).norm() > 10.0);
+        assert!((z[0] * y[0] - 1.0).norm() < 1e-12);
+        assert!((both.input_power() - 0.5 * (y[0].re + y[1].re)).abs() < 1e-12);
+
+        // equal drive voltages give unequal currents once coupled, so the rear null fills in
+        // compared to an ideal current fed cardioid
+        let front = far_field(&geometry, &both, PI / 2.0, 0.0);
+        let back = far_field(&geometry, &both, PI / 2.0, PI);
+        let ratio: f64 = 20.0 * (front.0.norm() / back.0.norm()).log10();
+        dbg!(ratio);
+        assert!(ratio > 3.0);
+    }
+
+    #[test]
+    fn test_receiving_dipole() {
+        let f: f64 = 14.2e6;
+        let wavelength: f64 = SPEED_OF_LIGHT / f;
+        let geometry: WireGeometry = vertical_dipole(f, 0.0).into();
+        let solver: ThinWireSolver = ThinWireSolver::default();
+        let source: VoltageSource = VoltageSource {
+            wire: 0,
+            segment: 10,
+            voltage: Complex::new(1.0, 0.0),
+        };
+        let z_in: Complex<f64> = solver.solve(f, &geometry, &source.into()).unwrap().input_impedance().unwrap();
+
+        // broadside, polarized along the wire. The short circuit current times the input
+        // impedance is the open circuit voltage, close to the lambda / pi effective length
+        let broadside: PlaneWave = PlaneWave::linear(PI / 2.0, 0.0, 0.0, 1.0);
+        let received: MomSolution = solver.solve(f, &geometry, &broadside.into()).unwrap();
+        let open_circuit: f64 = (received.segment_current(0, 10) * z_in).norm();
+        dbg!(open_circuit, wavelength / PI);
+        assert!((open_circuit / (wavelength / PI) - 1.0).abs() < 0.05);
+        // nothing drives the antenna, so there is no input impedance or efficiency
+        assert_eq!(received.input_impedance(), None);
+        assert_eq!(received.efficiency(), None);
+
+        // cross polarized or end-on waves couple nothing into a straight wire
+        let crossed: PlaneWave = PlaneWave::linear(PI / 2.0, 0.0, PI / 2.0, 1.0);
+        let end_on: PlaneWave = PlaneWave::linear(0.0, 0.0, 0.0, 1.0);
+        for wave in [crossed, end_on] {
+            let solution: MomSolution = solver.solve(f, &geometry, &wave.into()).unwrap();
+            assert!(solution.currents.norm() < 1e-9 * received.currents.norm());
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use std::f64::consts::PI;
+
+use nalgebra::{DMatrix, Vector3};
+use num_complex::Complex;
+
+use crate::{
+    consts::{FREE_SPACE_IMPEDANCE, SPEED_OF_LIGHT},
+    mom::{
+        geometry::{Mesh, WireGeometry},
+        ground::{mirror, Ground},
+        solver::MomSolution,
+    },
+    pattern::RadiationPattern,
+    util::hz_to_angular_freq,
+};
+
+/// Number of theta and phi samples used to integrate the radiated power
+const POWER_THETAS: usize = 45;
+const POWER_PHIS: usize = 90;
+
+/// Straight filament carrying a uniform current
+struct Filament {
+    center: Vector3<f64>,
+    vector: Vector3<f64>,
+    current: Complex<f64>,
+}
+
+/// Filaments of the structure, and their images in a perfect ground. Bases crossing into the
+/// ground are their own image and only show up once
+fn filaments(geometry: &WireGeometry, solution: &MomSolution) -> (Vec<Filament>, Vec<Filament>) {
+    let mesh: Mesh = Mesh::new(geometry);
+    let mut direct: Vec<Filament> = Vec::with_capacity(2 * mesh.bases.len());
+    let mut images: Vec<Filament> = Vec::new();
+    for (basis, &current) in mesh.bases.iter().zip(solution.currents.iter()) {
+        for h in mesh.halves(basis) {
+            direct.push(Filament {
+                center: h.center(),
+                vector: h.vector(),
+                current,
+            });
+            if !geometry.ground.is_free_space() && !basis.grounded {
+                let v: Vector3<f64> = h.vector();
+                images.push(Filament {
+                    center: mirror(h.center()),
+                    vector: Vector3::new(-v.x, -v.y, v.z),
+                    current,
+                });
+            }
+        }
+    }
+    (direct, images)
+}
+
+/// Unit vectors r, theta and phi for a direction
+pub(crate) fn spherical_units(theta: f64, phi: f64) -> [Vector3<f64>; 3] {
+    let (sin_t, cos_t) = theta.sin_cos();
+    let (sin_p, cos_p) = phi.sin_cos();
+    [
+        Vector3::new(sin_t * cos_p, sin_t * sin_p, cos_t),
+        Vector3::new(cos_t * cos_p, cos_t * sin_p, -sin_t),
+        Vector3::new(-sin_p, cos_p, 0.0),
+    ]
+}
+
+fn radiate(filaments: &[Filament], k: f64, theta: f64, phi: f64) -> (Complex<f64>, Complex<f64>) {
+    let [r_hat, theta_hat, phi_hat] = spherical_units(theta, phi);
+    let mut radiation: Vector3<Complex<f64>> = Vector3::zeros();
+    for f in filaments {
+        // uniform current along the filament gives a sinc shaped element factor
+        let half_phase: f64 = 0.5 * k * r_hat.dot(&f.vector);
+        let element: f64 = if half_phase.abs() < 1e-9 { 1.0 } else { half_phase.sin() / half_phase };
+        let phase: Complex<f64> = Complex::new(0.0, k * r_hat.dot(&f.center)).exp();
+        radiation += f.vector.map(|c| Complex::new(c, 0.0)) * (f.current * phase * element);
+    }
+    let coefficient: Complex<f64> = Complex::new(0.0, -k * FREE_SPACE_IMPEDANCE / (4.0 * PI));
+    let n_theta: Complex<f64> = radiation.x * theta_hat.x + radiation.y * theta_hat.y + radiation.z * theta_hat.z;
+    let n_phi: Complex<f64> = radiation.x * phi_hat.x + radiation.y * phi_hat.y;
+    (coefficient * n_theta, coefficient * n_phi)
+}
+
+/// Direct field plus the ground reflection, split by polarization. Nothing radiates into the ground
+fn radiate_over(
+    (direct, images): &(Vec<Filament>, Vec<Filament>),
+    ground: Ground,
+    omega: f64,
+    theta: f64,
+    phi: f64,
+) -> (Complex<f64>, Complex<f64>) {
+    let k: f64 = omega / SPEED_OF_LIGHT;
+    if ground.is_free_space() {
+        return radiate(direct, k, theta, phi);
+    }
+    if theta > PI / 2.0 {
+        return (Complex::new(0.0, 0.0), Complex::new(0.0, 0.0));
+    }
+    let (e_theta, e_phi) = radiate(direct, k, theta, phi);
+    let (image_theta, image_phi) = radiate(images, k, theta, phi);
+    let (vertical, horizontal) = ground.reflection(omega, theta.cos());
+    (e_theta + vertical * image_theta, e_phi - horizontal * image_phi)
+}
+
+/// r * (E_theta, E_phi) in volts radiated by the solved currents towards (`theta`, `phi`)
+pub fn far_field(geometry: &WireGeometry, solution: &MomSolution, theta: f64, phi: f64) -> (Complex<f64>, Complex<f64>) {
+    let omega: f64 = hz_to_angular_freq(solution.frequency);
+    radiate_over(&filaments(geometry, solution), geometry.ground, omega, theta, phi)
+}
+
+/// Samples the far field on every combination of `thetas` and `phis` (radians). The radiated
+/// power is integrated over a separate full sphere so partial grids still give directivity, over
+/// ground only the upper hemisphere counts and power lost in the ground lowers the gain
+pub fn radiation_pattern(
+    geometry: &WireGeometry,
+    solution: &MomSolution,
+    thetas: &[f64],
+    phis: &[f64],
+) -> RadiationPattern {
+    let omega: f64 = hz_to_angular_freq(solution.frequency);
+    let filaments: (Vec<Filament>, Vec<Filament>) = filaments(geometry, solution);
+
+    let mut e_theta: DMatrix<Complex<f64>> = DMatrix::zeros(thetas.len(), phis.len());
+    let mut e_phi: DMatrix<Complex<f64>> = DMatrix::zeros(thetas.len(), phis.len());
+    for (i, &theta) in thetas.iter().enumerate() {
+        for (j, &phi) in phis.iter().enumerate() {
+            (e_theta[(i, j)], e_phi[(i, j)]) = radiate_over(&filaments, geometry.ground, omega, theta, phi);
+        }
+    }
+
+    // midpoint rule over the sphere, or the half of it above ground
+    let theta_span: f64 = if geometry.ground.is_free_space() { PI } else { PI / 2.0 };
+    let d_theta: f64 = theta_span / POWER_THETAS as f64;
+    let d_phi: f64 = 2.0 * PI / POWER_PHIS as f64;
+    let mut radiated_power: f64 = 0.0;
+    for i in 0..POWER_THETAS {
+        let theta: f64 = (i as f64 + 0.5) * d_theta;
+        for j in 0..POWER_PHIS {
+            let (e_t, e_p) = radiate_over(&filaments, geometry.ground, omega, theta, (j as f64 + 0.5) * d_phi);
+            radiated_power += (e_t.norm_sqr() + e_p.norm_sqr()) * theta.sin();
+        }
+    }
+    radiated_power *= d_theta * d_phi / (2.0 * FREE_SPACE_IMPEDANCE);
+
+    RadiationPattern {
+        frequency: solution.frequency,
+        thetas: thetas.to_vec(),
+        phis: phis.to_vec(),
+        e_theta,
+        e_phi,
+        radiated_power,
+        input_power: solution.input_power(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use nalgebra::Vector3;
+    use num_complex::Complex;
+
+    use crate::{
+        consts::SPEED_OF_LIGHT,
+        mom::{
+            excitation::VoltageSource,
+            geometry::{StraightWire, WireGeometry},
+            solver::ThinWireSolver,
+        },
+        pattern::{angles, RadiationPattern},
+    };
+
+    use super::radiation_pattern;
+
+    #[test]
+    fn test_half_wave_dipole_pattern() {
+        let f: f64 = 14.2e6;
+        let half: f64 = SPEED_OF_LIGHT / f / 4.0;
+        let geometry: WireGeometry =
+            StraightWire::new(Vector3::new(0.0, 0.0, -half), Vector3::new(0.0, 0.0, half), 1e-3, 31).into();
+        let source: VoltageSource = VoltageSource {
+            wire: 0,
+            segment: 15,
+            voltage: Complex::new(1.0, 0.0),
+        };
+        let solution = ThinWireSolver::default().solve(f, &geometry, &source.into()).unwrap();
+        let step: f64 = PI / 90.0;
+        let pattern: RadiationPattern =
+            radiation_pattern(&geometry, &solution, &angles(0.0, step, 91), &angles(0.0, step, 180));
+        let summary = pattern.summary().unwrap();
+        dbg!(summary, pattern.efficiency());
+
+        // lossless, so all input power is radiated
+        assert!((pattern.efficiency() - 1.0).abs() < 0.02);
+        assert!((summary.max_gain - 2.15).abs() < 0.1);
+        assert!((summary.max_theta - PI / 2.0).abs() < 1e-9);
+        assert!(summary.front_to_back.abs() < 0.01);
+        assert!((summary.elevation_beamwidth.unwrap().to_degrees() - 78.0).abs() < 2.0);
+        // the plane normal to the wire is omnidirectional
+        assert!(summary.azimuth_beamwidth.is_none());
+        // nulls off the wire ends
+        assert!(pattern.gain_dbi(0, 0) < -30.0);
+    }
+
+    #[test]
+    fn test_two_element_yagi_front_to_back() {
+        let f: f64 = 14.2e6;
+        let wavelength: f64 = SPEED_OF_LIGHT / f;
+        let driven: f64 = 0.47 * wavelength / 2.0;
+        let reflector: f64 = 0.50 * wavelength / 2.0;
+        let spacing: f64 = 0.15 * wavelength;
+
+        let mut geometry: WireGeometry = WireGeometry::new();
+        let feed: usize = geometry.add_wire(StraightWire::new(
+            Vector3::new(0.0, -driven, 0.0),
+            Vector3::new(0.0, driven, 0.0),
+            5e-3,
+            21,
+        ));
+        geometry.add_wire(StraightWire::new(
+            Vector3::new(-spacing, -reflector, 0.0),
+            Vector3::new(-spacing, reflector, 0.0),
+            5e-3,
+            21,
+        ));
+        let source: VoltageSource = VoltageSource {
+            wire: feed,
+            segment: 10,
+            voltage: Complex::new(1.0, 0.0),
+        };
+        let solution = ThinWireSolver::default().solve(f, &geometry, &source.into()).unwrap();
+        let step: f64 = PI / 36.0;
+        let pattern: RadiationPattern =
+            radiation_pattern(&geometry, &solution, &[PI / 2.0], &angles(0.0, step, 72));
+        let summary = pattern.summary().unwrap();
+        dbg!(summary);
+
+        // beam points along +x, away from the reflector
+        assert!(summary.max_phi.abs() < 1e-9);
+        assert!(summary.front_to_back > 6.0);
+        assert!(summary.max_gain > 5.0 && summary.max_gain < 8.0);
+    }
+}
@@ -0,0 +1,411 @@
+use nalgebra::Vector3;
+
+use crate::mom::{
+    ground::{mirror, Ground},
+    load::Load,
+    MomError,
+};
+
+/// Endpoints closer than this fraction of the shorter segment length are joined
+const JUNCTION_TOLERANCE: f64 = 1e-4;
+
+/// A straight, perfectly conducting wire split into equal length segments
+#[derive(Debug, Clone, Copy)]
+pub struct StraightWire {
+    /// First endpoint in meters
+    pub start: Vector3<f64>,
+    /// Second endpoint in meters
+    pub end: Vector3<f64>,
+    /// Wire radius in meters
+    pub radius: f64,
+    /// Number of segments the wire is split into
+    pub segments: usize,
+}
+
+impl StraightWire {
+    pub fn new(start: Vector3<f64>, end: Vector3<f64>, radius: f64, segments: usize) -> Self {
+        Self {
+            start,
+            end,
+            radius,
+            segments,
+        }
+    }
+    /// Length of the wire in meters
+    pub fn length(&self) -> f64 {
+        (self.end - self.start).norm()
+    }
+    /// Length of a single segment in meters
+    pub fn segment_length(&self) -> f64 {
+        self.length() / self.segments as f64
+    }
+    /// Segment length over radius, see `convergence::THIN_WIRE_LIMIT`
+    pub fn thin_wire_ratio(&self) -> f64 {
+        self.segment_length() / self.radius
+    }
+    /// Unit vector pointing from `start` to `end`
+    pub fn direction(&self) -> Vector3<f64> {
+        (self.end - self.start).normalize()
+    }
+    /// Center of segment `idx`, counted from `start`
+    pub fn segment_center(&self, idx: usize) -> Vector3<f64> {
+        self.start + (self.end - self.start) * ((idx as f64 + 0.5) / self.segments as f64)
+    }
+    pub fn endpoint(&self, end: WireEnd) -> Vector3<f64> {
+        match end {
+            WireEnd::Start => self.start,
+            WireEnd::End => self.end,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireEnd {
+    Start,
+    End,
+}
+
+/// A point where two or more wires share an endpoint
+#[derive(Debug, Clone)]
+pub struct Junction {
+    pub position: Vector3<f64>,
+    /// Index of every wire meeting here together with the end that touches the junction
+    pub ends: Vec<(usize, WireEnd)>,
+}
+
+/// Collection of straight wires, wires that share an endpoint are electrically connected
+#[derive(Debug, Clone, Default)]
+pub struct WireGeometry {
+    pub wires: Vec<StraightWire>,
+    /// Loads placed on the wires
+    pub loads: Vec<Load>,
+    /// Ground below z = 0
+    pub ground: Ground,
+}
+
+impl WireGeometry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a wire and returns its index
+    pub fn add_wire(&mut self, wire: StraightWire) -> usize {
+        self.wires.push(wire);
+        self.wires.len() - 1
+    }
+    pub fn add_load(&mut self, load: Load) {
+        self.loads.push(load);
+    }
+    /// Total number of segments over all wires
+    pub fn segments(&self) -> usize {
+        self.wires.iter().map(|w| w.segments).sum()
+    }
+    /// Index of the first unknown belonging to every wire
+    pub fn offsets(&self) -> Vec<usize> {
+        self.wires
+            .iter()
+            .scan(0, |acc, w| {
+                let offset: usize = *acc;
+                *acc += w.segments;
+                Some(offset)
+            })
+            .collect()
+    }
+    /// Index of the unknown holding the current at the center of `segment` on `wire`
+    pub fn segment_index(&self, wire: usize, segment: usize) -> Option<usize> {
+        let w: &StraightWire = self.wires.get(wire)?;
+        (segment < w.segments).then(|| self.offsets()[wire] + segment)
+    }
+    /// Number of unknowns, one per segment, one extra per wire beyond the first at every junction
+    /// and one per point where the structure touches the ground
+    pub fn unknowns(&self) -> usize {
+        self.segments()
+            + self.junctions().iter().map(|j| j.ends.len() - 1).sum::<usize>()
+            + self.ground_contacts().len()
+    }
+
+    pub fn validate(&self) -> Result<(), MomError> {
+        if self.wires.is_empty() {
+            return Err(MomError::InvalidGeometry("geometry has no wires".to_string()));
+        }
+        for (i, wire) in self.wires.iter().enumerate() {
+            if wire.segments == 0 {
+                return Err(MomError::InvalidGeometry(format!("wire {i} has no segments")));
+            }
+            // NaN fails every comparison, so finiteness is checked first
+            if !wire.length().is_finite() || wire.length() <= 0.0 {
+                return Err(MomError::InvalidGeometry(format!("wire {i} has no finite, positive length")));
+            }
+            if !wire.radius.is_finite() || wire.radius <= 0.0 {
+                return Err(MomError::InvalidGeometry(format!("wire {i} has no finite, positive radius")));
+            }
+            let tolerance: f64 = JUNCTION_TOLERANCE * wire.segment_length();
+            if !self.ground.is_free_space() && wire.start.z.min(wire.end.z) < -tolerance {
+                return Err(MomError::InvalidGeometry(format!("wire {i} runs below the ground")));
+            }
+        }
+        for (i, load) in self.loads.iter().enumerate() {
+            let valid: bool = self.wires.get(load.wire).is_some_and(|w| {
+                load.segments.is_none_or(|(first, last)| first <= last && last < w.segments)
+            });
+            if !valid {
+                return Err(MomError::InvalidLoad(i));
+            }
+        }
+        Ok(())
+    }
+
+    /// Groups wire endpoints that coincide. Free wire ends are not reported
+    pub fn junctions(&self) -> Vec<Junction> {
+        let mut junctions: Vec<Junction> = Vec::new();
+        let mut tolerances: Vec<f64> = Vec::new();
+        for (i, wire) in self.wires.iter().enumerate() {
+            let tolerance: f64 = JUNCTION_TOLERANCE * wire.segment_length();
+            for end in [WireEnd::Start, WireEnd::End] {
+                let position: Vector3<f64> = wire.endpoint(end);
+                let existing = junctions
+                    .iter_mut()
+                    .zip(tolerances.iter_mut())
+                    .find(|(j, t)| (j.position - position).norm() <= t.min(tolerance));
+                match existing {
+                    Some((junction, t)) => {
+                        junction.ends.push((i, end));
+                        *t = t.min(tolerance);
+                    }
+                    None => {
+                        junctions.push(Junction {
+                            position,
+                            ends: vec![(i, end)],
+                        });
+                        tolerances.push(tolerance);
+                    }
+                }
+            }
+        }
+        junctions.retain(|j| j.ends.len() > 1);
+        junctions
+    }
+
+    /// One wire end for every point where the structure touches the ground, wires joined to it at
+    /// a junction share its connection. Empty in free space
+    pub fn ground_contacts(&self) -> Vec<(usize, WireEnd)> {
+        let mut contacts: Vec<(usize, WireEnd)> = Vec::new();
+        if self.ground.is_free_space() {
+            return contacts;
+        }
+        for (i, wire) in self.wires.iter().enumerate() {
+            let tolerance: f64 = JUNCTION_TOLERANCE * wire.segment_length();
+            for end in [WireEnd::Start, WireEnd::End] {
+                let position: Vector3<f64> = wire.endpoint(end);
+                let known: bool = contacts
+                    .iter()
+                    .any(|&(w, e)| (self.wires[w].endpoint(e) - position).norm() <= tolerance);
+                if position.z.abs() <= tolerance && !known {
+                    contacts.push((i, end));
+                }
+            }
+        }
+        contacts
+    }
+}
+
+impl From<StraightWire> for WireGeometry {
+    fn from(wire: StraightWire) -> Self {
+        Self {
+            wires: vec![wire],
+            loads: Vec::new(),
+            ground: Ground::FreeSpace,
+        }
+    }
+}
+
+/// Straight piece of wire carrying a uniform line charge in the MoM expansion
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ChargeSegment {
+    pub start: Vector3<f64>,
+    pub end: Vector3<f64>,
+    pub radius: f64,
+    /// Index of the wire the segment lies on
+    pub wire: usize,
+    /// Place among the equal, full length segments of the wire, `None` for the half length
+    /// segments at the wire ends and for images
+    pub position: Option<usize>,
+}
+
+impl ChargeSegment {
+    pub fn midpoint(&self) -> Vector3<f64> {
+        (self.start + self.end) / 2.0
+    }
+}
+
+/// Current basis function, the current flows from the middle of the `minus` charge segment,
+/// through `node`, to the middle of the `plus` charge segment
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Basis {
+    pub node: Vector3<f64>,
+    pub minus: usize,
+    pub plus: usize,
+    /// The basis crosses the ground plane into its own image, so it gets no separate image
+    pub grounded: bool,
+}
+
+/// Half of a basis function, a straight filament carrying the basis current
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HalfSegment {
+    pub start: Vector3<f64>,
+    pub end: Vector3<f64>,
+    pub radius: f64,
+}
+
+impl HalfSegment {
+    pub fn vector(&self) -> Vector3<f64> {
+        self.end - self.start
+    }
+    pub fn center(&self) -> Vector3<f64> {
+        (self.start + self.end) / 2.0
+    }
+}
+
+/// Discretized wire structure consumed by the matrix fill.
+///
+/// Current samples sit at the segment centers of the user geometry, so a wire with `n` segments
+/// gets `n` unknowns. The charge segments run between neighbouring samples, with a half length
+/// segment at each end of a wire. At a free end the current goes to zero, at a junction one extra
+/// basis function per additional wire carries current from a reference wire into the others, which
+/// keeps the sum of currents into the junction at zero. Where a wire touches the ground a basis
+/// runs from the image of its end charge segment into the wire, letting current into the ground.
+#[derive(Debug, Clone)]
+pub(crate) struct Mesh {
+    pub segments: Vec<ChargeSegment>,
+    pub bases: Vec<Basis>,
+    pub ground: Ground,
+}
+
+impl Mesh {
+    pub fn new(geometry: &WireGeometry) -> Self {
+        let mut segments: Vec<ChargeSegment> = Vec::new();
+        let mut bases: Vec<Basis> = Vec::with_capacity(geometry.unknowns());
+        // charge segment touching the start and end of every wire
+        let mut end_segments: Vec<(usize, usize)> = Vec::with_capacity(geometry.wires.len());
+
+        for (idx, wire) in geometry.wires.iter().enumerate() {
+            let first: usize = segments.len();
+            let mut points: Vec<Vector3<f64>> = Vec::with_capacity(wire.segments + 2);
+            points.push(wire.start);
+            points.extend((0..wire.segments).map(|i| wire.segment_center(i)));
+            points.push(wire.end);
+
+            segments.extend(points.windows(2).enumerate().map(|(i, p)| ChargeSegment {
+                start: p[0],
+                end: p[1],
+                radius: wire.radius,
+                wire: idx,
+                position: (i > 0 && i < wire.segments).then_some(i),
+            }));
+            bases.extend((0..wire.segments).map(|i| Basis {
+                node: points[i + 1],
+                minus: first + i,
+                plus: first + i + 1,
+                grounded: false,
+            }));
+            end_segments.push((first, first + wire.segments));
+        }
+
+        let end_segment = |(wire, end): (usize, WireEnd)| match end {
+            WireEnd::Start => end_segments[wire].0,
+            WireEnd::End => end_segments[wire].1,
+        };
+        for junction in geometry.junctions() {
+            let reference: usize = end_segment(junction.ends[0]);
+            bases.extend(junction.ends[1..].iter().map(|&end| Basis {
+                node: junction.position,
+                minus: reference,
+                plus: end_segment(end),
+                grounded: false,
+            }));
+        }
+        for contact in geometry.ground_contacts() {
+            let real: ChargeSegment = segments[end_segment(contact)];
+            segments.push(ChargeSegment {
+                start: mirror(real.start),
+                end: mirror(real.end),
+                position: None,
+                ..real
+            });
+            let node: Vector3<f64> = geometry.wires[contact.0].endpoint(contact.1);
+            bases.push(Basis {
+                node: Vector3::new(node.x, node.y, 0.0),
+                minus: segments.len() - 1,
+                plus: end_segment(contact),
+                grounded: true,
+            });
+        }
+
+        Self {
+            segments,
+            bases,
+            ground: geometry.ground,
+        }
+    }
+
+    /// The two filaments making up a basis function, oriented along the current flow
+    pub fn halves(&self, basis: &Basis) -> [HalfSegment; 2] {
+        let minus: &ChargeSegment = &self.segments[basis.minus];
+        let plus: &ChargeSegment = &self.segments[basis.plus];
+        [
+            HalfSegment {
+                start: minus.midpoint(),
+                end: basis.node,
+                radius: minus.radius,
+            },
+            HalfSegment {
+                start: basis.node,
+                end: plus.midpoint(),
+                radius: plus.radius,
+            },
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use crate::mom::MomError;
+
+    use super::{StraightWire, WireEnd, WireGeometry};
+
+    #[test]
+    fn test_junction_detection() {
+        // three radials meeting at the origin plus an unconnected wire
+        let origin: Vector3<f64> = Vector3::zeros();
+        let mut geometry: WireGeometry = WireGeometry::new();
+        geometry.add_wire(StraightWire::new(origin, Vector3::new(1.0, 0.0, 0.0), 1e-3, 5));
+        geometry.add_wire(StraightWire::new(Vector3::new(0.0, 2.0, 0.0), origin, 1e-3, 4));
+        geometry.add_wire(StraightWire::new(origin, Vector3::new(0.0, 0.0, 3.0), 1e-3, 3));
+        geometry.add_wire(StraightWire::new(Vector3::new(5.0, 0.0, 0.0), Vector3::new(6.0, 0.0, 0.0), 1e-3, 2));
+
+        let junctions = geometry.junctions();
+        assert_eq!(junctions.len(), 1);
+        assert_eq!(junctions[0].ends, vec![(0, WireEnd::Start), (1, WireEnd::End), (2, WireEnd::Start)]);
+        assert_eq!(geometry.segments(), 14);
+        assert_eq!(geometry.unknowns(), 16);
+        assert_eq!(geometry.segment_index(2, 1), Some(10));
+        assert_eq!(geometry.segment_index(2, 3), None);
+    }
+
+    #[test]
+    fn test_validate_rejects_degenerate_wires() {
+        let wire: StraightWire = StraightWire::new(Vector3::zeros(), Vector3::zeros(), 1e-3, 5);
+        assert!(WireGeometry::from(wire).validate().is_err());
+        assert!(WireGeometry::new().validate().is_err());
+
+        let end: Vector3<f64> = Vector3::new(0.0, 0.0, 1.0);
+        let unknown: StraightWire = StraightWire::new(Vector3::new(f64::NAN, 0.0, 0.0), end, 1e-3, 5);
+        assert!(WireGeometry::from(unknown).validate().is_err());
+        for radius in [f64::NAN, f64::INFINITY, 0.0] {
+            let wire: StraightWire = StraightWire::new(Vector3::zeros(), end, radius, 5);
+            assert!(matches!(WireGeometry::from(wire).validate(), Err(MomError::InvalidGeometry(_))));
+        }
+        assert!(WireGeometry::from(StraightWire::new(Vector3::zeros(), end, 1e-3, 5)).validate().is_ok());
+    }
+}
@@ -0,0 +1,284 @@
+use std::f64::consts::PI;
+
+use nalgebra::Vector3;
+use num_complex::Complex;
+
+use crate::{consts::FREE_SPACE_PERMITTIVITY, util::erfcx};
+
+/// Ground beneath the structure, filling the half space below z = 0
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Ground {
+    #[default]
+    FreeSpace,
+    /// Perfectly conducting ground, solved exactly with images
+    Perfect,
+    /// Lossy ground in the reflection coefficient approximation: every image is weighted with
+    /// the Fresnel coefficients of the ray running from it to the observer. Relative permittivity
+    /// and conductivity in siemens/meter
+    Finite { permittivity: f64, conductivity: f64 },
+    /// Lossy ground with Norton's surface wave added to the vertically polarized image, a cheap
+    /// stand-in for the Sommerfeld integrals that matters for antennas within a small fraction of
+    /// a wavelength of the ground
+    SommerfeldNorton { permittivity: f64, conductivity: f64 },
+}
+
+impl Ground {
+    /// Typical pastoral land, relative permittivity 13 and 5 mS/m
+    pub const AVERAGE: Ground = Ground::Finite {
+        permittivity: 13.0,
+        conductivity: 0.005,
+    };
+
+    pub fn is_free_space(&self) -> bool {
+        *self == Ground::FreeSpace
+    }
+
+    /// Complex relative permittivity e_r - j sigma / (omega e_0) of a lossy ground
+    pub fn complex_permittivity(&self, omega: f64) -> Option<Complex<f64>> {
+        match *self {
+            Ground::Finite { permittivity, conductivity }
+            | Ground::SommerfeldNorton { permittivity, conductivity } => Some(Complex::new(
+                permittivity,
+                -conductivity / (omega * FREE_SPACE_PERMITTIVITY),
+            )),
+            Ground::FreeSpace | Ground::Perfect => None,
+        }
+    }
+
+    /// Fresnel coefficients (vertical, horizontal) of a plane wave meeting the ground at a grazing
+    /// angle with sine `sin_psi`, relative to the image of a perfect ground: a perfect ground gives
+    /// (1, -1) and free space (0, 0)
+    pub fn reflection(&self, omega: f64, sin_psi: f64) -> (Complex<f64>, Complex<f64>) {
+        match (*self, self.complex_permittivity(omega)) {
+            (Ground::FreeSpace, _) => (Complex::new(0.0, 0.0), Complex::new(0.0, 0.0)),
+            (_, None) => (Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0)),
+            (_, Some(eps)) => {
+                let root: Complex<f64> = (eps - (1.0 - sin_psi * sin_psi)).sqrt();
+                (
+                    (eps * sin_psi - root) / (eps * sin_psi + root),
+                    (sin_psi - root) / (sin_psi + root),
+                )
+            }
+        }
+    }
+
+    /// Coefficients (vertical, horizontal) weighting the image at `image` as seen from `obs`. The
+    /// Sommerfeld-Norton ground adds the surface wave to the vertical coefficient
+    pub(crate) fn image_coefficients(
+        &self,
+        omega: f64,
+        k: f64,
+        obs: Vector3<f64>,
+        image: Vector3<f64>,
+    ) -> (Complex<f64>, Complex<f64>) {
+        let distance: f64 = (obs - image).norm();
+        let sin_psi: f64 = if distance > 0.0 { ((obs.z - image.z) / distance).max(0.0) } else { 1.0 };
+        let (vertical, horizontal) = self.reflection(omega, sin_psi);
+        match (*self, self.complex_permittivity(omega)) {
+            (Ground::SommerfeldNorton { .. }, Some(eps)) => {
+                let attenuation: Complex<f64> = norton_attenuation(eps, vertical, k * distance, sin_psi);
+                (vertical + (1.0 - vertical) * attenuation, horizontal)
+            }
+            _ => (vertical, horizontal),
+        }
+    }
+}
+
+/// Reflection of `point` in the ground plane
+pub(crate) fn mirror(point: Vector3<f64>) -> Vector3<f64> {
+    Vector3::new(point.x, point.y, -point.z)
+}
+
+/// Norton's ground wave attenuation function F(w) = 1 - j sqrt(pi w) e^(-w) erfc(j sqrt(w)) for
+/// the numerical distance w of an image path `kr` radians long
+fn norton_attenuation(eps: Complex<f64>, vertical: Complex<f64>, kr: f64, sin_psi: f64) -> Complex<f64> {
+    let u2: Complex<f64> = 1.0 / eps;
+    let cos2: f64 = 1.0 - sin_psi * sin_psi;
+    let w: Complex<f64> =
+        Complex::new(0.0, -2.0 * kr) * u2 * (1.0 - u2 * cos2) / ((1.0 - vertical) * (1.0 - vertical));
+    if !w.is_finite() {
+        return Complex::new(0.0, 0.0);
+    }
+    let root: Complex<f64> = w.sqrt();
+    let j: Complex<f64> = Complex::new(0.0, 1.0);
+    1.0 - j * PI.sqrt() * root * erfcx(j * root)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use nalgebra::Vector3;
+    use num_complex::Complex;
+
+    use crate::{
+        consts::SPEED_OF_LIGHT,
+        mom::{
+            excitation::{Excitation, PlaneWave, VoltageSource},
+            far_field::radiation_pattern,
+            geometry::{StraightWire, WireGeometry},
+            solver::{MomSolution, ThinWireSolver},
+        },
+        pattern::{angles, RadiationPattern},
+    };
+
+    use super::{mirror, norton_attenuation, Ground};
+
+    const F: f64 = 14.2e6;
+
+    fn source(wire: usize, segment: usize, voltage: f64) -> VoltageSource {
+        VoltageSource {
+            wire,
+            segment,
+            voltage: Complex::new(voltage, 0.0),
+        }
+    }
+
+    /// Half wave dipole centered at `center` along `direction`
+    fn dipole(center: Vector3<f64>, direction: Vector3<f64>) -> StraightWire {
+        let half: Vector3<f64> = direction * SPEED_OF_LIGHT / F / 4.0;
+        StraightWire::new(center - half, center + half, 1e-3, 21)
+    }
+
+    fn solve(geometry: &WireGeometry, excitation: &Excitation) -> MomSolution {
+        ThinWireSolver::default().solve(F, geometry, excitation).unwrap()
+    }
+
+    #[test]
+    fn test_reflection_limits() {
+        let omega: f64 = 2.0 * PI * 7.1e6;
+        let sea: Ground = Ground::Finite {
+            permittivity: 80.0,
+            conductivity: 5.0,
+        };
+        // sea water is nearly a perfect conductor at HF except at grazing angles
+        let (vertical, horizontal) = sea.reflection(omega, 1.0);
+        assert!((vertical - 1.0).norm() < 0.05 && (horizontal + 1.0).norm() < 0.05);
+
+        // both polarizations flip at grazing incidence
+        let (vertical, horizontal) = Ground::AVERAGE.reflection(omega, 0.0);
+        assert!((vertical + 1.0).norm() < 1e-12 && (horizontal + 1.0).norm() < 1e-12);
+
+        // vertical polarization dips towards the pseudo-Brewster angle
+        let steep: f64 = Ground::AVERAGE.reflection(omega, 1.0).0.norm();
+        let brewster: f64 = Ground::AVERAGE.reflection(omega, 0.2).0.norm();
+        assert!(brewster < 0.5 * steep);
+    }
+
+    #[test]
+    fn test_surface_wave_decays() {
+        let omega: f64 = 2.0 * PI * 1.8e6;
+        let eps: Complex<f64> = Ground::AVERAGE.complex_permittivity(omega).unwrap();
+        let vertical: Complex<f64> = Ground::AVERAGE.reflection(omega, 0.0).0;
+
+        let near: Complex<f64> = norton_attenuation(eps, vertical, 0.01, 0.0);
+        let mid: Complex<f64> = norton_attenuation(eps, vertical, 100.0, 0.0);
+        let far: Complex<f64> = norton_attenuation(eps, vertical, 1e4, 0.0);
+        dbg!(near, mid, far);
+        assert!((near - 1.0).norm() < 0.1);
+        assert!(mid.norm() < near.norm() && far.norm() < mid.norm());
+        assert!(far.norm() < 0.05);
+    }
+
+    #[test]
+    fn test_perfect_ground_matches_image_pair() {
+        let height: f64 = 0.3 * SPEED_OF_LIGHT / F;
+        let center: Vector3<f64> = Vector3::new(0.0, 0.0, height);
+        // a horizontal image carries the opposite current, a vertical one the same
+        for (direction, sign) in [(Vector3::x(), -1.0), (Vector3::z(), 1.0)] {
+            let mut grounded: WireGeometry = dipole(center, direction).into();
+            grounded.ground = Ground::Perfect;
+            let over_ground: Complex<f64> = solve(&grounded, &source(0, 10, 1.0).into()).input_impedance().unwrap();
+
+            let mut pair: WireGeometry = WireGeometry::new();
+            pair.add_wire(dipole(center, direction));
+            let image: StraightWire = dipole(mirror(center), direction);
+            pair.add_wire(image);
+            let mut excitation: Excitation = Excitation::new();
+            excitation.add_source(source(0, 10, 1.0));
+            excitation.add_source(source(1, 10, sign));
+            let free_space: Complex<f64> = solve(&pair, &excitation).input_impedance().unwrap();
+            dbg!(over_ground, free_space);
+
+            assert!((over_ground - free_space).norm() < 1e-6 * free_space.norm());
+        }
+    }
+
+    #[test]
+    fn test_monopole_on_perfect_ground() {
+        let quarter: f64 = SPEED_OF_LIGHT / F / 4.0;
+        let mut monopole: WireGeometry =
+            StraightWire::new(Vector3::zeros(), Vector3::new(0.0, 0.0, quarter), 1e-3, 20).into();
+        monopole.ground = Ground::Perfect;
+        assert_eq!(monopole.unknowns(), 21);
+        let solution: MomSolution = solve(&monopole, &source(0, 0, 1.0).into());
+
+        let free: WireGeometry = dipole(Vector3::zeros(), Vector3::z()).into();
+        let reference: Complex<f64> = solve(&free, &source(0, 10, 1.0).into()).input_impedance().unwrap();
+        dbg!(solution.input_impedance().unwrap(), reference / 2.0);
+        assert!((solution.input_impedance().unwrap().re / (reference.re / 2.0) - 1.0).abs() < 0.1);
+
+        // all power goes into the upper half space, doubling the dipole's directivity
+        let step: f64 = PI / 90.0;
+        let pattern: RadiationPattern =
+            radiation_pattern(&monopole, &solution, &angles(0.0, step, 91), &angles(0.0, step, 4));
+        let summary = pattern.summary().unwrap();
+        dbg!(summary, pattern.efficiency());
+        assert!((pattern.efficiency() - 1.0).abs() < 0.03);
+        assert!((summary.max_gain - 5.16).abs() < 0.2);
+        assert!((summary.max_theta - PI / 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_real_ground_absorbs_power() {
+        let center: Vector3<f64> = Vector3::new(0.0, 0.0, 0.5 * SPEED_OF_LIGHT / F);
+        let mut geometry: WireGeometry = dipole(center, Vector3::x()).into();
+        let step: f64 = PI / 90.0;
+        let mut gains: Vec<f64> = Vec::new();
+        for ground in [
+            Ground::Perfect,
+            Ground::AVERAGE,
+            Ground::SommerfeldNorton {
+                permittivity: 13.0,
+                conductivity: 0.005,
+            },
+        ] {
+            geometry.ground = ground;
+            let solution: MomSolution = solve(&geometry, &source(0, 10, 1.0).into());
+            let pattern: RadiationPattern =
+                radiation_pattern(&geometry, &solution, &angles(0.0, step, 46), &[0.0, PI / 2.0]);
+            let summary = pattern.summary().unwrap();
+            dbg!(ground, solution.input_impedance().unwrap(), summary.max_gain, pattern.efficiency());
+            gains.push(summary.max_gain);
+            if ground == Ground::Perfect {
+                assert!((pattern.efficiency() - 1.0).abs() < 0.03);
+            } else {
+                assert!(pattern.efficiency() < 0.95);
+            }
+        }
+        // a half wave up, the lobe is straight overhead and grows about 5 to 6 dB over a
+        // perfect ground
+        assert!(gains[0] > 7.0 && gains[0] < 9.5);
+        assert!(gains[1] < gains[0]);
+        // the surface wave hardly matters this high up
+        assert!((gains[1] - gains[2]).abs() < 0.2);
+    }
+
+    #[test]
+    fn test_reflection_doubles_received_field() {
+        // at a quarter wave the reflection off a perfect ground adds in phase overhead, the
+        // coupling to the image only slightly reshapes the current
+        let center: Vector3<f64> = Vector3::new(0.0, 0.0, SPEED_OF_LIGHT / F / 4.0);
+        let mut geometry: WireGeometry = dipole(center, Vector3::x()).into();
+        let overhead: PlaneWave = PlaneWave::linear(0.0, 0.0, 0.0, 1.0);
+        let mut open_circuit: Vec<f64> = Vec::new();
+        for ground in [Ground::FreeSpace, Ground::Perfect] {
+            geometry.ground = ground;
+            let z_in: Complex<f64> = solve(&geometry, &source(0, 10, 1.0).into()).input_impedance().unwrap();
+            let received: MomSolution = solve(&geometry, &overhead.into());
+            open_circuit.push((received.segment_current(0, 10) * z_in).norm());
+        }
+        dbg!(&open_circuit);
+        assert!((open_circuit[1] / open_circuit[0] - 2.0).abs() < 0.05);
+    }
+}
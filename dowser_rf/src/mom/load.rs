@@ -0,0 +1,280 @@
+use nalgebra::DVector;
+use num_complex::Complex;
+
+use crate::{
+    consts::FREE_SPACE_PERMEABILITY,
+    mom::{
+        geometry::{Mesh, WireGeometry},
+        MomError,
+    },
+    util::{get_rf_resistance, get_skin_depth},
+};
+
+/// Electrical load types, following the NEC LD card. Like NEC, a zero component is left out of
+/// the circuit: a zero series capacitance is a short and a zero parallel resistance or inductance
+/// is an open
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadKind {
+    /// Resistance (ohms), inductance (henries) and capacitance (farads) in series on one segment
+    SeriesRlc { resistance: f64, inductance: f64, capacitance: f64 },
+    /// Resistance, inductance and capacitance in parallel on one segment
+    ParallelRlc { resistance: f64, inductance: f64, capacitance: f64 },
+    /// Series RLC given per meter of wire
+    SeriesRlcPerLength { resistance: f64, inductance: f64, capacitance: f64 },
+    /// Parallel RLC given per meter of wire
+    ParallelRlcPerLength { resistance: f64, inductance: f64, capacitance: f64 },
+    /// Fixed complex impedance in ohms on one segment
+    Impedance(Complex<f64>),
+    /// Skin effect loss of a wire with this conductivity in siemens/meter
+    Conductivity(f64),
+}
+
+impl LoadKind {
+    /// Parallel LC trap resonant at 1 / (2 pi sqrt(LC)), `q` sets the loss of the coil and may be
+    /// infinite for a lossless trap, which is an open circuit that cannot be solved at resonance
+    pub fn trap(inductance: f64, capacitance: f64, q: f64) -> Self {
+        let resonance: f64 = 1.0 / (inductance * capacitance).sqrt();
+        LoadKind::ParallelRlc {
+            resistance: if q.is_finite() { q * resonance * inductance } else { 0.0 },
+            inductance,
+            capacitance,
+        }
+    }
+
+    /// Whether the impedance scales with the length of wire it covers
+    pub fn is_distributed(&self) -> bool {
+        matches!(
+            self,
+            LoadKind::SeriesRlcPerLength { .. } | LoadKind::ParallelRlcPerLength { .. } | LoadKind::Conductivity(_)
+        )
+    }
+
+    /// Impedance in ohms at angular frequency `omega`, lumped loads give their total impedance,
+    /// distributed loads the impedance per meter of a wire with the given radius
+    pub fn impedance(&self, omega: f64, radius: f64) -> Complex<f64> {
+        let series = |r: f64, l: f64, c: f64| {
+            let mut z: Complex<f64> = Complex::new(r, omega * l);
+            if c != 0.0 {
+                z += 1.0 / Complex::new(0.0, omega * c);
+            }
+            z
+        };
+        let parallel = |r: f64, l: f64, c: f64| {
+            let mut y: Complex<f64> = Complex::new(0.0, omega * c);
+            if r != 0.0 {
+                y += 1.0 / r;
+            }
+            if l != 0.0 {
+                y += 1.0 / Complex::new(0.0, omega * l);
+            }
+            1.0 / y
+        };
+        match *self {
+            LoadKind::SeriesRlc { resistance, inductance, capacitance }
+            | LoadKind::SeriesRlcPerLength { resistance, inductance, capacitance } => {
+                series(resistance, inductance, capacitance)
+            }
+            LoadKind::ParallelRlc { resistance, inductance, capacitance }
+            | LoadKind::ParallelRlcPerLength { resistance, inductance, capacitance } => {
+                parallel(resistance, inductance, capacitance)
+            }
+            LoadKind::Impedance(z) => z,
+            LoadKind::Conductivity(conductivity) => {
+                // at HF the skin depth is far below the wire radius, so the internal reactance
+                // equals the resistance
+                let resistivity: f64 = 1.0 / conductivity;
+                let frequency: f64 = omega / (2.0 * std::f64::consts::PI);
+                let skin_depth: f64 = get_skin_depth(frequency, FREE_SPACE_PERMEABILITY, resistivity);
+                let resistance: f64 = get_rf_resistance(skin_depth, 2.0 * radius, resistivity);
+                Complex::new(resistance, resistance)
+            }
+        }
+    }
+}
+
+/// Load placed on a range of segments of one wire
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Load {
+    pub wire: usize,
+    /// First and last loaded segment (inclusive), `None` loads the whole wire
+    pub segments: Option<(usize, usize)>,
+    pub kind: LoadKind,
+}
+
+impl Load {
+    /// Load on a single segment
+    pub fn segment(wire: usize, segment: usize, kind: LoadKind) -> Self {
+        Self {
+            wire,
+            segments: Some((segment, segment)),
+            kind,
+        }
+    }
+    /// Load on every segment of a wire
+    pub fn wire(wire: usize, kind: LoadKind) -> Self {
+        Self {
+            wire,
+            segments: None,
+            kind,
+        }
+    }
+}
+
+/// Impedance added to the diagonal for every unknown of `mesh`. Lumped loads sit in series with
+/// the current sample of their segment, distributed loads act over the length of wire each basis
+/// function spans, including the junction bases of wires loaded along their whole length. A load
+/// without a finite impedance, like an all zero parallel RLC or a zero conductivity, is invalid
+pub(crate) fn load_impedances(
+    geometry: &WireGeometry,
+    mesh: &Mesh,
+    omega: f64,
+) -> Result<DVector<Complex<f64>>, MomError> {
+    let mut loads: DVector<Complex<f64>> = DVector::zeros(mesh.bases.len());
+    let offsets: Vec<usize> = geometry.offsets();
+
+    for (i, load) in geometry.loads.iter().enumerate() {
+        let wire = &geometry.wires[load.wire];
+        let (first, last) = load.segments.unwrap_or((0, wire.segments - 1));
+        let z: Complex<f64> = load.kind.impedance(omega, wire.radius);
+        if !z.is_finite() {
+            return Err(MomError::InvalidLoad(i));
+        }
+        for segment in first..=last {
+            let idx: usize = offsets[load.wire] + segment;
+            loads[idx] += match load.kind.is_distributed() {
+                true => z * mesh.halves(&mesh.bases[idx]).iter().map(|h| h.vector().norm()).sum::<f64>(),
+                false => z,
+            };
+        }
+        if load.kind.is_distributed() && load.segments.is_none() {
+            for idx in geometry.segments()..mesh.bases.len() {
+                let basis = &mesh.bases[idx];
+                for (half, segment) in mesh.halves(basis).iter().zip([basis.minus, basis.plus]) {
+                    if mesh.segments[segment].wire == load.wire {
+                        loads[idx] += z * half.vector().norm();
+                    }
+                }
+            }
+        }
+    }
+    Ok(loads)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use nalgebra::Vector3;
+    use num_complex::Complex;
+
+    use crate::{
+        consts::SPEED_OF_LIGHT,
+        mom::{
+            excitation::VoltageSource,
+            geometry::{StraightWire, WireGeometry},
+            solver::{MomSolution, ThinWireSolver},
+            MomError,
+        },
+    };
+
+    use super::{Load, LoadKind};
+
+    const SOURCE: VoltageSource = VoltageSource {
+        wire: 0,
+        segment: 15,
+        voltage: Complex::new(1.0, 0.0),
+    };
+
+    fn dipole(f: f64) -> WireGeometry {
+        let half: f64 = SPEED_OF_LIGHT / f / 4.0;
+        StraightWire::new(Vector3::new(0.0, 0.0, -half), Vector3::new(0.0, 0.0, half), 1e-3, 31).into()
+    }
+
+    fn solve(f: f64, geometry: &WireGeometry) -> MomSolution {
+        ThinWireSolver::default().solve(f, geometry, &SOURCE.into()).unwrap()
+    }
+
+    #[test]
+    fn test_load_impedances() {
+        let omega: f64 = 2.0 * PI * 14.2e6;
+        let trap: LoadKind = LoadKind::trap(1e-6, 1.0 / (omega * omega * 1e-6), 1000.0);
+        let q_omega_l: f64 = 1000.0 * omega * 1e-6;
+        assert!((trap.impedance(omega, 1e-3) - Complex::new(q_omega_l, 0.0)).norm() < 1e-6 * q_omega_l);
+        assert!(trap.impedance(omega * 0.5, 1e-3).im > 0.0);
+
+        let series: LoadKind = LoadKind::SeriesRlc {
+            resistance: 5.0,
+            inductance: 1e-6,
+            capacitance: 0.0,
+        };
+        let z: Complex<f64> = series.impedance(omega, 1e-3);
+        assert!((z - Complex::new(5.0, omega * 1e-6)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn test_loads_without_finite_impedance() {
+        let open: LoadKind = LoadKind::ParallelRlc {
+            resistance: 0.0,
+            inductance: 0.0,
+            capacitance: 0.0,
+        };
+        for load in [Load::segment(0, 3, open), Load::wire(0, LoadKind::Conductivity(0.0))] {
+            assert!(!load.kind.impedance(2.0 * PI * 14.2e6, 1e-3).is_finite());
+            let mut geometry: WireGeometry = dipole(14.2e6);
+            geometry.add_load(load);
+            let solved = ThinWireSolver::default().solve(14.2e6, &geometry, &SOURCE.into());
+            assert_eq!(solved.unwrap_err(), MomError::InvalidLoad(0));
+        }
+        let mut misplaced: WireGeometry = dipole(14.2e6);
+        misplaced.add_load(Load::segment(3, 0, LoadKind::Impedance(Complex::new(50.0, 0.0))));
+        let matrix = ThinWireSolver::default().impedance_matrix(14.2e6, &misplaced);
+        assert_eq!(matrix.unwrap_err(), MomError::InvalidLoad(0));
+    }
+
+    #[test]
+    fn test_feedpoint_resistor_adds_in_series() {
+        let f: f64 = 14.2e6;
+        let mut geometry: WireGeometry = dipole(f);
+        let unloaded: MomSolution = solve(f, &geometry);
+        geometry.add_load(Load::segment(0, 15, LoadKind::Impedance(Complex::new(10.0, 0.0))));
+        let loaded: MomSolution = solve(f, &geometry);
+
+        let shift: Complex<f64> = loaded.input_impedance().unwrap() - unloaded.input_impedance().unwrap();
+        assert!((shift - Complex::new(10.0, 0.0)).norm() < 1e-6);
+        let expected: f64 = unloaded.input_impedance().unwrap().re / loaded.input_impedance().unwrap().re;
+        assert!((loaded.efficiency().unwrap() - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_copper_loss() {
+        let f: f64 = 14.2e6;
+        let mut geometry: WireGeometry = dipole(f);
+        let lossless: MomSolution = solve(f, &geometry);
+        geometry.add_load(Load::wire(0, LoadKind::Conductivity(5.8e7)));
+        let copper: MomSolution = solve(f, &geometry);
+        dbg!(copper.input_impedance().unwrap(), copper.efficiency().unwrap());
+
+        assert!(copper.input_impedance().unwrap().re > lossless.input_impedance().unwrap().re);
+        assert!(copper.efficiency().unwrap() > 0.97 && copper.efficiency().unwrap() < 1.0);
+    }
+
+    #[test]
+    fn test_trap_isolates_outer_wire() {
+        let f: f64 = 14.2e6;
+        let omega: f64 = 2.0 * PI * f;
+        let inductance: f64 = 2e-6;
+        let trap: LoadKind = LoadKind::trap(inductance, 1.0 / (omega * omega * inductance), 200.0);
+
+        // a 40m sized dipole with traps a third of the way out on each side
+        let mut geometry: WireGeometry = dipole(f / 2.0);
+        let open: MomSolution = solve(f, &geometry);
+        geometry.add_load(Load::segment(0, 10, trap));
+        geometry.add_load(Load::segment(0, 20, trap));
+        let trapped: MomSolution = solve(f, &geometry);
+
+        let outer = |s: &MomSolution| (0..8).map(|i| s.currents[i].norm()).fold(0.0, f64::max);
+        dbg!(outer(&open), outer(&trapped));
+        assert!(outer(&trapped) < 0.25 * outer(&open));
+        assert!(trapped.efficiency().unwrap() < 1.0);
+    }
+}
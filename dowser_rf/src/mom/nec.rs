@@ -0,0 +1,727 @@
+use std::{fmt, fs, io::{self, Write}, path::Path};
+
+use nalgebra::Vector3;
+use num_complex::Complex;
+
+use crate::mom::{
+    excitation::{PlaneWave, VoltageSource},
+    geometry::{StraightWire, WireGeometry},
+    ground::Ground,
+    load::{Load, LoadKind},
+    solver::Kernel,
+};
+
+#[derive(Debug)]
+pub enum NecError {
+    Io(io::Error),
+    /// A card could not be understood, `line` counts from 1
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for NecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NecError::Io(err) => write!(f, "{err}"),
+            NecError::Parse { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for NecError {}
+
+impl From<io::Error> for NecError {
+    fn from(err: io::Error) -> Self {
+        NecError::Io(err)
+    }
+}
+
+/// Frequencies to run a model at, as given by an FR card
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrequencySweep {
+    /// First frequency in Hz
+    pub start: f64,
+    /// Number of frequencies
+    pub steps: usize,
+    /// Increment in Hz, or the ratio between neighbouring frequencies if `multiplicative`
+    pub step: f64,
+    pub multiplicative: bool,
+}
+
+impl FrequencySweep {
+    pub fn single(frequency: f64) -> Self {
+        Self {
+            start: frequency,
+            steps: 1,
+            step: 0.0,
+            multiplicative: false,
+        }
+    }
+    /// Every frequency of the sweep in Hz
+    pub fn frequencies(&self) -> Vec<f64> {
+        (0..self.steps)
+            .map(|i| match self.multiplicative {
+                true => self.start * self.step.powi(i as i32),
+                false => self.start + self.step * i as f64,
+            })
+            .collect()
+    }
+}
+
+/// An EX card describing incident plane waves (types 1 to 3)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NecPlaneWave {
+    /// 1 linear, 2 right hand elliptic, 3 left hand elliptic
+    pub polarization: i32,
+    pub thetas: usize,
+    pub phis: usize,
+    /// Angles in degrees
+    pub theta: f64,
+    pub phi: f64,
+    pub eta: f64,
+    pub theta_step: f64,
+    pub phi_step: f64,
+    pub axial_ratio: f64,
+}
+
+impl NecPlaneWave {
+    /// Every incidence angle of the card as a unit amplitude wave, NEC solves each one separately.
+    /// Elliptic waves have their major axis at `eta` and the minor axis `axial_ratio` times as long
+    pub fn plane_waves(&self) -> Vec<PlaneWave> {
+        let (sin_e, cos_e) = self.eta.to_radians().sin_cos();
+        let minor: Complex<f64> = match self.polarization {
+            2 => Complex::new(0.0, self.axial_ratio),
+            3 => Complex::new(0.0, -self.axial_ratio),
+            _ => Complex::new(0.0, 0.0),
+        };
+        let mut waves: Vec<PlaneWave> = Vec::with_capacity(self.thetas * self.phis);
+        for i in 0..self.thetas {
+            for j in 0..self.phis {
+                waves.push(PlaneWave {
+                    theta: (self.theta + self.theta_step * i as f64).to_radians(),
+                    phi: (self.phi + self.phi_step * j as f64).to_radians(),
+                    e_theta: cos_e - minor * sin_e,
+                    e_phi: sin_e + minor * cos_e,
+                });
+            }
+        }
+        waves
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NecExcitation {
+    /// EX type 0, delta-gap voltage source. Type 5, NEC's current slope discontinuity source, is
+    /// read as the same delta gap and written back as type 0
+    Voltage(VoltageSource),
+    PlaneWave(NecPlaneWave),
+}
+
+/// Ground types of the GN card
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NecGroundKind {
+    FreeSpace,
+    /// Finite ground, reflection coefficient approximation
+    Finite,
+    Perfect,
+    /// Finite ground, Sommerfeld-Norton method
+    Sommerfeld,
+}
+
+/// A GN card
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NecGround {
+    pub kind: NecGroundKind,
+    pub radials: usize,
+    /// Relative permittivity
+    pub permittivity: f64,
+    /// Conductivity in siemens/meter
+    pub conductivity: f64,
+}
+
+impl NecGround {
+    /// GN card for `ground` without radials, none in free space
+    pub fn from_ground(ground: Ground) -> Option<Self> {
+        let (kind, permittivity, conductivity) = match ground {
+            Ground::FreeSpace => return None,
+            Ground::Perfect => (NecGroundKind::Perfect, 0.0, 0.0),
+            Ground::Finite { permittivity, conductivity } => (NecGroundKind::Finite, permittivity, conductivity),
+            Ground::SommerfeldNorton { permittivity, conductivity } => {
+                (NecGroundKind::Sommerfeld, permittivity, conductivity)
+            }
+        };
+        Some(Self {
+            kind,
+            radials: 0,
+            permittivity,
+            conductivity,
+        })
+    }
+
+    /// Ground model used by the solver, a radial screen is not modeled
+    pub fn ground(&self) -> Ground {
+        let (permittivity, conductivity) = (self.permittivity, self.conductivity);
+        match self.kind {
+            NecGroundKind::FreeSpace => Ground::FreeSpace,
+            NecGroundKind::Finite => Ground::Finite { permittivity, conductivity },
+            NecGroundKind::Perfect => Ground::Perfect,
+            NecGroundKind::Sommerfeld => Ground::SommerfeldNorton { permittivity, conductivity },
+        }
+    }
+}
+
+/// An RP card
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NecPattern {
+    pub mode: i32,
+    pub thetas: usize,
+    pub phis: usize,
+    pub xnda: i32,
+    /// Angles in degrees
+    pub theta_start: f64,
+    pub phi_start: f64,
+    pub theta_step: f64,
+    pub phi_step: f64,
+}
+
+/// Model described by a NEC-2 card deck
+#[derive(Debug, Clone)]
+pub struct NecDeck {
+    /// CM and CE card text
+    pub comments: Vec<String>,
+    pub geometry: WireGeometry,
+    /// NEC tag of every wire in `geometry`
+    pub tags: Vec<u32>,
+    /// GE card flag, the structure touches or sits over a ground plane
+    pub ground_plane: bool,
+    /// GN card, without one a ground plane is perfect
+    pub ground: Option<NecGround>,
+    pub kernel: Kernel,
+    pub excitations: Vec<NecExcitation>,
+    pub sweep: FrequencySweep,
+    pub patterns: Vec<NecPattern>,
+}
+
+/// Whitespace or comma separated fields of a card, integers first then floats like NEC's
+/// free-format reader. Missing trailing fields read as zero
+struct Fields<'a> {
+    line: usize,
+    card: &'a str,
+    tokens: Vec<&'a str>,
+    ints: usize,
+}
+
+impl Fields<'_> {
+    fn error(&self, message: String) -> NecError {
+        NecError::Parse {
+            line: self.line,
+            message: format!("{}: {message}", self.card),
+        }
+    }
+    fn int(&self, idx: usize) -> Result<i64, NecError> {
+        let Some(token) = self.tokens.get(idx) else {
+            return Ok(0);
+        };
+        token
+            .parse::<i64>()
+            .ok()
+            .or_else(|| token.parse::<f64>().ok().filter(|v| v.fract() == 0.0).map(|v| v as i64))
+            .ok_or_else(|| self.error(format!("integer field {} is {token:?}", idx + 1)))
+    }
+    fn count(&self, idx: usize) -> Result<usize, NecError> {
+        let value: i64 = self.int(idx)?;
+        usize::try_from(value).map_err(|_| self.error(format!("field {} must not be negative", idx + 1)))
+    }
+    fn tag(&self, idx: usize) -> Result<u32, NecError> {
+        let value: usize = self.count(idx)?;
+        u32::try_from(value).map_err(|_| self.error(format!("tag {value} in field {} is too large", idx + 1)))
+    }
+    fn float(&self, idx: usize) -> Result<f64, NecError> {
+        let Some(token) = self.tokens.get(self.ints + idx) else {
+            return Ok(0.0);
+        };
+        token
+            .parse::<f64>()
+            .map_err(|_| self.error(format!("float field {} is {token:?}", self.ints + idx + 1)))
+    }
+}
+
+impl NecDeck {
+    /// Deck for `geometry` with every wire tagged by its index plus one, and a GN card for its
+    /// ground
+    pub fn new(geometry: WireGeometry) -> Self {
+        let tags: Vec<u32> = (1..=geometry.wires.len() as u32).collect();
+        let ground: Option<NecGround> = NecGround::from_ground(geometry.ground);
+        Self {
+            comments: Vec::new(),
+            geometry,
+            tags,
+            ground_plane: ground.is_some(),
+            ground,
+            kernel: Kernel::Reduced,
+            excitations: Vec::new(),
+            sweep: FrequencySweep::single(299.8e6),
+            patterns: Vec::new(),
+        }
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, NecError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(deck: &str) -> Result<Self, NecError> {
+        let mut model: NecDeck = NecDeck::new(WireGeometry::new());
+
+        for (idx, raw) in deck.lines().enumerate() {
+            let text: &str = raw.trim();
+            if text.is_empty() {
+                continue;
+            }
+            let line: usize = idx + 1;
+            let split: usize = text.char_indices().nth(2).map_or(text.len(), |(i, _)| i);
+            let (card, rest) = text.split_at(split);
+            let card: String = card.to_ascii_uppercase();
+            let ints: usize = match card.as_str() {
+                "CM" | "CE" => {
+                    model.comments.push(rest.trim().to_string());
+                    continue;
+                }
+                "EN" => break,
+                "XQ" => continue,
+                "GE" | "EK" => 1,
+                "GW" | "GS" => 2,
+                "GN" | "EX" | "LD" | "FR" | "RP" => 4,
+                _ => {
+                    return Err(NecError::Parse {
+                        line,
+                        message: format!("unsupported card {card:?}"),
+                    })
+                }
+            };
+            let fields: Fields = Fields {
+                line,
+                card: &card,
+                tokens: rest.split(|c: char| c.is_whitespace() || c == ',').filter(|t| !t.is_empty()).collect(),
+                ints,
+            };
+            model.parse_card(&fields)?;
+        }
+        Ok(model)
+    }
+
+    fn parse_card(&mut self, fields: &Fields) -> Result<(), NecError> {
+        match fields.card {
+            "GW" => {
+                let tag: u32 = fields.tag(0)?;
+                let segments: usize = fields.count(1)?;
+                let start: Vector3<f64> = Vector3::new(fields.float(0)?, fields.float(1)?, fields.float(2)?);
+                let end: Vector3<f64> = Vector3::new(fields.float(3)?, fields.float(4)?, fields.float(5)?);
+                let radius: f64 = fields.float(6)?;
+                if segments == 0 {
+                    return Err(fields.error("wire needs at least one segment".to_string()));
+                }
+                if radius <= 0.0 {
+                    return Err(fields.error("tapered wires (GC) are not supported, radius must be positive".to_string()));
+                }
+                self.geometry.add_wire(StraightWire::new(start, end, radius, segments));
+                self.tags.push(tag);
+            }
+            "GS" => {
+                let scale: f64 = fields.float(0)?;
+                for wire in self.geometry.wires.iter_mut() {
+                    wire.start *= scale;
+                    wire.end *= scale;
+                    wire.radius *= scale;
+                }
+            }
+            "GE" => {
+                self.ground_plane = fields.int(0)? != 0;
+                // NEC-2 takes a ground plane without a GN card to be perfect, a later GN replaces it
+                if self.ground_plane && self.ground.is_none() {
+                    self.geometry.ground = Ground::Perfect;
+                }
+            }
+            "EK" => {
+                self.kernel = match fields.int(0)? {
+                    -1 => Kernel::Reduced,
+                    _ => Kernel::Exact,
+                }
+            }
+            "GN" => {
+                let kind: NecGroundKind = match fields.int(0)? {
+                    -1 => NecGroundKind::FreeSpace,
+                    0 => NecGroundKind::Finite,
+                    1 => NecGroundKind::Perfect,
+                    2 => NecGroundKind::Sommerfeld,
+                    other => return Err(fields.error(format!("unknown ground type {other}"))),
+                };
+                let ground: NecGround = NecGround {
+                    kind,
+                    radials: fields.count(1)?,
+                    permittivity: fields.float(0)?,
+                    conductivity: fields.float(1)?,
+                };
+                self.geometry.ground = ground.ground();
+                self.ground = Some(ground);
+            }
+            "EX" => {
+                let excitation: NecExcitation = match fields.int(0)? {
+                    0 | 5 => {
+                        let (wire, segment) = self
+                            .locate(fields.tag(1)?, fields.count(2)?)
+                            .ok_or_else(|| fields.error("source segment does not exist".to_string()))?;
+                        NecExcitation::Voltage(VoltageSource {
+                            wire,
+                            segment,
+                            voltage: Complex::new(fields.float(0)?, fields.float(1)?),
+                        })
+                    }
+                    polarization @ 1..=3 => NecExcitation::PlaneWave(NecPlaneWave {
+                        polarization: polarization as i32,
+                        thetas: fields.count(1)?,
+                        phis: fields.count(2)?,
+                        theta: fields.float(0)?,
+                        phi: fields.float(1)?,
+                        eta: fields.float(2)?,
+                        theta_step: fields.float(3)?,
+                        phi_step: fields.float(4)?,
+                        axial_ratio: fields.float(5)?,
+                    }),
+                    other => return Err(fields.error(format!("excitation type {other} is not supported"))),
+                };
+                self.excitations.push(excitation);
+            }
+            "LD" => {
+                let [a, b, c] = [fields.float(0)?, fields.float(1)?, fields.float(2)?];
+                let kind: LoadKind = match fields.int(0)? {
+                    0 => LoadKind::SeriesRlc { resistance: a, inductance: b, capacitance: c },
+                    1 => LoadKind::ParallelRlc { resistance: a, inductance: b, capacitance: c },
+                    2 => LoadKind::SeriesRlcPerLength { resistance: a, inductance: b, capacitance: c },
+                    3 => LoadKind::ParallelRlcPerLength { resistance: a, inductance: b, capacitance: c },
+                    4 => LoadKind::Impedance(Complex::new(a, b)),
+                    5 => LoadKind::Conductivity(a),
+                    other => return Err(fields.error(format!("unknown load type {other}"))),
+                };
+                let loads: Vec<Load> = self.resolve_load(fields.tag(1)?, fields.count(2)?, fields.count(3)?, kind);
+                if loads.is_empty() {
+                    return Err(fields.error("load is not placed on any existing segment".to_string()));
+                }
+                self.geometry.loads.extend(loads);
+            }
+            "FR" => {
+                let multiplicative: bool = match fields.int(0)? {
+                    0 => false,
+                    1 => true,
+                    other => return Err(fields.error(format!("unknown stepping type {other}"))),
+                };
+                let step: f64 = fields.float(1)?;
+                self.sweep = FrequencySweep {
+                    start: fields.float(0)? * 1e6,
+                    steps: fields.count(1)?.max(1),
+                    step: if multiplicative { step } else { step * 1e6 },
+                    multiplicative,
+                };
+            }
+            "RP" => self.patterns.push(NecPattern {
+                mode: fields.int(0)? as i32,
+                thetas: fields.count(1)?,
+                phis: fields.count(2)?,
+                xnda: fields.int(3)? as i32,
+                theta_start: fields.float(0)?,
+                phi_start: fields.float(1)?,
+                theta_step: fields.float(2)?,
+                phi_step: fields.float(3)?,
+            }),
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Finds the wire and 0 based segment for a NEC tag and 1 based segment number. Tag 0 numbers
+    /// segments over the whole structure, otherwise they are counted over every wire with the tag
+    fn locate(&self, tag: u32, segment: usize) -> Option<(usize, usize)> {
+        let mut remaining: usize = segment.checked_sub(1)?;
+        for (wire, w) in self.geometry.wires.iter().enumerate() {
+            if tag != 0 && self.tags[wire] != tag {
+                continue;
+            }
+            if remaining < w.segments {
+                return Some((wire, remaining));
+            }
+            remaining -= w.segments;
+        }
+        None
+    }
+
+    /// Splits an LD card over the wires it touches. A zero first and last segment loads every
+    /// wire carrying the tag, or the whole structure for tag 0
+    fn resolve_load(&self, tag: u32, first: usize, last: usize, kind: LoadKind) -> Vec<Load> {
+        let mut loads: Vec<Load> = Vec::new();
+        let mut number: usize = 1;
+        for (wire, w) in self.geometry.wires.iter().enumerate() {
+            if tag != 0 && self.tags[wire] != tag {
+                continue;
+            }
+            if first == 0 && last == 0 {
+                loads.push(Load::wire(wire, kind));
+            } else {
+                let (lo, hi) = (first.max(number), last.min(number + w.segments - 1));
+                if lo <= hi {
+                    loads.push(Load {
+                        wire,
+                        segments: Some((lo - number, hi - number)),
+                        kind,
+                    });
+                }
+            }
+            number += w.segments;
+        }
+        loads
+    }
+
+    /// Inverse of `locate` for a tagged wire
+    fn segment_number(&self, wire: usize, segment: usize) -> usize {
+        let tag: u32 = self.tags[wire];
+        let before: usize = (0..wire)
+            .filter(|&w| tag == 0 || self.tags[w] == tag)
+            .map(|w| self.geometry.wires[w].segments)
+            .sum();
+        before + segment + 1
+    }
+
+    /// Writes the deck in free format, geometry is written already scaled to meters
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        if self.comments.is_empty() {
+            writeln!(writer, "CE")?;
+        }
+        for (i, comment) in self.comments.iter().enumerate() {
+            let card: &str = if i + 1 == self.comments.len() { "CE" } else { "CM" };
+            writeln!(writer, "{card} {comment}")?;
+        }
+        for (wire, tag) in self.geometry.wires.iter().zip(self.tags.iter()) {
+            writeln!(
+                writer,
+                "GW {tag} {} {} {} {} {} {} {} {}",
+                wire.segments, wire.start.x, wire.start.y, wire.start.z, wire.end.x, wire.end.y, wire.end.z, wire.radius
+            )?;
+        }
+        writeln!(writer, "GE {}", if self.ground_plane { 1 } else { 0 })?;
+        if self.kernel == Kernel::Exact {
+            writeln!(writer, "EK 0")?;
+        }
+        if let Some(ground) = self.ground {
+            let kind: i32 = match ground.kind {
+                NecGroundKind::FreeSpace => -1,
+                NecGroundKind::Finite => 0,
+                NecGroundKind::Perfect => 1,
+                NecGroundKind::Sommerfeld => 2,
+            };
+            writeln!(writer, "GN {kind} {} 0 0 {} {}", ground.radials, ground.permittivity, ground.conductivity)?;
+        }
+        for load in self.geometry.loads.iter() {
+            let (kind, [a, b, c]) = match load.kind {
+                LoadKind::SeriesRlc { resistance, inductance, capacitance } => (0, [resistance, inductance, capacitance]),
+                LoadKind::ParallelRlc { resistance, inductance, capacitance } => (1, [resistance, inductance, capacitance]),
+                LoadKind::SeriesRlcPerLength { resistance, inductance, capacitance } => {
+                    (2, [resistance, inductance, capacitance])
+                }
+                LoadKind::ParallelRlcPerLength { resistance, inductance, capacitance } => {
+                    (3, [resistance, inductance, capacitance])
+                }
+                LoadKind::Impedance(z) => (4, [z.re, z.im, 0.0]),
+                LoadKind::Conductivity(conductivity) => (5, [conductivity, 0.0, 0.0]),
+            };
+            let tag: u32 = self.tags[load.wire];
+            let shares_tag: bool = tag == 0 || self.tags.iter().filter(|&&t| t == tag).count() > 1;
+            let (first, last) = match load.segments {
+                None if !shares_tag => (0, 0),
+                None => (
+                    self.segment_number(load.wire, 0),
+                    self.segment_number(load.wire, self.geometry.wires[load.wire].segments - 1),
+                ),
+                Some((first, last)) => (self.segment_number(load.wire, first), self.segment_number(load.wire, last)),
+            };
+            writeln!(writer, "LD {kind} {tag} {first} {last} {a} {b} {c}")?;
+        }
+        for excitation in self.excitations.iter() {
+            match excitation {
+                NecExcitation::Voltage(source) => writeln!(
+                    writer,
+                    "EX 0 {} {} 0 {} {}",
+                    self.tags[source.wire],
+                    self.segment_number(source.wire, source.segment),
+                    source.voltage.re,
+                    source.voltage.im
+                )?,
+                NecExcitation::PlaneWave(wave) => writeln!(
+                    writer,
+                    "EX {} {} {} 0 {} {} {} {} {} {}",
+                    wave.polarization,
+                    wave.thetas,
+                    wave.phis,
+                    wave.theta,
+                    wave.phi,
+                    wave.eta,
+                    wave.theta_step,
+                    wave.phi_step,
+                    wave.axial_ratio
+                )?,
+            }
+        }
+        let sweep: &FrequencySweep = &self.sweep;
+        let step: f64 = if sweep.multiplicative { sweep.step } else { sweep.step / 1e6 };
+        writeln!(
+            writer,
+            "FR {} {} 0 0 {} {step}",
+            if sweep.multiplicative { 1 } else { 0 },
+            sweep.steps,
+            sweep.start / 1e6
+        )?;
+        for pattern in self.patterns.iter() {
+            writeln!(
+                writer,
+                "RP {} {} {} {} {} {} {} {}",
+                pattern.mode,
+                pattern.thetas,
+                pattern.phis,
+                pattern.xnda,
+                pattern.theta_start,
+                pattern.phi_start,
+                pattern.theta_step,
+                pattern.phi_step
+            )?;
+        }
+        writeln!(writer, "EN")
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer: io::BufWriter<fs::File> = io::BufWriter::new(fs::File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use num_complex::Complex;
+
+    use crate::mom::{
+        geometry::{StraightWire, WireGeometry},
+        ground::Ground,
+        load::LoadKind,
+    };
+
+    use super::{NecDeck, NecError, NecExcitation, NecGroundKind};
+
+    const DIPOLE: &str = "CM 20m dipole over real ground
+CE
+GW 1 21 0 -5.3 10 0 5.3 10 0.001
+GW 2 1, 0, 0, 0, 0, 0, 1, 0.001
+GS 0 0 1.0
+GE 1
+GN 2 0 0 0 13 0.005
+LD 5 1 0 0 5.8e7
+LD 0 1 11 11 10 1e-6 0
+EX 0 1 11 0 1 0
+FR 0 3 0 0 14.0 0.1
+RP 0 19 73 1000 0 0 5 5
+EN
+";
+
+    #[test]
+    fn test_parse_deck() {
+        let deck: NecDeck = NecDeck::parse(DIPOLE).unwrap();
+
+        assert_eq!(deck.comments, vec!["20m dipole over real ground".to_string(), String::new()]);
+        assert_eq!(deck.geometry.wires.len(), 2);
+        assert_eq!(deck.tags, vec![1, 2]);
+        assert_eq!(deck.geometry.wires[0].segments, 21);
+        assert!(deck.ground_plane);
+        assert_eq!(deck.ground.unwrap().kind, NecGroundKind::Sommerfeld);
+        assert!(matches!(deck.geometry.ground, Ground::SommerfeldNorton { .. }));
+        let loads = &deck.geometry.loads;
+        assert_eq!(loads.len(), 2);
+        assert_eq!(loads[0].kind, LoadKind::Conductivity(5.8e7));
+        assert_eq!(loads[0].segments, None);
+        assert_eq!(loads[1].segments, Some((10, 10)));
+        match deck.excitations[0] {
+            NecExcitation::Voltage(source) => {
+                assert_eq!((source.wire, source.segment), (0, 10));
+                assert_eq!(source.voltage, Complex::new(1.0, 0.0));
+            }
+            _ => panic!("expected a voltage source"),
+        }
+        let frequencies: Vec<f64> = deck.sweep.frequencies();
+        assert_eq!(frequencies.len(), 3);
+        assert!((frequencies[2] - 14.2e6).abs() < 1e-3);
+        assert_eq!(deck.patterns[0].phis, 73);
+    }
+
+    #[test]
+    fn test_ground_plane_without_gn_is_perfect() {
+        let deck: NecDeck = NecDeck::parse("GW 1 11 0 -5 10 0 5 10 0.001\nGE 1\nEX 0 1 6 0 1 0\nEN\n").unwrap();
+        assert!(deck.ground_plane);
+        assert!(deck.ground.is_none());
+        assert_eq!(deck.geometry.ground, Ground::Perfect);
+        let free: NecDeck = NecDeck::parse("GW 1 11 0 -5 10 0 5 10 0.001\nGE 0\nEN\n").unwrap();
+        assert_eq!(free.geometry.ground, Ground::FreeSpace);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let deck: NecDeck = NecDeck::parse(DIPOLE).unwrap();
+        let mut written: Vec<u8> = Vec::new();
+        deck.write(&mut written).unwrap();
+        let reparsed: NecDeck = NecDeck::parse(std::str::from_utf8(&written).unwrap()).unwrap();
+
+        assert_eq!(reparsed.comments, deck.comments);
+        assert_eq!(reparsed.tags, deck.tags);
+        for (a, b) in reparsed.geometry.wires.iter().zip(deck.geometry.wires.iter()) {
+            assert_eq!((a.start, a.end, a.radius, a.segments), (b.start, b.end, b.radius, b.segments));
+        }
+        assert_eq!(reparsed.ground, deck.ground);
+        assert_eq!(reparsed.geometry.loads, deck.geometry.loads);
+        assert_eq!(reparsed.excitations, deck.excitations);
+        assert_eq!(reparsed.sweep, deck.sweep);
+        assert_eq!(reparsed.patterns, deck.patterns);
+    }
+
+    #[test]
+    fn test_errors_carry_line_numbers() {
+        let bad_float: &str = "CM test\nCE\nGW 1 21 0 -5 x 0 5 0 0.001\nEN\n";
+        match NecDeck::parse(bad_float) {
+            Err(NecError::Parse { line, .. }) => assert_eq!(line, 3),
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+
+        let missing_tag: &str = "GW 1 5 0 0 0 0 0 1 0.001\nGE 0\nEX 0 7 3 0 1 0\n";
+        match NecDeck::parse(missing_tag) {
+            Err(NecError::Parse { line, .. }) => assert_eq!(line, 3),
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+
+        assert!(NecDeck::parse("GC 0 0 1 0.001 0.002\n").is_err());
+        // 2^32 + 1 would wrap to tag 1
+        assert!(NecDeck::parse("GW 4294967297 5 0 0 0 0 0 1 0.001\n").is_err());
+    }
+
+    #[test]
+    fn test_new_deck_keeps_ground() {
+        let sommerfeld: Ground = Ground::SommerfeldNorton {
+            permittivity: 5.0,
+            conductivity: 0.001,
+        };
+        for ground in [Ground::Perfect, Ground::AVERAGE, sommerfeld] {
+            let mut geometry: WireGeometry =
+                StraightWire::new(Vector3::new(0.0, -5.0, 10.0), Vector3::new(0.0, 5.0, 10.0), 1e-3, 11).into();
+            geometry.ground = ground;
+            let mut written: Vec<u8> = Vec::new();
+            NecDeck::new(geometry).write(&mut written).unwrap();
+            let reparsed: NecDeck = NecDeck::parse(std::str::from_utf8(&written).unwrap()).unwrap();
+            assert_eq!(reparsed.geometry.ground, ground);
+            assert!(reparsed.ground_plane);
+        }
+        let free: NecDeck = NecDeck::new(WireGeometry::new());
+        assert!(free.ground.is_none() && !free.ground_plane);
+    }
+}
@@ -0,0 +1,640 @@
+use std::{
+    f64::consts::{LN_2, PI},
+    sync::LazyLock,
+};
+
+use nalgebra::{DMatrix, DVector, Scalar, Vector3};
+use num_complex::Complex;
+
+use crate::{
+    consts::{FREE_SPACE_PERMEABILITY, FREE_SPACE_PERMITTIVITY, SPEED_OF_LIGHT},
+    mom::{
+        excitation::{excitation_vector, Excitation, VoltageSource},
+        geometry::{HalfSegment, Mesh, WireGeometry},
+        ground::mirror,
+        load::load_impedances,
+        MomError,
+    },
+    quadrature::{line_kernel_integral, GaussLegendre},
+    util::{gmres, hz_to_angular_freq, GmresSettings, IterativeSolution, LuFactorization},
+};
+
+/// Rule for the smooth part of the segment integrals
+static SEGMENT_RULE: LazyLock<GaussLegendre> = LazyLock::new(|| GaussLegendre::new(8));
+
+/// Thin-wire approximation of the Green's function used on a segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kernel {
+    /// Current on the wire axis, observed on the surface, R = sqrt(|r - r'|^2 + a^2)
+    Reduced,
+    /// Current on the wire surface, observed on the axis. Only differs from the reduced kernel
+    /// when the observation point sits on the axis of the source segment
+    Exact,
+}
+
+/// Frequency domain thin-wire solver for the mixed-potential form of Pocklington's equation.
+///
+/// Currents are expanded in triangle-like basis functions centered on every segment and tested
+/// with the same functions, so the derivatives of Pocklington's integro-differential equation end
+/// up as finite differences of scalar potentials instead of a hypersingular kernel.
+#[derive(Debug, Clone, Copy)]
+pub struct ThinWireSolver {
+    pub kernel: Kernel,
+    pub method: SolveMethod,
+}
+
+impl Default for ThinWireSolver {
+    fn default() -> Self {
+        Self {
+            kernel: Kernel::Reduced,
+            method: SolveMethod::Direct,
+        }
+    }
+}
+
+/// How the impedance matrix equation is solved
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SolveMethod {
+    /// LU factorization, exact up to rounding and shared by every excitation
+    Direct,
+    /// Preconditioned GMRES, faster for models with thousands of segments
+    Iterative(GmresSettings),
+}
+
+#[derive(Debug, Clone)]
+pub struct MomSolution {
+    /// Frequency in Hz
+    pub frequency: f64,
+    /// Complex current in amperes at the center of every segment, wire after wire, followed by
+    /// the currents flowing through each junction
+    pub currents: DVector<Complex<f64>>,
+    /// Voltage sources of the excitation, in the order they were given
+    pub sources: Vec<VoltageSource>,
+    /// Index of the first current belonging to every wire
+    pub offsets: Vec<usize>,
+    /// Load impedance in ohms in series with every current
+    pub loads: DVector<Complex<f64>>,
+    /// Estimated 1-norm condition number of the impedance matrix, a very large value points at
+    /// segments that are too short for their radius or a matrix that is close to singular. NaN
+    /// when solved iteratively
+    pub condition: f64,
+    /// Relative residual after every GMRES iteration, empty for the direct solver
+    pub residuals: Vec<f64>,
+}
+
+impl MomSolution {
+    /// Current in amperes at the center of `segment` on `wire`, positive from `start` to `end`
+    pub fn segment_current(&self, wire: usize, segment: usize) -> Complex<f64> {
+        self.currents[self.offsets[wire] + segment]
+    }
+    /// Current in amperes flowing through the `idx`th voltage source
+    pub fn source_current(&self, idx: usize) -> Complex<f64> {
+        let source: &VoltageSource = &self.sources[idx];
+        self.segment_current(source.wire, source.segment)
+    }
+    /// Active impedance in ohms seen by every voltage source, mutual coupling to the other
+    /// sources and any incident field included
+    pub fn source_impedances(&self) -> Vec<Complex<f64>> {
+        (0..self.sources.len())
+            .map(|idx| self.sources[idx].voltage / self.source_current(idx))
+            .collect()
+    }
+    /// Active admittance in siemens seen by every voltage source
+    pub fn source_admittances(&self) -> Vec<Complex<f64>> {
+        (0..self.sources.len())
+            .map(|idx| self.source_current(idx) / self.sources[idx].voltage)
+            .collect()
+    }
+    /// Impedance in ohms seen by the first voltage source, `None` without any, as in receive mode
+    pub fn input_impedance(&self) -> Option<Complex<f64>> {
+        let source: &VoltageSource = self.sources.first()?;
+        Some(source.voltage / self.source_current(0))
+    }
+    /// Time averaged power delivered by all voltage sources in watts
+    pub fn input_power(&self) -> f64 {
+        (0..self.sources.len())
+            .map(|idx| 0.5 * (self.sources[idx].voltage * self.source_current(idx).conj()).re)
+            .sum()
+    }
+    /// Time averaged power dissipated in the loads in watts
+    pub fn loss_power(&self) -> f64 {
+        self.currents
+            .iter()
+            .zip(self.loads.iter())
+            .map(|(i, z)| 0.5 * i.norm_sqr() * z.re)
+            .sum()
+    }
+    /// Fraction of the input power that is radiated, `None` without voltage sources
+    pub fn efficiency(&self) -> Option<f64> {
+        (!self.sources.is_empty()).then(|| 1.0 - self.loss_power() / self.input_power())
+    }
+}
+
+impl ThinWireSolver {
+    pub fn new(kernel: Kernel) -> Self {
+        Self {
+            kernel,
+            ..Default::default()
+        }
+    }
+
+    /// Fills the complex impedance matrix of `geometry` at `frequency` Hz, loads included
+    pub fn impedance_matrix(&self, frequency: f64, geometry: &WireGeometry) -> Result<DMatrix<Complex<f64>>, MomError> {
+        geometry.validate()?;
+        let mesh: Mesh = Mesh::new(geometry);
+        let loads: DVector<Complex<f64>> = load_impedances(geometry, &mesh, hz_to_angular_freq(frequency))?;
+        Ok(self.loaded_matrix(frequency, &mesh, &loads))
+    }
+
+    /// Impedance matrix with `loads` on its diagonal
+    fn loaded_matrix(&self, frequency: f64, mesh: &Mesh, loads: &DVector<Complex<f64>>) -> DMatrix<Complex<f64>> {
+        let mut z_matrix: DMatrix<Complex<f64>> = self.fill(frequency, mesh);
+        z_matrix.set_diagonal(&(z_matrix.diagonal() + loads));
+        z_matrix
+    }
+
+    /// Solves for the segment currents on `geometry` driven by every part of `excitation`
+    pub fn solve(
+        &self,
+        frequency: f64,
+        geometry: &WireGeometry,
+        excitation: &Excitation,
+    ) -> Result<MomSolution, MomError> {
+        Ok(self.solve_all(frequency, geometry, std::slice::from_ref(excitation))?.remove(0))
+    }
+
+    /// Solves every excitation separately, the direct solver factors the impedance matrix only once.
+    /// An iterative solve that does not converge fails with its residual history
+    pub fn solve_all(
+        &self,
+        frequency: f64,
+        geometry: &WireGeometry,
+        excitations: &[Excitation],
+    ) -> Result<Vec<MomSolution>, MomError> {
+        geometry.validate()?;
+        let k: f64 = hz_to_angular_freq(frequency) / SPEED_OF_LIGHT;
+        let mesh: Mesh = Mesh::new(geometry);
+        let v_vectors: Vec<DVector<Complex<f64>>> = excitations
+            .iter()
+            .map(|excitation| excitation_vector(geometry, &mesh, k, excitation))
+            .collect::<Result<_, _>>()?;
+
+        let loads: DVector<Complex<f64>> = load_impedances(geometry, &mesh, hz_to_angular_freq(frequency))?;
+        let z_matrix: DMatrix<Complex<f64>> = self.loaded_matrix(frequency, &mesh, &loads);
+        let solved: Vec<(DVector<Complex<f64>>, f64, Vec<f64>)> = match self.method {
+            SolveMethod::Direct => {
+                let lu: LuFactorization<Complex<f64>> = LuFactorization::new(z_matrix)?;
+                let condition: f64 = lu.condition_estimate();
+                v_vectors
+                    .iter()
+                    .map(|v_vector| Ok((lu.solve(v_vector)?, condition, Vec::new())))
+                    .collect::<Result<_, MomError>>()?
+            }
+            SolveMethod::Iterative(settings) => v_vectors
+                .iter()
+                .map(|v_vector| {
+                    let solution: IterativeSolution<Complex<f64>> = gmres(&z_matrix, v_vector, &settings)?;
+                    match solution.converged {
+                        true => Ok((solution.x, f64::NAN, solution.residuals)),
+                        false => Err(MomError::NotConverged(solution.residuals)),
+                    }
+                })
+                .collect::<Result<_, MomError>>()?,
+        };
+        Ok(excitations
+            .iter()
+            .zip(solved)
+            .map(|(excitation, (currents, condition, residuals))| MomSolution {
+                frequency,
+                currents,
+                sources: excitation.sources.clone(),
+                offsets: geometry.offsets(),
+                loads: loads.clone(),
+                condition,
+                residuals,
+            })
+            .collect())
+    }
+
+    pub(crate) fn fill(&self, frequency: f64, mesh: &Mesh) -> DMatrix<Complex<f64>> {
+        let omega: f64 = hz_to_angular_freq(frequency);
+        let k: f64 = omega / SPEED_OF_LIGHT;
+
+        // scalar potential of every charge segment seen from the middle of every other one
+        let n_segments: usize = mesh.segments.len();
+        let segment_psi = |obs: usize, src: usize| {
+            let s = &mesh.segments[src];
+            psi(s.start, s.end, s.radius, mesh.segments[obs].midpoint(), k, self.kernel)
+        };
+        let places: Vec<Option<(usize, usize)>> =
+            mesh.segments.iter().map(|s| s.position.map(|p| (s.wire, p))).collect();
+        let toeplitz: Toeplitz = Toeplitz::new(places, segment_psi);
+        let scalar: DMatrix<Complex<f64>> = matrix_from_fn(n_segments, n_segments, |obs, src| {
+            toeplitz.get(obs, src).unwrap_or_else(|| segment_psi(obs, src))
+        });
+
+        // vector potential of every half basis seen from the center of every other one. Within a
+        // wire the full length halves step along it half a segment at a time
+        let halves: Vec<HalfSegment> = mesh.bases.iter().flat_map(|b| mesh.halves(b)).collect();
+        let half_psi = |obs: usize, src: usize| {
+            let h = &halves[src];
+            psi(h.start, h.end, h.radius, halves[obs].center(), k, self.kernel)
+        };
+        let places: Vec<Option<(usize, usize)>> = mesh
+            .bases
+            .iter()
+            .flat_map(|b| {
+                let minus = &mesh.segments[b.minus];
+                let plus = &mesh.segments[b.plus];
+                [minus.position.map(|p| (minus.wire, 2 * p)), plus.position.map(|p| (plus.wire, 2 * p - 1))]
+            })
+            .collect();
+        let toeplitz: Toeplitz = Toeplitz::new(places, half_psi);
+        let vector: DMatrix<Complex<f64>> = matrix_from_fn(halves.len(), halves.len(), |obs, src| {
+            toeplitz.get(obs, src).unwrap_or_else(|| half_psi(obs, src))
+        });
+
+        // the same from the images, weighted by the ground. Image charges take the negated
+        // vertical coefficient, image currents the vertical or horizontal one by component. The
+        // coefficients change with height, so the images are always filled in full
+        let images: bool = !mesh.ground.is_free_space();
+        let image_psi = |start: Vector3<f64>, end: Vector3<f64>, radius: f64, obs: Vector3<f64>| {
+            let (start, end) = (mirror(start), mirror(end));
+            let coefficients = mesh.ground.image_coefficients(omega, k, obs, (start + end) / 2.0);
+            (coefficients, psi(start, end, radius, obs, k, self.kernel))
+        };
+        let scalar_image: DMatrix<Complex<f64>> = match images {
+            true => matrix_from_fn(n_segments, n_segments, |obs, src| {
+                let s = &mesh.segments[src];
+                let ((vertical, _), p) = image_psi(s.start, s.end, s.radius, mesh.segments[obs].midpoint());
+                -vertical * p
+            }),
+            false => DMatrix::zeros(0, 0),
+        };
+        let vector_image: DMatrix<(Complex<f64>, Complex<f64>)> = match images {
+            true => matrix_from_fn(halves.len(), halves.len(), |obs, src| {
+                let h = &halves[src];
+                let ((vertical, horizontal), p) = image_psi(h.start, h.end, h.radius, halves[obs].center());
+                (vertical * p, horizontal * p)
+            }),
+            false => DMatrix::from_element(0, 0, Default::default()),
+        };
+
+        let jwu: Complex<f64> = Complex::new(0.0, omega * FREE_SPACE_PERMEABILITY);
+        let jwe: Complex<f64> = Complex::new(0.0, omega * FREE_SPACE_PERMITTIVITY);
+        let n_bases: usize = mesh.bases.len();
+        matrix_from_fn(n_bases, n_bases, |m, n| {
+            let (b_m, b_n) = (&mesh.bases[m], &mesh.bases[n]);
+            let phi = |s: &DMatrix<Complex<f64>>| {
+                s[(b_m.plus, b_n.plus)] - s[(b_m.plus, b_n.minus)] - s[(b_m.minus, b_n.plus)] + s[(b_m.minus, b_n.minus)]
+            };
+            let mut a_term: Complex<f64> = Complex::new(0.0, 0.0);
+            let mut phi_term: Complex<f64> = phi(&scalar);
+            for h_m in 2 * m..2 * m + 2 {
+                for h_n in 2 * n..2 * n + 2 {
+                    let (t, s) = (halves[h_m].vector(), halves[h_n].vector());
+                    a_term += t.dot(&s) * vector[(h_m, h_n)];
+                    if images && !b_n.grounded {
+                        let (vertical, horizontal) = vector_image[(h_m, h_n)];
+                        a_term += (t.x * s.x + t.y * s.y) * horizontal + t.z * s.z * vertical;
+                    }
+                }
+            }
+            if images && !b_n.grounded {
+                phi_term += phi(&scalar_image);
+            }
+            jwu * a_term + phi_term / jwe
+        })
+    }
+}
+
+/// Builds a matrix from a function of (row, column), spread over every core with the `parallel`
+/// feature
+fn matrix_from_fn<T, F>(rows: usize, cols: usize, f: F) -> DMatrix<T>
+where
+    T: Scalar + Send,
+    F: Fn(usize, usize) -> T + Sync,
+{
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        let data: Vec<T> = (0..rows * cols).into_par_iter().map(|idx| f(idx % rows, idx / rows)).collect();
+        DMatrix::from_vec(rows, cols, data)
+    }
+    #[cfg(not(feature = "parallel"))]
+    DMatrix::from_fn(rows, cols, f)
+}
+
+/// Interactions between equal elements of a uniformly segmented straight wire only depend on how
+/// many steps apart they are, so every wire needs one row of them instead of a full block
+struct Toeplitz {
+    /// Wire and step along it of every element, `None` for elements that break the pattern
+    places: Vec<Option<(usize, usize)>>,
+    /// Interaction of every wire's first element with the ones after it, by distance in steps
+    rows: Vec<Vec<Complex<f64>>>,
+}
+
+impl Toeplitz {
+    /// `interaction(obs, src)` is called once per element of every wire, steps along a wire
+    /// must be consecutive
+    fn new(places: Vec<Option<(usize, usize)>>, interaction: impl Fn(usize, usize) -> Complex<f64>) -> Self {
+        let wires: usize = places.iter().flatten().map(|&(w, _)| w + 1).max().unwrap_or(0);
+        let mut chains: Vec<Vec<(usize, usize)>> = vec![Vec::new(); wires];
+        for (idx, place) in places.iter().enumerate() {
+            if let Some((wire, step)) = *place {
+                chains[wire].push((step, idx));
+            }
+        }
+        let rows: Vec<Vec<Complex<f64>>> = chains
+            .iter_mut()
+            .map(|chain| {
+                chain.sort_unstable();
+                chain.iter().map(|&(_, obs)| interaction(obs, chain[0].1)).collect()
+            })
+            .collect();
+        Self { places, rows }
+    }
+
+    fn get(&self, obs: usize, src: usize) -> Option<Complex<f64>> {
+        match (self.places[obs], self.places[src]) {
+            (Some((w_obs, s_obs)), Some((w_src, s_src))) if w_obs == w_src => Some(self.rows[w_obs][s_obs.abs_diff(s_src)]),
+            _ => None,
+        }
+    }
+}
+
+/// Average of e^(-jkR) / (4 pi R) over the segment from `start` to `end` as seen from `obs`.
+///
+/// The 1/R part is integrated analytically and only the smooth remainder (e^(-jkR) - 1) / R is
+/// left to quadrature, so coincident source and observation points need no special casing.
+pub(crate) fn psi(
+    start: Vector3<f64>,
+    end: Vector3<f64>,
+    radius: f64,
+    obs: Vector3<f64>,
+    k: f64,
+    kernel: Kernel,
+) -> Complex<f64> {
+    let length: f64 = (end - start).norm();
+    let axis: Vector3<f64> = (end - start) / length;
+    let rel: Vector3<f64> = obs - start;
+    let z_0: f64 = rel.dot(&axis);
+    let rho2: f64 = (rel.norm_squared() - z_0 * z_0).max(0.0);
+
+    let rho_e: f64 = (rho2 + radius * radius).sqrt();
+    let mut integral: Complex<f64> =
+        SEGMENT_RULE.integrate_singular(length, z_0, rho_e, |r| Complex::new(0.0, -k * r).exp());
+    // on the axis of the source the exact kernel swaps out the analytic 1/R part
+    if kernel == Kernel::Exact && rho2 <= (1e-6 * radius).powi(2) {
+        integral += exact_kernel_integral(length - z_0, radius) + exact_kernel_integral(z_0, radius)
+            - line_kernel_integral(length, z_0, rho_e);
+    }
+
+    integral / (4.0 * PI * length)
+}
+
+/// Integral of the exact cylindrical kernel along the axis from 0 to `x`, which is an odd
+/// function of `x`. The logarithmic singularity of the azimuthal integral is removed analytically
+/// (the mean of -ln|sin(phi/2)| is ln 2) and what is left is smooth
+fn exact_kernel_integral(x: f64, radius: f64) -> f64 {
+    let x_n: f64 = x.abs() / (2.0 * radius);
+    // panels get smaller towards phi = 0 where the integrand is steepest for small x
+    let panels: [f64; 5] = [0.0, PI / 64.0, PI / 16.0, PI / 4.0, PI];
+    let sum: f64 = panels
+        .windows(2)
+        .map(|p| {
+            SEGMENT_RULE.integrate(p[0], p[1], |phi| {
+                let s: f64 = f64::sin(phi / 2.0);
+                (x_n + (x_n * x_n + s * s).sqrt()).ln()
+            })
+        })
+        .sum();
+    x.signum() * (sum / PI + LN_2)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use num_complex::Complex;
+
+    use crate::{
+        antennas::dipole,
+        consts::SPEED_OF_LIGHT,
+        mom::{
+            excitation::{Excitation, VoltageSource},
+            geometry::{HalfSegment, Mesh, StraightWire, WireGeometry},
+            MomError,
+        },
+        util::GmresSettings,
+    };
+
+    use super::{psi, Kernel, SolveMethod, ThinWireSolver, Toeplitz};
+
+    fn half_wave_dipole(f: f64, segments: usize) -> StraightWire {
+        let half: f64 = SPEED_OF_LIGHT / f / 4.0;
+        StraightWire::new(Vector3::new(0.0, 0.0, -half), Vector3::new(0.0, 0.0, half), 1e-3, segments)
+    }
+
+    fn center_source(segment: usize) -> VoltageSource {
+        VoltageSource {
+            wire: 0,
+            segment,
+            voltage: Complex::new(1.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn test_half_wave_dipole_impedance() {
+        let f: f64 = 14.2e6;
+        let wire: StraightWire = half_wave_dipole(f, 51);
+        let z = ThinWireSolver::default()
+            .solve(f, &wire.into(), &center_source(25).into())
+            .unwrap()
+            .input_impedance().unwrap();
+        let analytic = dipole::z(f, wire.length(), 2.0 * wire.radius);
+        dbg!(z, analytic);
+
+        assert!((z.re - analytic.re).abs() / analytic.re < 0.1);
+        assert!((z.im - analytic.im).abs() < 10.0);
+    }
+
+    #[test]
+    fn test_dipole_current_is_symmetric() {
+        let f: f64 = 7.1e6;
+        let solution = ThinWireSolver::default()
+            .solve(f, &half_wave_dipole(f, 21).into(), &center_source(10).into())
+            .unwrap();
+        let currents = solution.currents;
+
+        for i in 0..10 {
+            assert!((currents[i] - currents[20 - i]).norm() < 1e-9 * currents[10].norm());
+            assert!(currents[i].norm() < currents[i + 1].norm());
+        }
+    }
+
+    #[test]
+    fn test_solve_all_reuses_factors() {
+        let f: f64 = 14.2e6;
+        let geometry: WireGeometry = half_wave_dipole(f, 21).into();
+        let solver: ThinWireSolver = ThinWireSolver::default();
+        let excitations: Vec<Excitation> = (8..13).map(|segment| center_source(segment).into()).collect();
+        let solutions = solver.solve_all(f, &geometry, &excitations).unwrap();
+
+        for (excitation, batched) in excitations.iter().zip(solutions.iter()) {
+            let single = solver.solve(f, &geometry, excitation).unwrap();
+            assert!((&single.currents - &batched.currents).norm() < 1e-12 * single.currents.norm());
+        }
+        // moving the feed off center raises the resistance
+        assert!(solutions[0].input_impedance().unwrap().re > solutions[2].input_impedance().unwrap().re);
+        assert!(solutions[2].condition.is_finite() && solutions[2].condition > 1.0);
+    }
+
+    #[test]
+    fn test_iterative_matches_direct() {
+        // a broadside curtain of four dipoles, one preconditioner block per element
+        let f: f64 = 14.2e6;
+        let spacing: f64 = 0.5 * SPEED_OF_LIGHT / f;
+        let mut geometry: WireGeometry = WireGeometry::new();
+        let mut excitation: Excitation = Excitation::new();
+        for i in 0..4 {
+            let offset: Vector3<f64> = Vector3::new(spacing * i as f64, 0.0, 0.0);
+            let wire: StraightWire = half_wave_dipole(f, 21);
+            let idx: usize = geometry.add_wire(StraightWire::new(wire.start + offset, wire.end + offset, wire.radius, 21));
+            excitation.add_source(VoltageSource {
+                wire: idx,
+                segment: 10,
+                voltage: Complex::new(1.0, 0.0),
+            });
+        }
+        let settings: GmresSettings = GmresSettings {
+            tolerance: 1e-10,
+            block_size: 21,
+            ..Default::default()
+        };
+        let direct = ThinWireSolver::default().solve(f, &geometry, &excitation).unwrap();
+        let iterative = ThinWireSolver {
+            method: SolveMethod::Iterative(settings),
+            ..Default::default()
+        }
+        .solve(f, &geometry, &excitation)
+        .unwrap();
+        dbg!(iterative.residuals.len(), direct.condition);
+
+        assert!((&iterative.currents - &direct.currents).norm() < 1e-8 * direct.currents.norm());
+        assert!(iterative.residuals.len() < 30);
+        assert!(direct.residuals.is_empty());
+
+        let starved = ThinWireSolver {
+            method: SolveMethod::Iterative(GmresSettings { max_iterations: 1, ..settings }),
+            ..Default::default()
+        }
+        .solve(f, &geometry, &excitation);
+        assert!(matches!(starved, Err(MomError::NotConverged(residuals)) if residuals.len() == 2));
+    }
+
+    #[test]
+    fn test_exact_kernel_matches_reduced() {
+        let f: f64 = 14.2e6;
+        let geometry: WireGeometry = half_wave_dipole(f, 31).into();
+        let reduced = ThinWireSolver::new(Kernel::Reduced).solve(f, &geometry, &center_source(15).into()).unwrap();
+        let exact = ThinWireSolver::new(Kernel::Exact).solve(f, &geometry, &center_source(15).into()).unwrap();
+        dbg!(reduced.input_impedance().unwrap(), exact.input_impedance().unwrap());
+
+        assert!((reduced.input_impedance().unwrap() - exact.input_impedance().unwrap()).norm() < 2.0);
+    }
+
+    #[test]
+    fn test_dipole_from_joined_wires() {
+        // NEC style: a one segment feed wire between two arms
+        let f: f64 = 14.2e6;
+        let single: StraightWire = half_wave_dipole(f, 51);
+        let step: Vector3<f64> = single.direction() * single.segment_length();
+        let feed_start: Vector3<f64> = single.start + step * 25.0;
+        let feed_end: Vector3<f64> = feed_start + step;
+
+        let mut geometry: WireGeometry = WireGeometry::new();
+        let feed: usize = geometry.add_wire(StraightWire::new(feed_start, feed_end, single.radius, 1));
+        geometry.add_wire(StraightWire::new(single.start, feed_start, single.radius, 25));
+        geometry.add_wire(StraightWire::new(feed_end, single.end, single.radius, 25));
+        assert_eq!(geometry.junctions().len(), 2);
+        assert_eq!(geometry.unknowns(), 53);
+
+        let source: VoltageSource = VoltageSource {
+            wire: feed,
+            segment: 0,
+            voltage: Complex::new(1.0, 0.0),
+        };
+        let solver: ThinWireSolver = ThinWireSolver::default();
+        let joined = solver.solve(f, &geometry, &source.into()).unwrap().input_impedance().unwrap();
+        let reference = solver.solve(f, &single.into(), &center_source(25).into()).unwrap().input_impedance().unwrap();
+        dbg!(joined, reference);
+
+        assert!((joined - reference).norm() < 2.0);
+    }
+
+    #[test]
+    fn test_inverted_v_lowers_resistance() {
+        let f: f64 = 7.1e6;
+        let dipole: StraightWire = half_wave_dipole(f, 21);
+        let half: f64 = dipole.length() / 2.0;
+        let droop: f64 = half * f64::sin(f64::to_radians(45.0));
+        let apex: Vector3<f64> = Vector3::new(0.0, 0.0, 0.0);
+        let feed_half: Vector3<f64> = Vector3::new(dipole.segment_length() / 2.0, 0.0, 0.0);
+
+        let mut geometry: WireGeometry = WireGeometry::new();
+        let feed: usize = geometry.add_wire(StraightWire::new(apex - feed_half, apex + feed_half, dipole.radius, 1));
+        geometry.add_wire(StraightWire::new(Vector3::new(-droop, 0.0, -droop), apex - feed_half, dipole.radius, 10));
+        geometry.add_wire(StraightWire::new(apex + feed_half, Vector3::new(droop, 0.0, -droop), dipole.radius, 10));
+
+        let source: VoltageSource = VoltageSource {
+            wire: feed,
+            segment: 0,
+            voltage: Complex::new(1.0, 0.0),
+        };
+        let solver: ThinWireSolver = ThinWireSolver::default();
+        let inverted_v = solver.solve(f, &geometry, &source.into()).unwrap().input_impedance().unwrap();
+        let straight = solver.solve(f, &dipole.into(), &center_source(10).into()).unwrap().input_impedance().unwrap();
+        dbg!(inverted_v, straight);
+
+        // drooping the arms lowers the radiation resistance
+        assert!(inverted_v.re < straight.re);
+        assert!(inverted_v.re > 20.0);
+    }
+
+    #[test]
+    fn test_toeplitz_matches_direct_fill() {
+        // a bent pair of wires, every entry the tables give must match the direct integral
+        let k: f64 = 0.3;
+        let mut geometry: WireGeometry = WireGeometry::new();
+        geometry.add_wire(StraightWire::new(Vector3::zeros(), Vector3::new(0.0, 0.0, 5.0), 1e-3, 9));
+        geometry.add_wire(StraightWire::new(Vector3::new(0.0, 0.0, 5.0), Vector3::new(4.0, 0.0, 7.0), 1e-3, 7));
+        let mesh: Mesh = Mesh::new(&geometry);
+
+        let halves: Vec<HalfSegment> = mesh.bases.iter().flat_map(|b| mesh.halves(b)).collect();
+        let half_psi = |obs: usize, src: usize| {
+            let h = &halves[src];
+            psi(h.start, h.end, h.radius, halves[obs].center(), k, Kernel::Reduced)
+        };
+        let places: Vec<Option<(usize, usize)>> = mesh
+            .bases
+            .iter()
+            .flat_map(|b| {
+                let minus = &mesh.segments[b.minus];
+                let plus = &mesh.segments[b.plus];
+                [minus.position.map(|p| (minus.wire, 2 * p)), plus.position.map(|p| (plus.wire, 2 * p - 1))]
+            })
+            .collect();
+        let toeplitz: Toeplitz = Toeplitz::new(places, half_psi);
+
+        let mut covered: usize = 0;
+        for obs in 0..halves.len() {
+            for src in 0..halves.len() {
+                if let Some(value) = toeplitz.get(obs, src) {
+                    covered += 1;
+                    assert!((value - half_psi(obs, src)).norm() < 1e-9 * value.norm());
+                }
+            }
+        }
+        assert_eq!(covered, 16 * 16 + 12 * 12);
+    }
+}
@@ -0,0 +1,260 @@
+use nalgebra::{DMatrix, DVector};
+use num_complex::Complex;
+
+use crate::{
+    consts::SPEED_OF_LIGHT,
+    mom::{
+        excitation::{excitation_vector, VoltageSource},
+        geometry::{Mesh, WireGeometry},
+        load::load_impedances,
+        nec::FrequencySweep,
+        solver::ThinWireSolver,
+        MomError,
+    },
+    util::{hz_to_angular_freq, solve_square_matrix, swr},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SweepSettings {
+    /// Frequencies the impedance matrix is actually filled at, spread evenly over the sweep. At
+    /// least 3 are used, and a sweep with no more points than anchors is solved in full
+    pub anchors: usize,
+    /// Sweep points solved again with a full fill to check the interpolation
+    pub checks: usize,
+}
+
+impl Default for SweepSettings {
+    fn default() -> Self {
+        Self {
+            anchors: 5,
+            checks: 3,
+        }
+    }
+}
+
+/// Feedpoint impedance over a frequency sweep
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImpedanceSweep {
+    /// Frequency in Hz and impedance in ohms at every point of the sweep
+    pub impedances: Vec<(f64, Complex<f64>)>,
+    /// Frequency in Hz and relative error of the interpolated impedance at every check
+    pub checks: Vec<(f64, f64)>,
+}
+
+impl ImpedanceSweep {
+    /// Fills the impedance matrix at a few anchor frequencies only and interpolates every element
+    /// in between with a quadratic through the three nearest anchors. An element varies as
+    /// e^(-jkR) / omega over the band, so the phase delay between the two basis functions is
+    /// taken out and omega multiplied in before interpolating. Loads are added exactly at every
+    /// frequency, and every point is solved directly whatever the method of `solver`. A sweep
+    /// that never leaves one frequency and a point at 0 Hz get a full fill instead
+    pub fn run(
+        solver: &ThinWireSolver,
+        sweep: &FrequencySweep,
+        geometry: &WireGeometry,
+        source: VoltageSource,
+        settings: &SweepSettings,
+    ) -> Result<Self, MomError> {
+        geometry.validate()?;
+        let frequencies: Vec<f64> = sweep.frequencies();
+        let mesh: Mesh = Mesh::new(geometry);
+        let anchors: usize = settings.anchors.max(3);
+        let lowest: f64 = frequencies.iter().copied().filter(|&f| f > 0.0).fold(f64::INFINITY, f64::min);
+        let highest: f64 = frequencies.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        // anchors all on the same frequency would leave nothing to interpolate between, and none
+        // can sit at DC
+        if frequencies.len() <= anchors || highest - lowest <= f64::EPSILON * highest.abs() {
+            let impedances: Vec<(f64, Complex<f64>)> = frequencies
+                .iter()
+                .map(|&f| Ok((f, full_impedance(solver, f, geometry, &mesh, source)?)))
+                .collect::<Result<_, MomError>>()?;
+            return Ok(Self {
+                impedances,
+                checks: Vec::new(),
+            });
+        }
+
+        let anchor_frequencies: Vec<f64> =
+            (0..anchors).map(|i| lowest + (highest - lowest) * i as f64 / (anchors - 1) as f64).collect();
+        // distance between the nodes of every pair of basis functions, the delay taken out
+        let n: usize = mesh.bases.len();
+        let distances: DMatrix<f64> = DMatrix::from_fn(n, n, |m, n| (mesh.bases[m].node - mesh.bases[n].node).norm());
+        let delay = |f: f64| distances.map(|r| Complex::new(0.0, hz_to_angular_freq(f) * r / SPEED_OF_LIGHT).exp());
+        let smoothed: Vec<DMatrix<Complex<f64>>> = anchor_frequencies
+            .iter()
+            .map(|&f| solver.fill(f, &mesh).component_mul(&delay(f)) * Complex::new(hz_to_angular_freq(f), 0.0))
+            .collect();
+
+        let impedances: Vec<(f64, Complex<f64>)> = frequencies
+            .iter()
+            .map(|&f| {
+                // the interpolated elements carry a factor omega that cannot be divided out at DC
+                if f == 0.0 {
+                    return Ok((f, full_impedance(solver, f, geometry, &mesh, source)?));
+                }
+                // quadratic through the closest anchor and its two neighbours
+                let closest: usize = (0..anchors)
+                    .min_by(|&a, &b| (anchor_frequencies[a] - f).abs().total_cmp(&(anchor_frequencies[b] - f).abs()))
+                    .unwrap_or(0);
+                let first: usize = closest.saturating_sub(1).min(anchors - 3);
+                let nodes: &[f64] = &anchor_frequencies[first..first + 3];
+                let mut interpolated: DMatrix<Complex<f64>> = DMatrix::zeros(n, n);
+                for (i, &node) in nodes.iter().enumerate() {
+                    let weight: f64 = nodes
+                        .iter()
+                        .enumerate()
+                        .filter(|&(j, _)| j != i)
+                        .map(|(_, &other)| (f - other) / (node - other))
+                        .product();
+                    interpolated += &smoothed[first + i] * Complex::new(weight, 0.0);
+                }
+                let z_matrix: DMatrix<Complex<f64>> =
+                    interpolated.component_div(&delay(f)) / Complex::new(hz_to_angular_freq(f), 0.0);
+                Ok((f, impedance(z_matrix, f, geometry, &mesh, source)?))
+            })
+            .collect::<Result<_, MomError>>()?;
+
+        // halfway between two anchors is where the interpolation is worst
+        let checks: Vec<(f64, f64)> = (0..settings.checks)
+            .map(|i| {
+                let gap: usize = i * (anchors - 1) / settings.checks;
+                let target: f64 = (anchor_frequencies[gap] + anchor_frequencies[gap + 1]) / 2.0;
+                let (f, interpolated) = impedances
+                    .iter()
+                    .copied()
+                    .min_by(|a, b| (a.0 - target).abs().total_cmp(&(b.0 - target).abs()))
+                    .unwrap_or(impedances[0]);
+                let full: Complex<f64> = full_impedance(solver, f, geometry, &mesh, source)?;
+                Ok((f, (interpolated - full).norm() / full.norm()))
+            })
+            .collect::<Result<_, MomError>>()?;
+        Ok(Self { impedances, checks })
+    }
+
+    /// SWR against `z_source` at every point of the sweep
+    pub fn swr(&self, z_source: Complex<f64>) -> Vec<(f64, f64)> {
+        self.impedances.iter().map(|&(f, z)| (f, swr(z, z_source))).collect()
+    }
+
+    /// Largest relative error found by the checks, zero when nothing was interpolated
+    pub fn max_error(&self) -> f64 {
+        self.checks.iter().map(|c| c.1).fold(0.0, f64::max)
+    }
+}
+
+fn full_impedance(
+    solver: &ThinWireSolver,
+    frequency: f64,
+    geometry: &WireGeometry,
+    mesh: &Mesh,
+    source: VoltageSource,
+) -> Result<Complex<f64>, MomError> {
+    impedance(solver.fill(frequency, mesh), frequency, geometry, mesh, source)
+}
+
+/// Loads the bare impedance matrix and solves for the impedance seen by `source`
+fn impedance(
+    mut z_matrix: DMatrix<Complex<f64>>,
+    frequency: f64,
+    geometry: &WireGeometry,
+    mesh: &Mesh,
+    source: VoltageSource,
+) -> Result<Complex<f64>, MomError> {
+    let loads: DVector<Complex<f64>> = load_impedances(geometry, mesh, hz_to_angular_freq(frequency))?;
+    z_matrix.set_diagonal(&(z_matrix.diagonal() + &loads));
+    let k: f64 = hz_to_angular_freq(frequency) / SPEED_OF_LIGHT;
+    let v_vector: DVector<Complex<f64>> = excitation_vector(geometry, mesh, k, &source.into())?;
+    let currents: DVector<Complex<f64>> = solve_square_matrix(z_matrix, v_vector)?;
+    let idx: usize = geometry
+        .segment_index(source.wire, source.segment)
+        .ok_or(MomError::InvalidSource(source.wire, source.segment))?;
+    Ok(source.voltage / currents[idx])
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use num_complex::Complex;
+
+    use crate::{
+        consts::SPEED_OF_LIGHT,
+        mom::{
+            excitation::VoltageSource,
+            geometry::{StraightWire, WireGeometry},
+            load::{Load, LoadKind},
+            nec::FrequencySweep,
+            solver::ThinWireSolver,
+        },
+    };
+
+    use super::{ImpedanceSweep, SweepSettings};
+
+    const FEED: VoltageSource = VoltageSource {
+        wire: 0,
+        segment: 15,
+        voltage: Complex::new(1.0, 0.0),
+    };
+
+    fn dipole(f: f64) -> WireGeometry {
+        let half: f64 = SPEED_OF_LIGHT / f / 4.0;
+        StraightWire::new(Vector3::new(0.0, 0.0, -half), Vector3::new(0.0, 0.0, half), 1e-3, 31).into()
+    }
+
+    #[test]
+    fn test_interpolated_sweep_matches_full_solves() {
+        let mut geometry: WireGeometry = dipole(14.2e6);
+        geometry.add_load(Load::wire(0, LoadKind::Conductivity(5.8e7)));
+        // 13 to 15.5 MHz in 101 steps
+        let sweep: FrequencySweep = FrequencySweep {
+            start: 13e6,
+            steps: 101,
+            step: 25e3,
+            multiplicative: false,
+        };
+        let solver: ThinWireSolver = ThinWireSolver::default();
+        let result: ImpedanceSweep =
+            ImpedanceSweep::run(&solver, &sweep, &geometry, FEED, &SweepSettings::default()).unwrap();
+        dbg!(&result.checks);
+
+        assert_eq!(result.impedances.len(), 101);
+        assert_eq!(result.checks.len(), 3);
+        assert!(result.max_error() < 1e-4);
+
+        // the reactance crosses zero inside the band, where the SWR is lowest
+        let swr: Vec<(f64, f64)> = result.swr(Complex::new(75.0, 0.0));
+        let best: (f64, f64) = swr.iter().copied().min_by(|a, b| a.1.total_cmp(&b.1)).unwrap();
+        assert!(best.0 > 13.2e6 && best.0 < 14.2e6);
+        assert!(best.1 < 1.3);
+
+        let short: ImpedanceSweep = ImpedanceSweep::run(
+            &solver,
+            &FrequencySweep::single(14.2e6),
+            &geometry,
+            FEED,
+            &SweepSettings::default(),
+        )
+        .unwrap();
+        let full: Complex<f64> = solver.solve(14.2e6, &geometry, &FEED.into()).unwrap().input_impedance().unwrap();
+        assert!((short.impedances[0].1 - full).norm() < 1e-9 * full.norm());
+        assert!(short.checks.is_empty());
+    }
+
+    #[test]
+    fn test_sweep_on_one_frequency() {
+        let geometry: WireGeometry = dipole(14.2e6);
+        let sweep: FrequencySweep = FrequencySweep {
+            start: 14.2e6,
+            steps: 11,
+            step: 0.0,
+            multiplicative: false,
+        };
+        let solver: ThinWireSolver = ThinWireSolver::default();
+        let result: ImpedanceSweep =
+            ImpedanceSweep::run(&solver, &sweep, &geometry, FEED, &SweepSettings::default()).unwrap();
+        let full: Complex<f64> = solver.solve(14.2e6, &geometry, &FEED.into()).unwrap().input_impedance().unwrap();
+
+        assert_eq!(result.impedances.len(), 11);
+        assert!(result.checks.is_empty());
+        assert!(result.impedances.iter().all(|&(_, z)| (z - full).norm() < 1e-9 * full.norm()));
+    }
+}
@@ -0,0 +1,181 @@
+use std::f64::consts::PI;
+
+use nalgebra::DMatrix;
+use num_complex::Complex;
+
+use crate::consts::FREE_SPACE_IMPEDANCE;
+
+/// Far-field radiation pattern sampled on a theta/phi grid
+#[derive(Debug, Clone)]
+pub struct RadiationPattern {
+    /// Frequency in Hz
+    pub frequency: f64,
+    /// Angles from the +z axis in radians
+    pub thetas: Vec<f64>,
+    /// Angles from the +x axis towards +y in radians
+    pub phis: Vec<f64>,
+    /// r * E_theta in volts, one row per theta and one column per phi
+    pub e_theta: DMatrix<Complex<f64>>,
+    /// r * E_phi in volts, one row per theta and one column per phi
+    pub e_phi: DMatrix<Complex<f64>>,
+    /// Total radiated power in watts
+    pub radiated_power: f64,
+    /// Power delivered to the antenna terminals in watts
+    pub input_power: f64,
+}
+
+/// Headline numbers of a pattern
+#[derive(Debug, Clone, Copy)]
+pub struct PatternSummary {
+    /// Peak gain in dBi
+    pub max_gain: f64,
+    /// Direction of the peak in radians
+    pub max_theta: f64,
+    pub max_phi: f64,
+    /// Peak gain over the gain in the opposite azimuth at the same elevation, in dB
+    pub front_to_back: f64,
+    /// Half-power beamwidth in radians along phi through the peak, `None` if it never drops 3 dB
+    pub azimuth_beamwidth: Option<f64>,
+    /// Half-power beamwidth in radians along theta through the peak
+    pub elevation_beamwidth: Option<f64>,
+}
+
+/// Evenly spaced angles, `count` of them from `start` in steps of `step`, all in radians
+pub fn angles(start: f64, step: f64, count: usize) -> Vec<f64> {
+    (0..count).map(|i| start + step * i as f64).collect()
+}
+
+impl RadiationPattern {
+    /// Radiated power per unit solid angle in watts/steradian
+    pub fn intensity(&self, theta: usize, phi: usize) -> f64 {
+        (self.e_theta[(theta, phi)].norm_sqr() + self.e_phi[(theta, phi)].norm_sqr()) / (2.0 * FREE_SPACE_IMPEDANCE)
+    }
+    /// Directivity as a power ratio
+    pub fn directivity(&self, theta: usize, phi: usize) -> f64 {
+        4.0 * PI * self.intensity(theta, phi) / self.radiated_power
+    }
+    /// Gain as a power ratio, includes every loss between the terminals and free space
+    pub fn gain(&self, theta: usize, phi: usize) -> f64 {
+        4.0 * PI * self.intensity(theta, phi) / self.input_power
+    }
+    /// Directivity in dBi
+    pub fn directivity_dbi(&self, theta: usize, phi: usize) -> f64 {
+        10.0 * self.directivity(theta, phi).log10()
+    }
+    /// Gain in dBi
+    pub fn gain_dbi(&self, theta: usize, phi: usize) -> f64 {
+        10.0 * self.gain(theta, phi).log10()
+    }
+    /// Radiated over input power
+    pub fn efficiency(&self) -> f64 {
+        self.radiated_power / self.input_power
+    }
+    /// Gain in dBi over the whole grid, rows follow `thetas`
+    pub fn gain_table(&self) -> DMatrix<f64> {
+        DMatrix::from_fn(self.thetas.len(), self.phis.len(), |i, j| self.gain_dbi(i, j))
+    }
+
+    /// Grid indices of the highest intensity, none on an empty grid
+    pub fn peak(&self) -> Option<(usize, usize)> {
+        if self.thetas.is_empty() || self.phis.is_empty() {
+            return None;
+        }
+        let mut best: (usize, usize) = (0, 0);
+        for i in 0..self.thetas.len() {
+            for j in 0..self.phis.len() {
+                if self.intensity(i, j) > self.intensity(best.0, best.1) {
+                    best = (i, j);
+                }
+            }
+        }
+        Some(best)
+    }
+
+    /// Headline numbers, none on an empty grid
+    pub fn summary(&self) -> Option<PatternSummary> {
+        let (i, j) = self.peak()?;
+        let back: usize = nearest_angle(&self.phis, self.phis[j] + PI);
+        let azimuth: Vec<f64> = (0..self.phis.len()).map(|jj| self.gain_dbi(i, jj)).collect();
+        let elevation: Vec<f64> = (0..self.thetas.len()).map(|ii| self.gain_dbi(ii, j)).collect();
+        Some(PatternSummary {
+            max_gain: self.gain_dbi(i, j),
+            max_theta: self.thetas[i],
+            max_phi: self.phis[j],
+            front_to_back: self.gain_dbi(i, j) - self.gain_dbi(i, back),
+            azimuth_beamwidth: half_power_width(&self.phis, &azimuth, j, covers_circle(&self.phis)),
+            elevation_beamwidth: half_power_width(&self.thetas, &elevation, i, false),
+        })
+    }
+}
+
+/// Whether evenly spaced `angles` go all the way around
+fn covers_circle(angles: &[f64]) -> bool {
+    if angles.len() < 2 {
+        return false;
+    }
+    let step: f64 = angles[1] - angles[0];
+    (angles[angles.len() - 1] + step - angles[0] - 2.0 * PI).abs() < 1e-6
+}
+
+fn nearest_angle(angles: &[f64], target: f64) -> usize {
+    let distance = |a: f64| {
+        let d: f64 = (a - target).rem_euclid(2.0 * PI);
+        d.min(2.0 * PI - d)
+    };
+    (0..angles.len())
+        .min_by(|&a, &b| distance(angles[a]).total_cmp(&distance(angles[b])))
+        .unwrap_or(0)
+}
+
+/// Walks both ways from `peak` until the gain falls 3 dB and interpolates the crossing
+fn half_power_width(angles: &[f64], gain: &[f64], peak: usize, wrap: bool) -> Option<f64> {
+    let n: usize = angles.len();
+    let level: f64 = gain[peak] - 3.0;
+    let step: f64 = if n > 1 { angles[1] - angles[0] } else { return None };
+
+    let mut edges: [f64; 2] = [0.0; 2];
+    for (edge, direction) in edges.iter_mut().zip([-1isize, 1isize]) {
+        let mut idx: isize = peak as isize;
+        let mut found: bool = false;
+        for _ in 1..n {
+            let next: isize = idx + direction;
+            if !wrap && (next < 0 || next >= n as isize) {
+                break;
+            }
+            let (a, b) = (gain[idx.rem_euclid(n as isize) as usize], gain[next.rem_euclid(n as isize) as usize]);
+            if b < level {
+                let fraction: f64 = (a - level) / (a - b);
+                *edge = (idx - peak as isize) as f64 * step + direction as f64 * fraction * step;
+                found = true;
+                break;
+            }
+            idx = next;
+        }
+        if !found {
+            return None;
+        }
+    }
+    Some(edges[1] - edges[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+
+    use super::RadiationPattern;
+
+    #[test]
+    fn test_empty_pattern_has_no_summary() {
+        let pattern: RadiationPattern = RadiationPattern {
+            frequency: 14e6,
+            thetas: Vec::new(),
+            phis: vec![0.0],
+            e_theta: DMatrix::zeros(0, 1),
+            e_phi: DMatrix::zeros(0, 1),
+            radiated_power: 1.0,
+            input_power: 1.0,
+        };
+        assert!(pattern.peak().is_none());
+        assert!(pattern.summary().is_none());
+    }
+}
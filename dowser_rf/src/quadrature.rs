@@ -0,0 +1,173 @@
+use std::{
+    f64::consts::PI,
+    iter::Sum,
+    ops::{Add, Mul, Sub},
+};
+
+use num_complex::Complex;
+
+/// Subdivision limit of adaptive integration, deep enough for a 1 in 10^9 wide feature
+const MAX_DEPTH: usize = 30;
+
+/// Values that can be integrated, real or complex
+pub trait Integrand: Copy + Sum + Add<Output = Self> + Sub<Output = Self> + Mul<f64, Output = Self> {
+    fn magnitude(&self) -> f64;
+}
+
+impl Integrand for f64 {
+    fn magnitude(&self) -> f64 {
+        self.abs()
+    }
+}
+
+impl Integrand for Complex<f64> {
+    fn magnitude(&self) -> f64 {
+        self.norm()
+    }
+}
+
+/// Gauss-Legendre rule, exact for polynomials up to degree 2 * order - 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct GaussLegendre {
+    /// Abscissas and weights on [-1, 1]
+    pub nodes: Vec<(f64, f64)>,
+}
+
+impl GaussLegendre {
+    /// Finds the roots of the Legendre polynomial of degree `order` by Newton's method
+    pub fn new(order: usize) -> Self {
+        let mut nodes: Vec<(f64, f64)> = vec![(0.0, 0.0); order];
+        for i in 0..order.div_ceil(2) {
+            let mut x: f64 = f64::cos(PI * (i as f64 + 0.75) / (order as f64 + 0.5));
+            for _ in 0..100 {
+                let (p, dp) = legendre(order, x);
+                let step: f64 = p / dp;
+                x -= step;
+                if step.abs() < 1e-16 {
+                    break;
+                }
+            }
+            let (_, dp) = legendre(order, x);
+            let weight: f64 = 2.0 / ((1.0 - x * x) * dp * dp);
+            nodes[i] = (-x, weight);
+            nodes[order - 1 - i] = (x, weight);
+        }
+        Self { nodes }
+    }
+
+    pub fn order(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Integral of `f` from `a` to `b`
+    pub fn integrate<T: Integrand>(&self, a: f64, b: f64, f: impl Fn(f64) -> T) -> T {
+        let half: f64 = (b - a) / 2.0;
+        let mid: f64 = (a + b) / 2.0;
+        self.nodes.iter().map(|&(x, w)| f(mid + half * x) * (w * half)).sum()
+    }
+
+    /// Integral of `f` from `a` to `b`, halving the interval wherever the rule disagrees with
+    /// itself on the two halves until the estimate is within `tolerance` relative to the result
+    pub fn integrate_adaptive<T: Integrand>(&self, a: f64, b: f64, tolerance: f64, f: impl Fn(f64) -> T) -> T {
+        let whole: T = self.integrate(a, b, &f);
+        self.subdivide(a, b, whole, tolerance * whole.magnitude(), MAX_DEPTH, &f)
+    }
+
+    fn subdivide<T: Integrand>(&self, a: f64, b: f64, whole: T, tolerance: f64, depth: usize, f: &impl Fn(f64) -> T) -> T {
+        let mid: f64 = (a + b) / 2.0;
+        let left: T = self.integrate(a, mid, f);
+        let right: T = self.integrate(mid, b, f);
+        let halves: T = left + right;
+        if depth == 0 || (halves - whole).magnitude() <= tolerance {
+            return halves;
+        }
+        self.subdivide(a, mid, left, tolerance / 2.0, depth - 1, f)
+            + self.subdivide(mid, b, right, tolerance / 2.0, depth - 1, f)
+    }
+
+    /// Integral of h(R) / R along the line from 0 to `length`, where R = sqrt((z - z_0)^2 +
+    /// rho^2) and `h` is smooth. The 1/R part is integrated analytically with h(0) and only the
+    /// remainder (h(R) - h(0)) / R, which stays finite as R goes to 0, is left to the rule. The
+    /// remainder still has a kink at `z_0`, so the line is split there
+    pub fn integrate_singular<T: Integrand>(&self, length: f64, z_0: f64, rho: f64, h: impl Fn(f64) -> T) -> T {
+        let h_0: T = h(0.0);
+        let remainder = |z: f64| {
+            let r: f64 = ((z - z_0).powi(2) + rho * rho).sqrt();
+            (h(r) - h_0) * (1.0 / r)
+        };
+        let smooth: T = match z_0 > 0.0 && z_0 < length {
+            true => self.integrate(0.0, z_0, remainder) + self.integrate(z_0, length, remainder),
+            false => self.integrate(0.0, length, remainder),
+        };
+        h_0 * line_kernel_integral(length, z_0, rho) + smooth
+    }
+}
+
+/// Integral of 1 / sqrt((z - z_0)^2 + rho^2) over z from 0 to `length`
+pub fn line_kernel_integral(length: f64, z_0: f64, rho: f64) -> f64 {
+    ((length - z_0) / rho).asinh() + (z_0 / rho).asinh()
+}
+
+/// Legendre polynomial of degree `n` and its derivative at `x`
+fn legendre(n: usize, x: f64) -> (f64, f64) {
+    let (mut previous, mut p): (f64, f64) = (1.0, x);
+    if n == 0 {
+        return (1.0, 0.0);
+    }
+    for j in 2..=n {
+        let next: f64 = ((2 * j - 1) as f64 * x * p - (j - 1) as f64 * previous) / j as f64;
+        previous = p;
+        p = next;
+    }
+    (p, n as f64 * (x * p - previous) / (x * x - 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use num_complex::Complex;
+
+    use super::{line_kernel_integral, GaussLegendre};
+
+    #[test]
+    fn test_gauss_legendre_nodes() {
+        let rule: GaussLegendre = GaussLegendre::new(8);
+        assert!((rule.nodes[0].0 + 0.9602898564975363).abs() < 1e-15);
+        assert!((rule.nodes[0].1 - 0.1012285362903763).abs() < 1e-15);
+        assert!((rule.nodes.iter().map(|n| n.1).sum::<f64>() - 2.0).abs() < 1e-14);
+
+        // exact up to degree 2n - 1
+        for order in [1, 2, 5, 16] {
+            let rule: GaussLegendre = GaussLegendre::new(order);
+            let degree: i32 = 2 * order as i32 - 1;
+            let exact: f64 = (2f64.powi(degree + 1) - 1.0) / (degree + 1) as f64;
+            assert!((rule.integrate(1.0, 2.0, |x| x.powi(degree)) - exact).abs() < 1e-12 * exact);
+        }
+    }
+
+    #[test]
+    fn test_adaptive_resolves_peak() {
+        // a narrow Lorentzian, far too sharp for a single 8 point rule
+        let width: f64 = 1e-4;
+        let exact: f64 = 2.0 * (1.0 / width).atan() / width;
+        let rule: GaussLegendre = GaussLegendre::new(8);
+        let fixed: f64 = rule.integrate(-1.0, 1.0, |x| 1.0 / (x * x + width * width));
+        let adaptive: f64 = rule.integrate_adaptive(-1.0, 1.0, 1e-10, |x| 1.0 / (x * x + width * width));
+        assert!((fixed / exact - 1.0).abs() > 0.1);
+        assert!((adaptive / exact - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_singular_extraction() {
+        // e^(-jkR) / R on a thin segment, observed from its own middle
+        let (k, length, radius): (f64, f64, f64) = (2.0, 0.5, 1e-4);
+        let h = |r: f64| Complex::new(0.0, -k * r).exp();
+        let rule: GaussLegendre = GaussLegendre::new(8);
+        let extracted: Complex<f64> = rule.integrate_singular(length, length / 2.0, radius, h);
+        let brute: Complex<f64> = GaussLegendre::new(32).integrate_adaptive(0.0, length, 1e-12, |z| {
+            let r: f64 = ((z - length / 2.0).powi(2) + radius * radius).sqrt();
+            h(r) / r
+        });
+        assert!((extracted - brute).norm() < 1e-8 * brute.norm());
+        assert!((line_kernel_integral(length, 0.0, radius) - (length / radius).asinh()).abs() < 1e-12);
+    }
+}
@@ -1,6 +1,6 @@
-use std::{f64::consts::PI, vec};
+use std::{f64::consts::PI, fmt};
 
-use nalgebra::{stack, DMatrix, DVector, Dyn, Matrix, OMatrix, VecStorage};
+use nalgebra::{DMatrix, DVector};
 use num_complex::{Complex, ComplexFloat};
 
 pub fn coth(x: f64) -> f64 {
@@ -42,61 +42,456 @@ pub fn get_rf_resistance(skin_depth: f64, diameter: f64, resistivity: f64) -> f6
     (resistivity) / (PI * skin_depth * diameter)
 }
 
-pub fn gaussian_elimination(mut augmented: DMatrix<f64>) -> DMatrix<f64> {
-    let mut h: usize = 0; // pivot row
-    let mut k: usize = 0; // pivot column
-    let mut swap_pos: Vec<(usize, usize)> = Vec::new();
-
-    let epsilon: f64 = 1e-15;
-    while h < augmented.nrows() && k < augmented.ncols() {
-        let mut i_max: usize = 0;
-        for i in 0..augmented.nrows() {
-            if augmented[(i_max, k)] < augmented[(i, k)] {
-                i_max = i;
-            }
-        }
-        if augmented[(i_max, k)] == 0.0 || augmented[(i_max, k)].abs() <= epsilon {
-            k += 1;
-        } else {
-            swap_pos.push((h, i_max));
-            augmented.swap_rows(h, i_max);
-            for i in (h + 1)..augmented.nrows() {
-                let f: f64 = augmented[(i, k)] / augmented[(h, k)];
-                augmented[(i, k)] = 0.0;
-                for j in (k + 1)..augmented.ncols() {
-                    augmented[(i , j)] = augmented[(i, j)] - augmented[(h, j)] * f;
+/// Scaled complementary error function e^(z^2) erfc(z) of a complex argument
+pub fn erfcx(z: Complex<f64>) -> Complex<f64> {
+    if z.norm() < 3.0 {
+        // Maclaurin series of erf, converges everywhere but loses digits to cancellation far out
+        let mut term: Complex<f64> = z;
+        let mut erf: Complex<f64> = z;
+        for n in 1..60 {
+            term *= -z * z / n as f64;
+            erf += term / (2 * n + 1) as f64;
+        }
+        (z * z).exp() * (1.0 - erf * 2.0 / PI.sqrt())
+    } else if z.re >= 0.0 {
+        // Laplace continued fraction, evaluated from the tail
+        let mut t: Complex<f64> = z;
+        for n in (1..80).rev() {
+            t = z + (n as f64 / 2.0) / t;
+        }
+        1.0 / (PI.sqrt() * t)
+    } else {
+        2.0 * (z * z).exp() - erfcx(-z)
+    }
+}
+
+/// Failure of the dense linear solvers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolveError {
+    /// The matrix has this many rows and columns, but must be square
+    NotSquare(usize, usize),
+    /// The right hand side has `found` rows where `expected` are needed
+    DimensionMismatch { expected: usize, found: usize },
+    /// No pivot above the rounding level was left in this column
+    Singular(usize),
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::NotSquare(rows, cols) => write!(f, "matrix is {rows}x{cols}, not square"),
+            SolveError::DimensionMismatch { expected, found } => {
+                write!(f, "right hand side has {found} rows, expected {expected}")
+            }
+            SolveError::Singular(col) => write!(f, "matrix is singular at column {col}"),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+/// LU factorization with partial pivoting, P A = L U. Factor once and solve for as many right
+/// hand sides as needed, works over both `f64` and `Complex<f64>`
+#[derive(Debug, Clone)]
+pub struct LuFactorization<T> {
+    /// L below the diagonal (its unit diagonal is implied) and U on and above it
+    lu: DMatrix<T>,
+    /// Row of the original matrix that ended up in every row of `lu`
+    permutation: Vec<usize>,
+    /// 1-norm of the original matrix
+    norm: f64,
+}
+
+impl<T> LuFactorization<T>
+where
+    T: nalgebra::ComplexField<RealField = f64> + Copy,
+{
+    pub fn new(mut matrix: DMatrix<T>) -> Result<Self, SolveError> {
+        let n: usize = matrix.nrows();
+        if matrix.ncols() != n {
+            return Err(SolveError::NotSquare(n, matrix.ncols()));
+        }
+        let norm: f64 = matrix
+            .column_iter()
+            .map(|c| c.iter().map(|v| v.modulus()).sum::<f64>())
+            .fold(0.0, f64::max);
+        // pivots this small relative to the matrix are rounding noise
+        let largest: f64 = matrix.iter().map(|v| v.modulus()).fold(0.0, f64::max);
+        let tolerance: f64 = n as f64 * f64::EPSILON * largest;
+
+        let mut permutation: Vec<usize> = (0..n).collect();
+        for k in 0..n {
+            // the largest magnitude in the column keeps every multiplier at or below one
+            let pivot: usize = (k..n)
+                .max_by(|&a, &b| matrix[(a, k)].modulus().total_cmp(&matrix[(b, k)].modulus()))
+                .unwrap_or(k);
+            if matrix[(pivot, k)].modulus() <= tolerance {
+                return Err(SolveError::Singular(k));
+            }
+            if pivot != k {
+                matrix.swap_rows(pivot, k);
+                permutation.swap(pivot, k);
+            }
+            let diagonal: T = matrix[(k, k)];
+            for i in k + 1..n {
+                matrix[(i, k)] /= diagonal;
+            }
+            for j in k + 1..n {
+                let u: T = matrix[(k, j)];
+                for i in k + 1..n {
+                    let l: T = matrix[(i, k)];
+                    matrix[(i, j)] -= l * u;
                 }
             }
-            h += 1;
-            k += 1;
         }
+        Ok(Self {
+            lu: matrix,
+            permutation,
+            norm,
+        })
+    }
+
+    /// Number of rows and columns of the factored matrix
+    pub fn dimension(&self) -> usize {
+        self.lu.nrows()
+    }
+
+    /// Solves A x = b
+    pub fn solve(&self, b: &DVector<T>) -> Result<DVector<T>, SolveError> {
+        self.check(b.nrows())?;
+        Ok(self.substitute(b))
+    }
+
+    /// Solves A X = B for every column of `b`
+    pub fn solve_columns(&self, b: &DMatrix<T>) -> Result<DMatrix<T>, SolveError> {
+        self.check(b.nrows())?;
+        let mut x: DMatrix<T> = b.clone();
+        for mut column in x.column_iter_mut() {
+            column.copy_from(&self.substitute(&column.clone_owned()));
+        }
+        Ok(x)
+    }
+
+    pub fn determinant(&self) -> T {
+        let mut det: T = self.lu.diagonal().iter().fold(T::one(), |acc, &d| acc * d);
+        // every cycle of the permutation of length l takes l - 1 swaps
+        let mut visited: Vec<bool> = vec![false; self.dimension()];
+        for start in 0..self.dimension() {
+            let mut idx: usize = start;
+            let mut length: usize = 0;
+            while !visited[idx] {
+                visited[idx] = true;
+                idx = self.permutation[idx];
+                length += 1;
+            }
+            if length > 0 && length.is_multiple_of(2) {
+                det = -det;
+            }
+        }
+        det
+    }
+
+    /// Estimate of the 1-norm condition number ||A|| ||A^-1||, using Hager's method for the norm
+    /// of the inverse. Usually within a small factor of the true value and never above it
+    pub fn condition_estimate(&self) -> f64 {
+        let n: usize = self.dimension();
+        if n == 0 {
+            return 0.0;
+        }
+        let mut x: DVector<T> = DVector::from_element(n, T::from_real(1.0 / n as f64));
+        let mut inverse_norm: f64 = 0.0;
+        for _ in 0..5 {
+            let y: DVector<T> = self.substitute(&x);
+            inverse_norm = y.iter().map(|v| v.modulus()).sum();
+            let signs: DVector<T> =
+                y.map(|v| if v.modulus() > 0.0 { v / T::from_real(v.modulus()) } else { T::one() });
+            let z: DVector<T> = self.substitute_adjoint(&signs);
+            let j: usize = z.icamax();
+            // stop once no unit vector can raise the estimate
+            let current: f64 = z.iter().zip(x.iter()).map(|(z, x)| (z.conjugate() * *x).real()).sum();
+            if z[j].modulus() <= current {
+                break;
+            }
+            x = DVector::zeros(n);
+            x[j] = T::one();
+        }
+        self.norm * inverse_norm
     }
 
-    for (h, i_max) in swap_pos {
-        augmented.swap_rows(h, i_max);
+    fn check(&self, rows: usize) -> Result<(), SolveError> {
+        match rows == self.dimension() {
+            true => Ok(()),
+            false => Err(SolveError::DimensionMismatch {
+                expected: self.dimension(),
+                found: rows,
+            }),
+        }
+    }
+
+    /// Forward substitution with L, then back substitution with U
+    pub(crate) fn substitute(&self, b: &DVector<T>) -> DVector<T> {
+        let n: usize = self.dimension();
+        let mut x: DVector<T> = DVector::from_fn(n, |i, _| b[self.permutation[i]]);
+        for j in 0..n {
+            let x_j: T = x[j];
+            for i in j + 1..n {
+                x[i] -= self.lu[(i, j)] * x_j;
+            }
+        }
+        for j in (0..n).rev() {
+            x[j] /= self.lu[(j, j)];
+            let x_j: T = x[j];
+            for i in 0..j {
+                x[i] -= self.lu[(i, j)] * x_j;
+            }
+        }
+        x
+    }
+
+    /// Solves A^H x = b, as A^H = U^H L^H P
+    fn substitute_adjoint(&self, b: &DVector<T>) -> DVector<T> {
+        let n: usize = self.dimension();
+        let mut t: DVector<T> = b.clone();
+        for i in 0..n {
+            let mut sum: T = t[i];
+            for j in 0..i {
+                sum -= self.lu[(j, i)].conjugate() * t[j];
+            }
+            t[i] = sum / self.lu[(i, i)].conjugate();
+        }
+        for i in (0..n).rev() {
+            let mut sum: T = t[i];
+            for j in i + 1..n {
+                sum -= self.lu[(j, i)].conjugate() * t[j];
+            }
+            t[i] = sum;
+        }
+        let mut x: DVector<T> = DVector::zeros(n);
+        for (i, &row) in self.permutation.iter().enumerate() {
+            x[row] = t[i];
+        }
+        x
     }
-    augmented
 }
 
-pub fn solve_square_matrix(a_matrix: DMatrix<f64>, b_matrix: Vec<f64>) -> Vec<f64> {
-    let elim: DMatrix<f64> = gaussian_elimination(stack![a_matrix, DVector::from_vec(b_matrix)]);
-    let mut x: Vec<f64> = vec![0.0; elim.nrows()];
+/// Solves A x = b in one go, see [`LuFactorization`] to reuse the factors
+pub fn solve_square_matrix<T>(a_matrix: DMatrix<T>, b_matrix: DVector<T>) -> Result<DVector<T>, SolveError>
+where
+    T: nalgebra::ComplexField<RealField = f64> + Copy,
+{
+    LuFactorization::new(a_matrix)?.solve(&b_matrix)
+}
+
+/// Settings of the restarted GMRES solver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GmresSettings {
+    /// Converged once the residual norm falls below this fraction of the right hand side norm
+    pub tolerance: f64,
+    /// Iterations allowed over all restarts
+    pub max_iterations: usize,
+    /// Krylov vectors kept before restarting
+    pub restart: usize,
+    /// Size of the diagonal blocks inverted for the preconditioner, 0 disables it
+    pub block_size: usize,
+}
 
-    for i in (0..elim.nrows()).rev() {
-        let mut sum: f64 = 0.0;
-        for j in i..(elim.ncols() - 1) {
-            sum += elim[(i, j)] * x[j];
+impl Default for GmresSettings {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-8,
+            max_iterations: 1000,
+            restart: 50,
+            block_size: 32,
         }
-        x[i] = (elim[(i, elim.ncols() - 1)] - sum) / elim[(i, i)];
     }
-    x
+}
+
+/// Result of an iterative solve
+#[derive(Debug, Clone)]
+pub struct IterativeSolution<T> {
+    pub x: DVector<T>,
+    /// Relative residual norm of the initial guess and after every iteration
+    pub residuals: Vec<f64>,
+    pub converged: bool,
+}
+
+impl<T> IterativeSolution<T> {
+    pub fn iterations(&self) -> usize {
+        self.residuals.len() - 1
+    }
+    /// Relative residual norm of `x`
+    pub fn residual(&self) -> f64 {
+        self.residuals[self.residuals.len() - 1]
+    }
+}
+
+/// Block Jacobi preconditioner, the inverse of the diagonal blocks of a matrix
+#[derive(Debug, Clone)]
+pub struct BlockJacobi<T> {
+    /// First row of every block with its factors
+    blocks: Vec<(usize, LuFactorization<T>)>,
+}
+
+impl<T> BlockJacobi<T>
+where
+    T: nalgebra::ComplexField<RealField = f64> + Copy,
+{
+    pub fn new(matrix: &DMatrix<T>, block_size: usize) -> Result<Self, SolveError> {
+        let n: usize = matrix.nrows();
+        let blocks = (0..n)
+            .step_by(block_size.max(1))
+            .map(|start| {
+                let size: usize = block_size.min(n - start);
+                Ok((start, LuFactorization::new(matrix.view((start, start), (size, size)).clone_owned())?))
+            })
+            .collect::<Result<_, SolveError>>()?;
+        Ok(Self { blocks })
+    }
+
+    /// Multiplies `v` with the preconditioner
+    pub fn apply(&self, v: &DVector<T>) -> DVector<T> {
+        let mut out: DVector<T> = v.clone();
+        for (start, lu) in self.blocks.iter() {
+            let size: usize = lu.dimension();
+            let block: DVector<T> = lu.substitute(&v.rows(*start, size).clone_owned());
+            out.rows_mut(*start, size).copy_from(&block);
+        }
+        out
+    }
+}
+
+/// Solves A x = b with restarted GMRES, right preconditioned with the block diagonal of `a` so
+/// the reported residuals are those of the original system
+pub fn gmres<T>(
+    a_matrix: &DMatrix<T>,
+    b_matrix: &DVector<T>,
+    settings: &GmresSettings,
+) -> Result<IterativeSolution<T>, SolveError>
+where
+    T: nalgebra::ComplexField<RealField = f64> + Copy,
+{
+    let n: usize = a_matrix.nrows();
+    if a_matrix.ncols() != n {
+        return Err(SolveError::NotSquare(n, a_matrix.ncols()));
+    }
+    if b_matrix.nrows() != n {
+        return Err(SolveError::DimensionMismatch {
+            expected: n,
+            found: b_matrix.nrows(),
+        });
+    }
+    let preconditioner: Option<BlockJacobi<T>> = match settings.block_size {
+        0 => None,
+        size => Some(BlockJacobi::new(a_matrix, size)?),
+    };
+    let precondition = |v: &DVector<T>| match &preconditioner {
+        Some(p) => p.apply(v),
+        None => v.clone(),
+    };
+
+    let mut x: DVector<T> = DVector::zeros(n);
+    let b_norm: f64 = b_matrix.norm();
+    if b_norm == 0.0 {
+        return Ok(IterativeSolution {
+            x,
+            residuals: vec![0.0],
+            converged: true,
+        });
+    }
+    let mut residuals: Vec<f64> = vec![1.0];
+    let restart: usize = settings.restart.clamp(1, n.max(1));
+
+    loop {
+        let r: DVector<T> = b_matrix - a_matrix * &x;
+        let beta: f64 = r.norm();
+        if beta / b_norm <= settings.tolerance || residuals.len() > settings.max_iterations {
+            break;
+        }
+
+        // Arnoldi process, the Hessenberg matrix is kept triangular with Givens rotations
+        let mut basis: Vec<DVector<T>> = vec![r.unscale(beta)];
+        let mut hessenberg: DMatrix<T> = DMatrix::zeros(restart + 1, restart);
+        let mut rotations: Vec<(f64, T)> = Vec::with_capacity(restart);
+        let mut g: DVector<T> = DVector::zeros(restart + 1);
+        g[0] = T::from_real(beta);
+        let mut steps: usize = 0;
+        while steps < restart {
+            let j: usize = steps;
+            let mut w: DVector<T> = a_matrix * precondition(&basis[j]);
+            for (i, v) in basis.iter().enumerate() {
+                let h: T = v.dotc(&w);
+                w.axpy(-h, v, T::one());
+                hessenberg[(i, j)] = h;
+            }
+            let h_next: f64 = w.norm();
+            hessenberg[(j + 1, j)] = T::from_real(h_next);
+
+            for (i, &(c, s)) in rotations.iter().enumerate() {
+                let (upper, lower) = (hessenberg[(i, j)], hessenberg[(i + 1, j)]);
+                hessenberg[(i, j)] = upper.scale(c) + s * lower;
+                hessenberg[(i + 1, j)] = -s.conjugate() * upper + lower.scale(c);
+            }
+            let (upper, lower) = (hessenberg[(j, j)], hessenberg[(j + 1, j)]);
+            let radius: f64 = upper.modulus().hypot(lower.modulus());
+            let size: f64 = upper.modulus();
+            let (c, s) = if size == 0.0 {
+                (0.0, T::one())
+            } else {
+                (size / radius, (upper / T::from_real(size)) * lower.conjugate() / T::from_real(radius))
+            };
+            hessenberg[(j, j)] = upper.scale(c) + s * lower;
+            hessenberg[(j + 1, j)] = T::zero();
+            g[j + 1] = -s.conjugate() * g[j];
+            g[j] = g[j].scale(c);
+            rotations.push((c, s));
+
+            steps += 1;
+            residuals.push(g[j + 1].modulus() / b_norm);
+            if residuals[residuals.len() - 1] <= settings.tolerance
+                || residuals.len() > settings.max_iterations
+                || h_next == 0.0
+            {
+                break;
+            }
+            basis.push(w.unscale(h_next));
+        }
+
+        // least squares solution of the rotated system by back substitution
+        let mut y: DVector<T> = g.rows(0, steps).clone_owned();
+        for i in (0..steps).rev() {
+            for k in i + 1..steps {
+                let known: T = hessenberg[(i, k)] * y[k];
+                y[i] -= known;
+            }
+            y[i] /= hessenberg[(i, i)];
+        }
+        let mut update: DVector<T> = DVector::zeros(n);
+        for (v, &coefficient) in basis.iter().zip(y.iter()) {
+            update.axpy(coefficient, v, T::one());
+        }
+        x += precondition(&update);
+    }
+
+    let converged: bool = residuals[residuals.len() - 1] <= settings.tolerance;
+    Ok(IterativeSolution {
+        x,
+        residuals,
+        converged,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use nalgebra::{DMatrix, DVector};
+    use num_complex::Complex;
 
-    use crate::{consts::FREE_SPACE_PERMEABILITY, util::{get_rf_resistance, get_skin_depth, solve_square_matrix}};
+    use crate::{
+        consts::FREE_SPACE_PERMEABILITY,
+        util::{
+            erfcx, get_rf_resistance, get_skin_depth, gmres, solve_square_matrix, GmresSettings, LuFactorization,
+            SolveError,
+        },
+    };
 
     #[test]
     fn test_rf_resistance() {
@@ -116,15 +511,116 @@ mod tests {
             2.0, 6.0, 1.0,
             4.0, 5.0, 1.0,
         ]);
-        let b_matrix: Vec<f64> = vec![
+        let b_matrix: DVector<f64> = DVector::from_vec(vec![
             19.0,
             29.0,
             6.0,
-        ];
-        let x: Vec<f64> = solve_square_matrix(a_matrix, b_matrix);
+        ]);
+        let x: DVector<f64> = solve_square_matrix(a_matrix, b_matrix).unwrap();
 
         assert!((3.0 - x[2]).abs() < 1e-10);
         assert!((2.0 - x[1]).abs() < 1e-10);
         assert!((1.0 - x[0]).abs() < 1e-10);
     }
+
+    #[test]
+    fn test_complex_lu() {
+        let c = |re: f64, im: f64| Complex::new(re, im);
+        // zero in the top left corner, so it only factors with pivoting
+        let a_matrix: DMatrix<Complex<f64>> = DMatrix::from_row_slice(3, 3, &[
+            c(0.0, 0.0), c(2.0, 1.0), c(1.0, -1.0),
+            c(4.0, -2.0), c(1.0, 0.0), c(0.0, 3.0),
+            c(1.0, 1.0), c(-2.0, 0.5), c(5.0, 0.0),
+        ]);
+        let lu: LuFactorization<Complex<f64>> = LuFactorization::new(a_matrix.clone()).unwrap();
+
+        // several right hand sides against the same factors
+        let b_matrix: DMatrix<Complex<f64>> = DMatrix::from_fn(3, 2, |i, j| c(i as f64 + 1.0, j as f64 - 1.0));
+        let x: DMatrix<Complex<f64>> = lu.solve_columns(&b_matrix).unwrap();
+        assert!((&a_matrix * x - &b_matrix).norm() < 1e-12);
+        let det: Complex<f64> = a_matrix.clone().determinant();
+        assert!((lu.determinant() - det).norm() < 1e-12 * det.norm());
+
+        assert_eq!(
+            lu.solve(&DVector::zeros(2)),
+            Err(SolveError::DimensionMismatch { expected: 3, found: 2 })
+        );
+        let singular: DMatrix<f64> = DMatrix::from_row_slice(2, 2, &[1.0, 2.0, 2.0, 4.0]);
+        assert_eq!(LuFactorization::new(singular).unwrap_err(), SolveError::Singular(1));
+    }
+
+    #[test]
+    fn test_condition_estimate() {
+        let scaled: DMatrix<f64> = DMatrix::from_diagonal(&DVector::from_vec(vec![1.0, 1e-6, 2.0]));
+        let condition: f64 = LuFactorization::new(scaled).unwrap().condition_estimate();
+        assert!((condition - 2e6).abs() < 1e-3);
+
+        // the Hilbert matrix is famously ill conditioned
+        let hilbert: DMatrix<f64> = DMatrix::from_fn(6, 6, |i, j| 1.0 / (i + j + 1) as f64);
+        let lu: LuFactorization<f64> = LuFactorization::new(hilbert.clone()).unwrap();
+        let inverse: DMatrix<f64> = lu.solve_columns(&DMatrix::identity(6, 6)).unwrap();
+        let one_norm = |m: &DMatrix<f64>| m.column_iter().map(|c| c.abs().sum()).fold(0.0, f64::max);
+        let exact: f64 = one_norm(&hilbert) * one_norm(&inverse);
+        dbg!(exact, lu.condition_estimate());
+        assert!(lu.condition_estimate() <= exact * (1.0 + 1e-9));
+        assert!(lu.condition_estimate() > 0.1 * exact);
+    }
+
+    /// Blocks of very different scale coupled by a weak dense background, like a model mixing
+    /// thick and thin wires
+    fn blocky_matrix(n: usize, block: usize) -> DMatrix<Complex<f64>> {
+        DMatrix::from_fn(n, n, |i, j| {
+            let scale: f64 = 10f64.powi((i / block) as i32 % 4);
+            let coupling: Complex<f64> = Complex::new((i as f64 * 0.7 + j as f64 * 1.3).sin(), (i * j) as f64 * 0.01);
+            match i / block == j / block {
+                true if i == j => Complex::new(4.0, 1.0) * scale,
+                true => coupling * scale,
+                false => coupling * 0.05,
+            }
+        })
+    }
+
+    #[test]
+    fn test_gmres_matches_lu() {
+        let a_matrix: DMatrix<Complex<f64>> = blocky_matrix(60, 6);
+        let b_matrix: DVector<Complex<f64>> = DVector::from_fn(60, |i, _| Complex::new(1.0, i as f64 * 0.1));
+        let direct: DVector<Complex<f64>> = solve_square_matrix(a_matrix.clone(), b_matrix.clone()).unwrap();
+
+        let settings: GmresSettings = GmresSettings {
+            tolerance: 1e-10,
+            block_size: 6,
+            ..Default::default()
+        };
+        let preconditioned = gmres(&a_matrix, &b_matrix, &settings).unwrap();
+        let plain = gmres(&a_matrix, &b_matrix, &GmresSettings { block_size: 0, ..settings }).unwrap();
+        dbg!(preconditioned.iterations(), plain.iterations());
+
+        assert!(preconditioned.converged && plain.converged);
+        assert!((&preconditioned.x - &direct).norm() < 1e-8 * direct.norm());
+        assert!(preconditioned.iterations() < plain.iterations());
+        // the residual never grows
+        assert!(plain.residuals.windows(2).all(|r| r[1] <= r[0] * (1.0 + 1e-9)));
+
+        let starved = gmres(&a_matrix, &b_matrix, &GmresSettings { max_iterations: 3, block_size: 0, ..settings }).unwrap();
+        assert!(!starved.converged);
+        assert_eq!(starved.iterations(), 3);
+        assert!(starved.residual() > settings.tolerance);
+    }
+
+    #[test]
+    fn test_erfcx() {
+        let cases: [(Complex<f64>, Complex<f64>); 5] = [
+            (Complex::new(0.0, 0.0), Complex::new(1.0, 0.0)),
+            (Complex::new(1.0, 0.0), Complex::new(0.427583576155807, 0.0)),
+            (Complex::new(5.0, 0.0), Complex::new(0.1107046377339686, 0.0)),
+            (Complex::new(0.0, 1.0), Complex::new(0.3678794411714423, -0.6071577058413937)),
+            // e^(-16) erfc(4i) = e^(-16) (1 - i erfi(4))
+            (Complex::new(0.0, 4.0), Complex::new(1.1253517471925912e-7, -0.1459535899800123)),
+        ];
+        for (z, expected) in cases {
+            let value: Complex<f64> = erfcx(z);
+            dbg!(z, value);
+            assert!((value - expected).norm() < 1e-6 * expected.norm());
+        }
+    }
 }